@@ -0,0 +1,75 @@
+use crate::middleware::auth_middleware::{AuthState, AuthUser, auth};
+use crate::middleware::role_middleware::{RequiredRole, require_role};
+use crate::models::admin::{ListUsersRequest, UpdateUserRequest};
+use crate::models::user::UserProfileReply;
+use crate::services::admin_service::AdminService;
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    middleware,
+    routing::{get, post},
+};
+use shared::{
+    error::error::AppError,
+    extract::{validated_json::ValidatedJson, validated_query::ValidatedQuery},
+    pagination::{page::Page, page_request::PageRequest},
+    reply::reply::Reply,
+};
+use std::sync::Arc;
+
+pub fn create_router(
+    service: Arc<AdminService>,
+    auth_state: AuthState,
+    admin: RequiredRole,
+) -> Router {
+    let admin_router = Router::new()
+        .route("/users", get(list_users))
+        .route("/users/{id}", get(get_user).patch(update_user))
+        .route("/users/{id}/logout", post(logout_user))
+        .route_layer(middleware::from_fn_with_state(admin, require_role))
+        .route_layer(middleware::from_fn_with_state(auth_state, auth))
+        .with_state(service);
+
+    Router::new().nest("/admin", admin_router)
+}
+
+pub async fn list_users(
+    State(service): State<Arc<AdminService>>,
+    ValidatedQuery(req): ValidatedQuery<ListUsersRequest>,
+    page: PageRequest,
+) -> Result<Json<Reply<Page<UserProfileReply>>>, AppError> {
+    let reply = service.list_users(req, page).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn get_user(
+    State(service): State<Arc<AdminService>>,
+    Path(id): Path<i64>,
+) -> Result<Json<Reply<UserProfileReply>>, AppError> {
+    let reply = service.get_user(id).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn update_user(
+    State(service): State<Arc<AdminService>>,
+    admin: AuthUser,
+    Path(id): Path<i64>,
+    ValidatedJson(req): ValidatedJson<UpdateUserRequest>,
+) -> Result<Json<Reply<UserProfileReply>>, AppError> {
+    let reply = service.update_user(admin.id, id, req).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+// force-logout, revokes every session of the user
+pub async fn logout_user(
+    State(service): State<Arc<AdminService>>,
+    admin: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.logout_user(admin.id, id).await?;
+
+    Ok(Json(Reply::success(())))
+}
@@ -1,22 +1,32 @@
+use crate::middleware::auth_middleware::{AuthState, AuthUser, auth};
+use crate::models::two_factor::LoginTwoFactorRequest;
 use crate::models::user::{
-    LoginUserReply, LoginUserRequest, RefreshTokenReply, RefreshTokenRequest, RegisterUserRequest,
+    LoginReply, LoginUserReply, LoginUserRequest, LogoutRequest, RefreshTokenReply,
+    RefreshTokenRequest, RegisterUserRequest, ResendVerificationRequest, VerifyEmailRequest,
 };
 use crate::services::user_service::UserService;
-use axum::{
-    Json, Router,
-    extract::{State, rejection::JsonRejection},
-    http::StatusCode,
-    routing::{get, post},
+use axum::{Json, Router, extract::State, middleware, routing::post};
+use shared::{
+    error::error::AppError,
+    extract::{client_device::ClientDevice, validated_json::ValidatedJson},
+    reply::reply::Reply,
 };
-use shared::{constants::constants, reply::reply::Reply};
-use std::{string, sync::Arc};
-use validator::Validate;
+use std::sync::Arc;
+
+pub fn create_router(service: Arc<UserService>, auth_state: AuthState) -> Router {
+    let protected_router = Router::new()
+        .route("/logout-all", post(logout_all))
+        .route_layer(middleware::from_fn_with_state(auth_state, auth));
 
-pub fn create_router(service: Arc<UserService>) -> Router {
     let auth_router = Router::new()
         .route("/register", post(register))
         .route("/login", post(login))
+        .route("/login/2fa", post(login_two_factor))
         .route("/refresh", post(refresh_token))
+        .route("/logout", post(logout))
+        .route("/verify-email", post(verify_email))
+        .route("/resend-verification", post(resend_verification))
+        .merge(protected_router)
         .with_state(service);
 
     Router::new().nest("/auth", auth_router)
@@ -24,84 +34,75 @@ pub fn create_router(service: Arc<UserService>) -> Router {
 
 pub async fn register(
     State(service): State<Arc<UserService>>,
-    payload: Result<Json<RegisterUserRequest>, JsonRejection>,
-) -> Result<Json<Reply<()>>, (StatusCode, Json<Reply<()>>)> {
-    let Json(req) = payload.map_err(|e| {
-        tracing::error!("json error: {}", e);
-        (
-            StatusCode::BAD_REQUEST,
-            Json(Reply::error(constants::CODE_PARAMETER_ERROR)),
-        )
-    })?;
-
-    req.validate().map_err(|e| {
-        tracing::error!("validate error: {}", e);
-        (
-            StatusCode::BAD_REQUEST,
-            Json(Reply::error(constants::CODE_PARAMETER_ERROR)),
-        )
-    })?;
-
-    service
-        .register(req)
-        .await
-        .map_err(|code: u16| (StatusCode::INTERNAL_SERVER_ERROR, Json(Reply::error(code))))?;
+    ValidatedJson(req): ValidatedJson<RegisterUserRequest>,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.register(req).await?;
 
     Ok(Json(Reply::success(())))
 }
 
 pub async fn login(
     State(service): State<Arc<UserService>>,
-    payload: Result<Json<LoginUserRequest>, JsonRejection>,
-) -> Result<Json<Reply<LoginUserReply>>, (StatusCode, Json<Reply<()>>)> {
-    let Json(req) = payload.map_err(|e| {
-        tracing::error!("json error: {}", e);
-        (
-            StatusCode::BAD_REQUEST,
-            Json(Reply::error(constants::CODE_PARAMETER_ERROR)),
-        )
-    })?;
-
-    req.validate().map_err(|e| {
-        tracing::error!("validate error: {}", e);
-        (
-            StatusCode::BAD_REQUEST,
-            Json(Reply::error(constants::CODE_PARAMETER_ERROR)),
-        )
-    })?;
-
-    let reply = service
-        .login(req)
-        .await
-        .map_err(|code: u16| (StatusCode::INTERNAL_SERVER_ERROR, Json(Reply::error(code))))?;
+    device: ClientDevice,
+    ValidatedJson(req): ValidatedJson<LoginUserRequest>,
+) -> Result<Json<Reply<LoginReply>>, AppError> {
+    let reply = service.login(req, device).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn login_two_factor(
+    State(service): State<Arc<UserService>>,
+    device: ClientDevice,
+    ValidatedJson(req): ValidatedJson<LoginTwoFactorRequest>,
+) -> Result<Json<Reply<LoginUserReply>>, AppError> {
+    let reply = service.login_two_factor(req, device).await?;
 
     Ok(Json(Reply::success(reply)))
 }
 
 pub async fn refresh_token(
     State(service): State<Arc<UserService>>,
-    payload: Result<Json<RefreshTokenRequest>, JsonRejection>,
-) -> Result<Json<Reply<RefreshTokenReply>>, (StatusCode, Json<Reply<()>>)> {
-    let Json(req) = payload.map_err(|e| {
-        tracing::error!("json error: {}", e);
-        (
-            StatusCode::BAD_REQUEST,
-            Json(Reply::error(constants::CODE_PARAMETER_ERROR)),
-        )
-    })?;
-
-    req.validate().map_err(|e| {
-        tracing::error!("validate error: {}", e);
-        (
-            StatusCode::BAD_REQUEST,
-            Json(Reply::error(constants::CODE_PARAMETER_ERROR)),
-        )
-    })?;
-
-    let reply = service
-        .refresh_token(req)
-        .await
-        .map_err(|code: u16| (StatusCode::INTERNAL_SERVER_ERROR, Json(Reply::error(code))))?;
+    device: ClientDevice,
+    ValidatedJson(req): ValidatedJson<RefreshTokenRequest>,
+) -> Result<Json<Reply<RefreshTokenReply>>, AppError> {
+    let reply = service.refresh_token(req, device).await?;
 
     Ok(Json(Reply::success(reply)))
 }
+
+pub async fn logout(
+    State(service): State<Arc<UserService>>,
+    ValidatedJson(req): ValidatedJson<LogoutRequest>,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.logout(req).await?;
+
+    Ok(Json(Reply::success(())))
+}
+
+pub async fn logout_all(
+    State(service): State<Arc<UserService>>,
+    user: AuthUser,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.logout_all(user.id).await?;
+
+    Ok(Json(Reply::success(())))
+}
+
+pub async fn verify_email(
+    State(service): State<Arc<UserService>>,
+    ValidatedJson(req): ValidatedJson<VerifyEmailRequest>,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.verify_email(req).await?;
+
+    Ok(Json(Reply::success(())))
+}
+
+pub async fn resend_verification(
+    State(service): State<Arc<UserService>>,
+    ValidatedJson(req): ValidatedJson<ResendVerificationRequest>,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.resend_verification(req).await?;
+
+    Ok(Json(Reply::success(())))
+}
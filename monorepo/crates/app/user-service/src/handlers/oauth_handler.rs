@@ -0,0 +1,154 @@
+use crate::middleware::auth_middleware::{AuthState, AuthUser, auth};
+use crate::middleware::role_middleware::{RequiredRole, require_role};
+use crate::models::oauth::{
+    AuthorizeDecisionReply, AuthorizeDecisionRequest, AuthorizeReply, AuthorizeRequest,
+    ClientCredentials, IntrospectReply, OAuthError, RegisterClientReply, RegisterClientRequest,
+    TokenLookupRequest, TokenRequest, UserInfoReply,
+};
+use crate::services::oauth_service::OAuthService;
+use axum::{
+    Form, Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, header},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use shared::{error::error::AppError, extract::validated_json::ValidatedJson, reply::reply::Reply};
+use std::sync::Arc;
+
+pub fn create_router(
+    service: Arc<OAuthService>,
+    auth_state: AuthState,
+    admin: RequiredRole,
+) -> Router {
+    let client_router = Router::new()
+        .route("/clients", post(register_client))
+        .route_layer(middleware::from_fn_with_state(admin, require_role))
+        .route_layer(middleware::from_fn_with_state(auth_state.clone(), auth));
+
+    // called by our web app on behalf of the signed-in user
+    let consent_router = Router::new()
+        .route("/authorize", get(authorize).post(decide))
+        .route_layer(middleware::from_fn_with_state(auth_state, auth));
+
+    // called by client applications, answers follow RFC 6749 rather than `Reply`
+    let oauth_router = Router::new()
+        .route("/token", post(token))
+        .route("/introspect", post(introspect))
+        .route("/revoke", post(revoke))
+        .route("/userinfo", get(userinfo).post(userinfo))
+        .merge(client_router)
+        .merge(consent_router)
+        .with_state(service);
+
+    Router::new().nest("/oauth", oauth_router)
+}
+
+pub async fn register_client(
+    State(service): State<Arc<OAuthService>>,
+    user: AuthUser,
+    ValidatedJson(req): ValidatedJson<RegisterClientRequest>,
+) -> Result<Json<Reply<RegisterClientReply>>, AppError> {
+    let reply = service.register_client(user.id, req).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn authorize(
+    State(service): State<Arc<OAuthService>>,
+    user: AuthUser,
+    Query(req): Query<AuthorizeRequest>,
+) -> Result<Json<Reply<AuthorizeReply>>, AppError> {
+    let reply = service.authorize(user.id, req).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn decide(
+    State(service): State<Arc<OAuthService>>,
+    user: AuthUser,
+    ValidatedJson(req): ValidatedJson<AuthorizeDecisionRequest>,
+) -> Result<Json<Reply<AuthorizeDecisionReply>>, AppError> {
+    let reply = service.decide(user.id, user.auth_time, req).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn token(
+    State(service): State<Arc<OAuthService>>,
+    headers: HeaderMap,
+    Form(mut req): Form<TokenRequest>,
+) -> Result<Response, OAuthError> {
+    let credentials = client_credentials(&headers, req.client_id.take(), req.client_secret.take());
+    let reply = service.token(credentials, req).await?;
+
+    // tokens must not end up in caches
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(reply)).into_response())
+}
+
+pub async fn introspect(
+    State(service): State<Arc<OAuthService>>,
+    headers: HeaderMap,
+    Form(mut req): Form<TokenLookupRequest>,
+) -> Result<Json<IntrospectReply>, OAuthError> {
+    let credentials = client_credentials(&headers, req.client_id.take(), req.client_secret.take());
+    let reply = service.introspect(credentials, req).await?;
+
+    Ok(Json(reply))
+}
+
+pub async fn revoke(
+    State(service): State<Arc<OAuthService>>,
+    headers: HeaderMap,
+    Form(mut req): Form<TokenLookupRequest>,
+) -> Result<(), OAuthError> {
+    let credentials = client_credentials(&headers, req.client_id.take(), req.client_secret.take());
+    service.revoke(credentials, req).await
+}
+
+// takes the oauth access token, not one of our own session tokens
+pub async fn userinfo(
+    State(service): State<Arc<OAuthService>>,
+    headers: HeaderMap,
+) -> Result<Json<UserInfoReply>, OAuthError> {
+    let access_token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    let reply = service.userinfo(access_token).await?;
+
+    Ok(Json(reply))
+}
+
+// `client_secret_basic` wins over `client_secret_post`, public clients only send `client_id`
+fn client_credentials(
+    headers: &HeaderMap,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+) -> Option<ClientCredentials> {
+    let basic = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|decoded| {
+            decoded
+                .split_once(':')
+                .map(|(id, secret)| (id.to_string(), secret.to_string()))
+        });
+
+    match basic {
+        Some((client_id, client_secret)) => Some(ClientCredentials {
+            client_id,
+            client_secret: Some(client_secret),
+        }),
+        None => client_id.map(|client_id| ClientCredentials {
+            client_id,
+            client_secret,
+        }),
+    }
+}
@@ -0,0 +1,44 @@
+use crate::models::oidc::{OidcAuthorizeReply, OidcCallbackRequest};
+use crate::models::user::LoginReply;
+use crate::services::oidc_service::OidcService;
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    routing::{get, post},
+};
+use shared::{
+    error::error::AppError,
+    extract::{client_device::ClientDevice, validated_json::ValidatedJson},
+    reply::reply::Reply,
+};
+use std::sync::Arc;
+
+pub fn create_router(service: Arc<OidcService>) -> Router {
+    let oidc_router = Router::new()
+        .route("/{provider}/authorize", get(authorize))
+        .route("/{provider}/callback", post(callback))
+        .with_state(service);
+
+    Router::new().nest("/auth/oidc", oidc_router)
+}
+
+pub async fn authorize(
+    State(service): State<Arc<OidcService>>,
+    Path(provider): Path<String>,
+) -> Result<Json<Reply<OidcAuthorizeReply>>, AppError> {
+    let reply = service.authorize(&provider).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+// the web app forwards `code` and `state` from the provider's redirect
+pub async fn callback(
+    State(service): State<Arc<OidcService>>,
+    Path(provider): Path<String>,
+    device: ClientDevice,
+    ValidatedJson(req): ValidatedJson<OidcCallbackRequest>,
+) -> Result<Json<Reply<LoginReply>>, AppError> {
+    let reply = service.callback(&provider, req, device).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
@@ -0,0 +1,70 @@
+use crate::middleware::auth_middleware::{AuthState, AuthUser, auth};
+use crate::models::passkey::{
+    PasskeyLoginOptionsReply, PasskeyLoginOptionsRequest, PasskeyLoginVerifyRequest,
+    PasskeyRegisterOptionsReply, PasskeyRegisterVerifyRequest, PasskeyReply,
+};
+use crate::models::user::LoginUserReply;
+use crate::services::webauthn_service::WebauthnService;
+use axum::{Json, Router, extract::State, middleware, routing::post};
+use shared::{
+    error::error::AppError,
+    extract::{client_device::ClientDevice, validated_json::ValidatedJson},
+    reply::reply::Reply,
+};
+use std::sync::Arc;
+
+pub fn create_router(service: Arc<WebauthnService>, auth_state: AuthState) -> Router {
+    let register_router = Router::new()
+        .route("/options", post(register_options))
+        .route("/verify", post(register_verify))
+        .route_layer(middleware::from_fn_with_state(auth_state, auth))
+        .with_state(service.clone());
+
+    let login_router = Router::new()
+        .route("/options", post(login_options))
+        .route("/verify", post(login_verify))
+        .with_state(service);
+
+    Router::new()
+        .nest("/users/me/passkeys/register", register_router)
+        .nest("/auth/passkey/login", login_router)
+}
+
+pub async fn register_options(
+    State(service): State<Arc<WebauthnService>>,
+    user: AuthUser,
+) -> Result<Json<Reply<PasskeyRegisterOptionsReply>>, AppError> {
+    let reply = service.register_options(user.id).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn register_verify(
+    State(service): State<Arc<WebauthnService>>,
+    user: AuthUser,
+    ValidatedJson(req): ValidatedJson<PasskeyRegisterVerifyRequest>,
+) -> Result<Json<Reply<PasskeyReply>>, AppError> {
+    let reply = service.register_verify(user.id, req).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn login_options(
+    State(service): State<Arc<WebauthnService>>,
+    device: ClientDevice,
+    ValidatedJson(req): ValidatedJson<PasskeyLoginOptionsRequest>,
+) -> Result<Json<Reply<PasskeyLoginOptionsReply>>, AppError> {
+    let reply = service.login_options(req, device).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn login_verify(
+    State(service): State<Arc<WebauthnService>>,
+    device: ClientDevice,
+    ValidatedJson(req): ValidatedJson<PasskeyLoginVerifyRequest>,
+) -> Result<Json<Reply<LoginUserReply>>, AppError> {
+    let reply = service.login_verify(req, device).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
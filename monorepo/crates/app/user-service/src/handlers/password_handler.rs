@@ -0,0 +1,32 @@
+use crate::models::password_reset::{ForgotPasswordRequest, ResetPasswordRequest};
+use crate::services::password_service::PasswordService;
+use axum::{Json, Router, extract::State, routing::post};
+use shared::{error::error::AppError, extract::validated_json::ValidatedJson, reply::reply::Reply};
+use std::sync::Arc;
+
+pub fn create_router(service: Arc<PasswordService>) -> Router {
+    let password_router = Router::new()
+        .route("/forgot", post(forgot_password))
+        .route("/reset", post(reset_password))
+        .with_state(service);
+
+    Router::new().nest("/auth/password", password_router)
+}
+
+pub async fn forgot_password(
+    State(service): State<Arc<PasswordService>>,
+    ValidatedJson(req): ValidatedJson<ForgotPasswordRequest>,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.forgot_password(req).await?;
+
+    Ok(Json(Reply::success(())))
+}
+
+pub async fn reset_password(
+    State(service): State<Arc<PasswordService>>,
+    ValidatedJson(req): ValidatedJson<ResetPasswordRequest>,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.reset_password(req).await?;
+
+    Ok(Json(Reply::success(())))
+}
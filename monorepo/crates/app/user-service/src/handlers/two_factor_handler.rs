@@ -0,0 +1,53 @@
+use crate::middleware::auth_middleware::{AuthState, AuthUser, auth};
+use crate::models::two_factor::{
+    ConfirmTwoFactorRequest, DisableTwoFactorRequest, RecoveryCodesReply, TwoFactorSetupReply,
+};
+use crate::services::two_factor_service::TwoFactorService;
+use axum::{Json, Router, extract::State, middleware, routing::post};
+use shared::{
+    error::error::AppError,
+    extract::{client_device::ClientDevice, validated_json::ValidatedJson},
+    reply::reply::Reply,
+};
+use std::sync::Arc;
+
+pub fn create_router(service: Arc<TwoFactorService>, auth_state: AuthState) -> Router {
+    let two_factor_router = Router::new()
+        .route("/setup", post(setup))
+        .route("/confirm", post(confirm))
+        .route("/disable", post(disable))
+        .route_layer(middleware::from_fn_with_state(auth_state, auth))
+        .with_state(service);
+
+    Router::new().nest("/users/me/2fa", two_factor_router)
+}
+
+pub async fn setup(
+    State(service): State<Arc<TwoFactorService>>,
+    user: AuthUser,
+) -> Result<Json<Reply<TwoFactorSetupReply>>, AppError> {
+    let reply = service.setup(user.id).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn confirm(
+    State(service): State<Arc<TwoFactorService>>,
+    user: AuthUser,
+    ValidatedJson(req): ValidatedJson<ConfirmTwoFactorRequest>,
+) -> Result<Json<Reply<RecoveryCodesReply>>, AppError> {
+    let reply = service.confirm(user.id, req).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn disable(
+    State(service): State<Arc<TwoFactorService>>,
+    user: AuthUser,
+    device: ClientDevice,
+    ValidatedJson(req): ValidatedJson<DisableTwoFactorRequest>,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.disable(user.id, req, device).await?;
+
+    Ok(Json(Reply::success(())))
+}
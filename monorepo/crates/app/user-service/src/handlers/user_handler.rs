@@ -0,0 +1,86 @@
+use crate::middleware::auth_middleware::{AuthState, AuthUser, auth};
+use crate::models::session::SessionReply;
+use crate::models::user::{
+    ChangePasswordRequest, RefreshTokenReply, UpdateProfileRequest, UserProfileReply,
+};
+use crate::services::user_service::UserService;
+use axum::{
+    Json, Router,
+    extract::{Path, State},
+    middleware,
+    routing::{delete, get, post},
+};
+use shared::{
+    error::error::AppError,
+    extract::{client_device::ClientDevice, validated_json::ValidatedJson},
+    pagination::{page::Page, page_request::PageRequest},
+    reply::reply::Reply,
+};
+use std::sync::Arc;
+
+pub fn create_router(service: Arc<UserService>, auth_state: AuthState) -> Router {
+    let user_router = Router::new()
+        .route("/me", get(get_profile).patch(update_profile))
+        .route("/me/password", post(change_password))
+        .route("/me/sessions", get(list_sessions))
+        .route("/me/sessions/{id}", delete(revoke_session))
+        .route_layer(middleware::from_fn_with_state(auth_state, auth))
+        .with_state(service);
+
+    Router::new().nest("/users", user_router)
+}
+
+pub async fn get_profile(
+    State(service): State<Arc<UserService>>,
+    user: AuthUser,
+) -> Result<Json<Reply<UserProfileReply>>, AppError> {
+    let reply = service.get_profile(user.id).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn update_profile(
+    State(service): State<Arc<UserService>>,
+    user: AuthUser,
+    device: ClientDevice,
+    ValidatedJson(req): ValidatedJson<UpdateProfileRequest>,
+) -> Result<Json<Reply<UserProfileReply>>, AppError> {
+    let reply = service.update_profile(user.id, req, device).await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn change_password(
+    State(service): State<Arc<UserService>>,
+    user: AuthUser,
+    device: ClientDevice,
+    ValidatedJson(req): ValidatedJson<ChangePasswordRequest>,
+) -> Result<Json<Reply<RefreshTokenReply>>, AppError> {
+    let reply = service
+        .change_password(user.id, user.session_id, req, device)
+        .await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn list_sessions(
+    State(service): State<Arc<UserService>>,
+    user: AuthUser,
+    page: PageRequest,
+) -> Result<Json<Reply<Page<SessionReply>>>, AppError> {
+    let reply = service
+        .list_sessions(user.id, user.session_id, page)
+        .await?;
+
+    Ok(Json(Reply::success(reply)))
+}
+
+pub async fn revoke_session(
+    State(service): State<Arc<UserService>>,
+    user: AuthUser,
+    Path(id): Path<i64>,
+) -> Result<Json<Reply<()>>, AppError> {
+    service.revoke_session(user.id, id).await?;
+
+    Ok(Json(Reply::success(())))
+}
@@ -0,0 +1,32 @@
+use crate::models::oauth::OpenIdConfiguration;
+use crate::services::{oauth_service::OAuthService, signing_key_service::SigningKeyService};
+use axum::{Json, Router, extract::State, http::header, response::IntoResponse, routing::get};
+use std::sync::Arc;
+
+// verifiers may cache the key set this long, well within the prepublish period
+const JWKS_MAX_AGE: &str = "public, max-age=300";
+
+// served at the root, outside `/api/v1`, where verifiers look for it
+pub fn create_router(keys: Arc<SigningKeyService>, oauth: Arc<OAuthService>) -> Router {
+    let discovery_router = Router::new()
+        .route("/openid-configuration", get(openid_configuration))
+        .with_state(oauth);
+    let well_known_router = Router::new()
+        .route("/jwks.json", get(jwks))
+        .with_state(keys)
+        .merge(discovery_router);
+
+    Router::new().nest("/.well-known", well_known_router)
+}
+
+// RFC 7517 key set, the public keys of the access token signers
+pub async fn jwks(State(keys): State<Arc<SigningKeyService>>) -> impl IntoResponse {
+    ([(header::CACHE_CONTROL, JWKS_MAX_AGE)], Json(keys.jwks()))
+}
+
+// lets OpenID Connect clients find our endpoints from the issuer alone
+pub async fn openid_configuration(
+    State(oauth): State<Arc<OAuthService>>,
+) -> Json<OpenIdConfiguration> {
+    Json(oauth.discovery())
+}
@@ -0,0 +1,81 @@
+// 使用子模块文件的方式
+pub mod handlers {
+    pub mod admin_handler;
+    pub mod auth_handler;
+    pub mod oauth_handler;
+    pub mod oidc_handler;
+    pub mod passkey_handler;
+    pub mod password_handler;
+    pub mod two_factor_handler;
+    pub mod user_handler;
+    pub mod well_known_handler;
+}
+
+pub mod middleware {
+    pub mod auth_middleware;
+    pub mod role_middleware;
+}
+
+pub mod services {
+    pub mod admin_service;
+    pub mod login_guard;
+    pub mod mail_service;
+    pub mod oauth_service;
+    pub mod oidc_service;
+    pub mod password_service;
+    pub mod signing_key_service;
+    pub mod two_factor_service;
+    pub mod user_service;
+    pub mod webauthn_service;
+}
+
+pub mod repositories {
+    pub mod identity_repo;
+    pub mod login_attempt_repo;
+    pub mod memory_login_attempt_repo;
+    #[cfg(test)]
+    pub mod memory_oauth_repo;
+    #[cfg(test)]
+    pub mod memory_refresh_token_repo;
+    #[cfg(test)]
+    pub mod memory_session_repo;
+    #[cfg(test)]
+    pub mod memory_signing_key_repo;
+    #[cfg(test)]
+    pub mod memory_two_factor_repo;
+    #[cfg(test)]
+    pub mod memory_user_repo;
+    pub mod oauth_repo;
+    pub mod passkey_repo;
+    pub mod password_reset_repo;
+    pub mod pg_identity_repo;
+    pub mod pg_login_attempt_repo;
+    pub mod pg_oauth_repo;
+    pub mod pg_passkey_repo;
+    pub mod pg_password_reset_repo;
+    pub mod pg_refresh_token_repo;
+    pub mod pg_session_repo;
+    pub mod pg_signing_key_repo;
+    pub mod pg_two_factor_repo;
+    pub mod pg_user_repo;
+    pub mod refresh_token_repo;
+    pub mod session_repo;
+    pub mod signing_key_repo;
+    pub mod two_factor_repo;
+    pub mod user_repo;
+}
+
+pub mod models {
+    pub mod admin;
+    pub mod claims;
+    pub mod login_attempt;
+    pub mod oauth;
+    pub mod oidc;
+    pub mod passkey;
+    pub mod password_reset;
+    pub mod refresh_token;
+    pub mod session;
+    pub mod signing_key;
+    pub mod two_factor;
+    pub mod user;
+}
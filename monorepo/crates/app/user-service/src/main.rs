@@ -1,34 +1,40 @@
-use axum::Router;
+use axum::{Extension, Router, middleware};
 use idgenerator::*;
-use shared::{config, logger};
+use shared::{
+    config,
+    extract::client_ip::TrustedProxies,
+    i18n::i18n,
+    logger,
+    mail::{mail_queue::MailQueue, mailer, template::MailTemplates},
+    password::policy::PasswordPolicy,
+    rate_limit::rate_limit::{self, RateLimiter},
+};
 use sqlx::{migrate::Migrator, postgres::PgPoolOptions};
+use std::net::SocketAddr;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::handlers::auth_handler;
-use crate::repositories::{pg_user_repo, user_repo};
-use crate::services::user_service;
-use crate::models::claims::JwtSecret;
-
-// 使用子模块文件的方式
-pub mod handlers {
-    pub mod auth_handler;
-}
-
-pub mod services {
-    pub mod user_service;
-}
-
-pub mod repositories {
-    pub mod pg_user_repo;
-    pub mod user_repo;
-}
-
-pub mod models {
-    pub mod claims;
-    pub mod user;
-}
+use user_service::handlers::{
+    admin_handler, auth_handler, oauth_handler, oidc_handler, passkey_handler, password_handler,
+    two_factor_handler, user_handler, well_known_handler,
+};
+use user_service::middleware::auth_middleware::{AuthState, bearer_subject};
+use user_service::middleware::role_middleware::RoleHierarchy;
+use user_service::models::claims::JwtSecret;
+use user_service::repositories::{
+    identity_repo, login_attempt_repo, memory_login_attempt_repo, oauth_repo, passkey_repo,
+    password_reset_repo, pg_identity_repo, pg_login_attempt_repo, pg_oauth_repo, pg_passkey_repo,
+    pg_password_reset_repo, pg_refresh_token_repo, pg_session_repo, pg_signing_key_repo,
+    pg_two_factor_repo, pg_user_repo, refresh_token_repo, session_repo, signing_key_repo,
+    two_factor_repo, user_repo,
+};
+use user_service::services::{
+    admin_service::AdminService, login_guard::LoginGuard, mail_service::MailService,
+    oauth_service::OAuthService, oidc_service::OidcService, password_service::PasswordService,
+    signing_key_service::SigningKeyService, two_factor_service::TwoFactorService,
+    user_service::UserService, webauthn_service::WebauthnService,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -37,20 +43,50 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     logger::logger::init_logging(&config.log.level);
     tracing::info!("Service1 Config: {:?}", config);
 
+    let catalog = i18n::Catalog::load(
+        &config::config::config_dir().join(&config.i18n.dir),
+        &config.i18n.default_locale,
+    )?;
+    i18n::init(catalog);
+
+    let mail_templates =
+        MailTemplates::load(&config::config::config_dir().join(&config.mail.templates_dir))?;
+    let mailer =
+        mailer::create_mailer(&config.mail).map_err(|e| e as Box<dyn std::error::Error>)?;
+    let mail_queue = MailQueue::start(mailer, &config.mail.queue);
+    let mail = Arc::new(MailService::new(
+        mail_queue,
+        mail_templates,
+        config.mail.app_url,
+    ));
+
+    let policy = Arc::new(PasswordPolicy::load(
+        config.password_policy,
+        &config::config::config_dir(),
+    )?);
+
     let port = config.service.port;
     let url = config.database.url;
     let max_connect = config.database.max_connections;
     let timeout = config.database.idle_timeout as u64;
     let work_id = config.service.worker_id;
     let worker_id_bit_len = config.service.worker_id_bit_len;
+    let trusted_proxies = TrustedProxies::new(config.service.trusted_proxies);
 
     let secret = JwtSecret {
-        access_secret: config.jwt.access_secret,
+        issuer: config.jwt.issuer,
+        audience: config.jwt.audience,
+        accept_legacy_tokens: config.jwt.accept_legacy_tokens,
         access_validity_period: config.jwt.access_validity_period,
         refresh_secret: config.jwt.refresh_secret,
         refresh_validity_period: config.jwt.refresh_validity_period,
+        verification_secret: config.jwt.verification_secret,
+        verification_validity_period: config.jwt.verification_validity_period,
+        mfa_secret: config.jwt.mfa_secret,
+        mfa_validity_period: config.jwt.mfa_validity_period,
     };
     let jwt_secret = Arc::new(secret);
+    let signing = config.jwt.signing;
 
     let pool = PgPoolOptions::new()
         .max_connections(max_connect)
@@ -64,24 +100,153 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .worker_id_bit_len(worker_id_bit_len);
     // Initialize the id generator instance with the option.
     // Other options not set will be given the default value.
-    let _ = IdInstance::init(options)?;
+    IdInstance::init(options)?;
 
     let migrator = Migrator::new(Path::new("./migrations")).await?;
     migrator.run(&pool).await?;
 
+    let signing_key_repo: Arc<dyn signing_key_repo::SigningKeyRepo> =
+        Arc::new(pg_signing_key_repo::PgSigningKeyRepo::new(pool.clone()));
+    let keys = Arc::new(
+        SigningKeyService::new(signing_key_repo, signing, jwt_secret.access_validity_period)
+            .await?,
+    );
+    keys.clone().start();
+
     let repo: Arc<dyn user_repo::UserRepo> = Arc::new(pg_user_repo::PgUserRepo::new(pool.clone()));
-    let service = Arc::new(user_service::UserService::new(repo, pool, jwt_secret));
+    let refresh_repo: Arc<dyn refresh_token_repo::RefreshTokenRepo> =
+        Arc::new(pg_refresh_token_repo::PgRefreshTokenRepo::new(pool.clone()));
+    let session_repo: Arc<dyn session_repo::SessionRepo> =
+        Arc::new(pg_session_repo::PgSessionRepo::new(pool.clone()));
+    let oauth_repo: Arc<dyn oauth_repo::OAuthRepo> =
+        Arc::new(pg_oauth_repo::PgOAuthRepo::new(pool.clone()));
+    let reset_repo: Arc<dyn password_reset_repo::PasswordResetRepo> = Arc::new(
+        pg_password_reset_repo::PgPasswordResetRepo::new(pool.clone()),
+    );
+    let attempt_repo: Arc<dyn login_attempt_repo::LoginAttemptRepo> =
+        match config.login_protection.store.as_str() {
+            "postgres" => Arc::new(pg_login_attempt_repo::PgLoginAttemptRepo::new(pool.clone())),
+            "memory" => Arc::new(memory_login_attempt_repo::MemoryLoginAttemptRepo::new()),
+            store => return Err(format!("unknown login attempt store `{}`", store).into()),
+        };
+    let guard = Arc::new(LoginGuard::new(attempt_repo, config.login_protection));
+    let two_factor_repo: Arc<dyn two_factor_repo::TwoFactorRepo> =
+        Arc::new(pg_two_factor_repo::PgTwoFactorRepo::new(pool.clone()));
+    let two_factor = Arc::new(TwoFactorService::new(
+        two_factor_repo,
+        repo.clone(),
+        guard.clone(),
+        config.two_factor,
+    ));
+    let service = Arc::new(UserService::new(
+        repo.clone(),
+        refresh_repo.clone(),
+        session_repo.clone(),
+        oauth_repo.clone(),
+        jwt_secret.clone(),
+        keys.clone(),
+        mail.clone(),
+        policy.clone(),
+        guard.clone(),
+        two_factor.clone(),
+    ));
+    let password_service = Arc::new(PasswordService::new(
+        repo.clone(),
+        refresh_repo,
+        session_repo.clone(),
+        oauth_repo.clone(),
+        reset_repo,
+        mail,
+        policy,
+        config.password_reset.token_validity_period,
+    ));
+    let passkey_repo: Arc<dyn passkey_repo::PasskeyRepo> =
+        Arc::new(pg_passkey_repo::PgPasskeyRepo::new(pool.clone()));
+    let webauthn = Arc::new(WebauthnService::new(
+        passkey_repo,
+        repo.clone(),
+        service.clone(),
+        guard,
+        &config.webauthn,
+    )?);
+    let identity_repo: Arc<dyn identity_repo::IdentityRepo> =
+        Arc::new(pg_identity_repo::PgIdentityRepo::new(pool.clone()));
+    let oidc = Arc::new(OidcService::new(
+        identity_repo,
+        repo.clone(),
+        service.clone(),
+        config.oidc,
+    )?);
+    let oauth = Arc::new(OAuthService::new(
+        oauth_repo,
+        repo.clone(),
+        keys.clone(),
+        config.oauth,
+    ));
+    let roles = Arc::new(RoleHierarchy::new(&config.role));
+    let admin_service = Arc::new(AdminService::new(
+        repo.clone(),
+        service.clone(),
+        roles.clone(),
+    ));
+    let auth_state = AuthState {
+        jwt_secret,
+        keys: keys.clone(),
+        repo,
+        session_repo,
+    };
+    let limiter_state = auth_state.clone();
+    let limiter = Arc::new(
+        RateLimiter::new(config.rate_limit)
+            .with_user_identifier(Arc::new(move |headers| {
+                bearer_subject(&limiter_state, headers)
+            }))
+            .with_trusted_proxies(trusted_proxies.clone()),
+    );
 
     // build our application with a route
-    let auth_router = auth_handler::create_router(service);
+    let auth_router = auth_handler::create_router(service.clone(), auth_state.clone());
+    let user_router = user_handler::create_router(service, auth_state.clone());
+    let two_factor_router = two_factor_handler::create_router(two_factor, auth_state.clone());
+    let passkey_router = passkey_handler::create_router(webauthn, auth_state.clone());
+    let admin_router =
+        admin_handler::create_router(admin_service, auth_state.clone(), roles.require("admin"));
+    let oauth_router =
+        oauth_handler::create_router(oauth.clone(), auth_state, roles.require("admin"));
+    let password_router = password_handler::create_router(password_service);
+    let oidc_router = oidc_handler::create_router(oidc);
+    let well_known_router = well_known_handler::create_router(keys, oauth);
 
     // main router
-    let app = Router::new().nest("/api/v1", auth_router);
+    let app = Router::new()
+        .nest(
+            "/api/v1",
+            auth_router
+                .merge(user_router)
+                .merge(password_router)
+                .merge(two_factor_router)
+                .merge(passkey_router)
+                .merge(oidc_router)
+                .merge(oauth_router)
+                .merge(admin_router),
+        )
+        .merge(well_known_router)
+        .layer(middleware::from_fn_with_state(
+            limiter,
+            rate_limit::rate_limit,
+        ))
+        .layer(middleware::from_fn(i18n::negotiate_locale))
+        .layer(Extension(trusted_proxies));
 
     // run our app with hyper, listening globally on port
     let addr = format!("0.0.0.0:{}", port);
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    // the peer address feeds per-ip login throttling and rate limiting
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
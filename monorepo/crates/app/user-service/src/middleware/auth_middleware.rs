@@ -0,0 +1,283 @@
+use crate::models::claims::{AccessTokenClaims, JwtSecret};
+use crate::repositories::{session_repo::SessionRepo, user_repo::UserRepo};
+use crate::services::signing_key_service::SigningKeyService;
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderMap, header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use jsonwebtoken::{TokenData, errors::ErrorKind};
+use shared::{constants::constants, error::error::AppError, i18n::i18n};
+use std::sync::Arc;
+
+// authenticated principal, injected into request extensions by `auth`
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub id: i64,
+    pub email: String,
+    pub role: String,
+    // unix time the user signed in, survives token refreshes
+    pub auth_time: i64,
+    // sessions.id the token was issued for, legacy tokens have none
+    pub session_id: Option<i64>,
+}
+
+#[derive(Clone)]
+pub struct AuthState {
+    pub jwt_secret: Arc<JwtSecret>,
+    pub keys: Arc<SigningKeyService>,
+    pub repo: Arc<dyn UserRepo>,
+    pub session_repo: Arc<dyn SessionRepo>,
+}
+
+// usage: `router.route_layer(middleware::from_fn_with_state(auth_state, auth))`
+pub async fn auth(
+    State(state): State<AuthState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let token_data = decode_access_token(&state, req.headers())?;
+    let claims = &token_data.claims;
+
+    let user = if claims.is_legacy() {
+        state.repo.find_by_email(&claims.sub).await
+    } else {
+        let user_id: i64 = claims
+            .sub
+            .parse()
+            .map_err(|_| AppError::unauthorized(constants::CODE_UNAUTHORIZED))?;
+        state.repo.find_by_id(user_id).await
+    }
+    .map_err(|e| {
+        tracing::error!("database find user error: {}", e);
+        AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+    })?
+    .ok_or_else(|| AppError::unauthorized(constants::CODE_UNAUTHORIZED))?;
+
+    // logout-all bumps the version, so older tokens are stale
+    if user.token_version != token_data.claims.ver {
+        return Err(AppError::unauthorized(constants::CODE_TOKEN_REVOKED));
+    }
+    if !user.is_active {
+        return Err(AppError::forbidden(constants::CODE_ACCOUNT_DISABLED));
+    }
+
+    // a signed out device loses its access tokens along with its refresh tokens,
+    // families started before sessions were tracked have no row and stay valid
+    let session_id = claims.sid.parse::<i64>().ok();
+    if let Some(session_id) = session_id {
+        let session = state
+            .session_repo
+            .find_by_id(session_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find session error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        if session.is_some_and(|session| session.revoked_at.is_some()) {
+            return Err(AppError::unauthorized(constants::CODE_TOKEN_REVOKED));
+        }
+    }
+
+    req.extensions_mut().insert(AuthUser {
+        id: user.id,
+        email: user.email,
+        role: user.role,
+        // tokens issued before `auth_time` was recorded fall back to their own issue time
+        auth_time: match token_data.claims.auth_time {
+            0 => token_data.claims.iat,
+            auth_time => auth_time,
+        },
+        session_id,
+    });
+
+    // the user's preferred locale wins over Accept-Language
+    match user.locale {
+        Some(locale) => Ok(i18n::scope(locale, next.run(req)).await),
+        None => Ok(next.run(req).await),
+    }
+}
+
+// subject of a valid bearer token, lets the rate limiter key requests by user
+pub fn bearer_subject(state: &AuthState, headers: &HeaderMap) -> Option<String> {
+    decode_access_token(state, headers)
+        .ok()
+        .map(|token_data| token_data.claims.sub)
+}
+
+fn decode_access_token(
+    state: &AuthState,
+    headers: &HeaderMap,
+) -> Result<TokenData<AccessTokenClaims>, AppError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::unauthorized(constants::CODE_UNAUTHORIZED))?;
+
+    // decode & check expiry, issuer and audience, the algorithm is the signing key's
+    let validation = state.jwt_secret.validation();
+    state
+        .keys
+        .verify::<AccessTokenClaims>(token.trim(), &validation)
+        .map_err(|e| {
+            tracing::debug!("jwt decode error: {}", e);
+            match e.kind() {
+                ErrorKind::ExpiredSignature => {
+                    AppError::unauthorized(constants::CODE_TOKEN_EXPIRED)
+                }
+                _ => AppError::unauthorized(constants::CODE_UNAUTHORIZED),
+            }
+        })
+}
+
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthUser>()
+            .cloned()
+            .ok_or_else(|| AppError::unauthorized(constants::CODE_UNAUTHORIZED))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::User;
+    use crate::repositories::{
+        memory_session_repo::MemorySessionRepo, memory_signing_key_repo::MemorySigningKeyRepo,
+        memory_user_repo::MemoryUserRepo,
+    };
+    use axum::{Router, http::StatusCode, middleware, routing::get};
+    use serde::Deserialize;
+    use shared::config::config::JwtSigningConfig;
+    use time::{Duration, OffsetDateTime};
+    use tokio::net::TcpListener;
+
+    const USER_ID: i64 = 42;
+
+    #[derive(Deserialize)]
+    struct ErrorReply {
+        code: u16,
+    }
+
+    async fn state(users: Arc<MemoryUserRepo>) -> AuthState {
+        let keys = SigningKeyService::new(
+            Arc::new(MemorySigningKeyRepo::new()),
+            JwtSigningConfig {
+                algorithm: "EdDSA".to_string(),
+                encryption_key: "d2Db7M7TqkW5b4J23voREksskX28Ppq6XSEG7tsuAr8=".to_string(),
+                legacy_secret: None,
+                rotation_period: 86400,
+                prepublish_period: 3600,
+                retention_period: 7200,
+                refresh_interval_secs: 60,
+            },
+            600,
+        )
+        .await
+        .unwrap();
+
+        AuthState {
+            jwt_secret: Arc::new(JwtSecret {
+                issuer: "http://localhost:8080".to_string(),
+                audience: "web-service".to_string(),
+                accept_legacy_tokens: false,
+                access_validity_period: 600,
+                refresh_secret: "refresh-secret".to_string(),
+                refresh_validity_period: 3600,
+                verification_secret: "verification-secret".to_string(),
+                verification_validity_period: 3600,
+                mfa_secret: "mfa-secret".to_string(),
+                mfa_validity_period: 300,
+            }),
+            keys: Arc::new(keys),
+            repo: users,
+            session_repo: Arc::new(MemorySessionRepo::new()),
+        }
+    }
+
+    fn access_token(state: &AuthState, ver: i32) -> String {
+        let now = OffsetDateTime::now_utc();
+        state
+            .keys
+            .sign(&AccessTokenClaims {
+                iss: state.jwt_secret.issuer.clone(),
+                sub: USER_ID.to_string(),
+                aud: state.jwt_secret.audience.clone(),
+                exp: (now + Duration::minutes(10)).unix_timestamp(),
+                nbf: now.unix_timestamp(),
+                iat: now.unix_timestamp(),
+                jti: "1".to_string(),
+                sid: String::new(),
+                role: "user".to_string(),
+                ver,
+                auth_time: now.unix_timestamp(),
+            })
+            .unwrap()
+    }
+
+    // serves a route behind `auth` and returns its base url
+    async fn serve(state: AuthState) -> String {
+        let app = Router::new()
+            .route(
+                "/me",
+                get(|user: AuthUser| async move { user.id.to_string() }),
+            )
+            .route_layer(middleware::from_fn_with_state(state, auth));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        url
+    }
+
+    async fn get_me(url: &str, token: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!("{}/me", url))
+            .bearer_auth(token)
+            .send()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn stale_token_version_is_rejected() {
+        let users = Arc::new(MemoryUserRepo::new());
+        let now = OffsetDateTime::now_utc();
+        users.insert(User {
+            id: USER_ID,
+            username: "alice".to_string(),
+            email: "alice@x.com".to_string(),
+            password_hash: String::new(),
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+            role: "user".to_string(),
+            token_version: 0,
+            locale: None,
+            email_verified_at: Some(now),
+            pending_email: None,
+        });
+        let state = state(users.clone()).await;
+        let token = access_token(&state, 0);
+        let url = serve(state).await;
+
+        let response = get_me(&url, &token).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), USER_ID.to_string());
+
+        // what logout-all and a password change do
+        users.increment_token_version(USER_ID).await.unwrap();
+        let response = get_me(&url, &token).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let reply = response.json::<ErrorReply>().await.unwrap();
+        assert_eq!(reply.code, constants::CODE_TOKEN_REVOKED);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::middleware::auth_middleware::AuthUser;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use shared::{config::config::RoleConfig, constants::constants, error::error::AppError};
+use std::{collections::HashMap, sync::Arc};
+
+// roles ranked by privilege, a role satisfies every role ranked below it
+#[derive(Debug)]
+pub struct RoleHierarchy {
+    ranks: HashMap<String, usize>,
+}
+
+impl RoleHierarchy {
+    pub fn new(config: &RoleConfig) -> Self {
+        let ranks = config
+            .hierarchy
+            .iter()
+            .enumerate()
+            .map(|(rank, role)| (role.clone(), rank))
+            .collect();
+        RoleHierarchy { ranks }
+    }
+
+    // unknown roles satisfy nothing
+    pub fn satisfies(&self, role: &str, required: &str) -> bool {
+        match (self.ranks.get(role), self.ranks.get(required)) {
+            (Some(rank), Some(required_rank)) => rank >= required_rank,
+            _ => false,
+        }
+    }
+
+    pub fn contains(&self, role: &str) -> bool {
+        self.ranks.contains_key(role)
+    }
+
+    pub fn require(self: &Arc<Self>, role: &str) -> RequiredRole {
+        if !self.ranks.contains_key(role) {
+            tracing::warn!("role `{}` is not part of the configured hierarchy", role);
+        }
+        RequiredRole {
+            hierarchy: self.clone(),
+            role: role.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RequiredRole {
+    hierarchy: Arc<RoleHierarchy>,
+    role: String,
+}
+
+// usage:
+//   router
+//       .route_layer(middleware::from_fn_with_state(roles.require("admin"), require_role))
+//       .route_layer(middleware::from_fn_with_state(auth_state, auth))
+// the auth layer must be added last so it runs first
+pub async fn require_role(
+    State(required): State<RequiredRole>,
+    user: AuthUser,
+    req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !required.hierarchy.satisfies(&user.role, &required.role) {
+        tracing::warn!(
+            "forbidden: {} with role `{}` requires `{}`",
+            user.email,
+            user.role,
+            required.role
+        );
+        return Err(AppError::forbidden(constants::CODE_FORBIDDEN));
+    }
+
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hierarchy() -> RoleHierarchy {
+        RoleHierarchy::new(&RoleConfig {
+            hierarchy: vec![
+                "user".to_string(),
+                "moderator".to_string(),
+                "admin".to_string(),
+            ],
+        })
+    }
+
+    #[test]
+    fn higher_roles_satisfy_lower_ones() {
+        let roles = hierarchy();
+        assert!(roles.satisfies("admin", "admin"));
+        assert!(roles.satisfies("admin", "moderator"));
+        assert!(roles.satisfies("admin", "user"));
+        assert!(roles.satisfies("moderator", "user"));
+        assert!(roles.satisfies("user", "user"));
+    }
+
+    #[test]
+    fn lower_roles_do_not_satisfy_higher_ones() {
+        let roles = hierarchy();
+        assert!(!roles.satisfies("user", "moderator"));
+        assert!(!roles.satisfies("user", "admin"));
+        assert!(!roles.satisfies("moderator", "admin"));
+    }
+
+    #[test]
+    fn unknown_roles_satisfy_nothing() {
+        let roles = hierarchy();
+        assert!(!roles.satisfies("root", "user"));
+        assert!(!roles.satisfies("admin", "root"));
+        assert!(!roles.satisfies("Admin", "user"));
+        assert!(roles.contains("moderator"));
+        assert!(!roles.contains("root"));
+    }
+}
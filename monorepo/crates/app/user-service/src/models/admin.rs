@@ -0,0 +1,41 @@
+use serde::Deserialize;
+use time::OffsetDateTime;
+use validator::Validate;
+
+// `GET /admin/users`, every filter is optional and they combine with AND,
+// `limit`, `offset` and `cursor` are read by `PageRequest`
+#[derive(Debug, Deserialize, Validate)]
+pub struct ListUsersRequest {
+    // substring, case-insensitive
+    #[validate(length(min = 1, max = 255))]
+    pub email: Option<String>,
+    // substring, case-insensitive
+    #[validate(length(min = 1, max = 80))]
+    pub username: Option<String>,
+    #[validate(length(min = 1, max = 20))]
+    pub role: Option<String>,
+    pub is_active: Option<bool>,
+    // unix time, inclusive
+    pub created_from: Option<i64>,
+    // unix time, exclusive
+    pub created_to: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct UserFilter {
+    pub email: Option<String>,
+    pub username: Option<String>,
+    pub role: Option<String>,
+    pub is_active: Option<bool>,
+    pub created_from: Option<OffsetDateTime>,
+    pub created_to: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateUserRequest {
+    // one of `role.hierarchy`
+    #[validate(length(min = 1, max = 20))]
+    pub role: Option<String>,
+    // deactivating also signs the user out everywhere
+    pub is_active: Option<bool>,
+}
@@ -1,23 +1,122 @@
+use crate::models::oauth::UserInfoReply;
+use jsonwebtoken::{Algorithm, Validation};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+// tokens issued before `iss` was added carry the email as `sub`,
+// they are accepted only while `jwt.accept_legacy_tokens` is on
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccessTokenClaims {
-    pub sub: String,  // email
-    pub exp: i64,     // exp
-    pub iat: i64,     // iat
+    #[serde(default)]
+    pub iss: String, // jwt.issuer
+    pub sub: String, // users.id
+    #[serde(default)]
+    pub aud: String, // jwt.audience
+    pub exp: i64,    // exp
+    #[serde(default)]
+    pub nbf: i64, // nbf
+    pub iat: i64,    // iat
+    #[serde(default)]
+    pub jti: String, // unique per token
+    #[serde(default)]
+    pub sid: String, // refresh_tokens.family_id, the login session
     pub role: String, // role
+    #[serde(default)]
+    pub ver: i32, // users.token_version
+    #[serde(default)]
+    pub auth_time: i64, // when the user signed in
+}
+
+impl AccessTokenClaims {
+    pub fn is_legacy(&self) -> bool {
+        self.iss.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RefreshTokenClaims {
-    pub sub: String,        // email
-    pub exp: i64,           // exp
+    #[serde(default)]
+    pub iss: String, // jwt.issuer
+    pub sub: String, // users.id
+    #[serde(default)]
+    pub aud: String, // jwt.audience
+    pub exp: i64,    // exp
+    #[serde(default)]
+    pub nbf: i64, // nbf
+    #[serde(default)]
+    pub iat: i64, // iat
     pub token_type: String, // refresh token
+    pub jti: String, // refresh_tokens.id
+    #[serde(default)]
+    pub sid: String, // refresh_tokens.family_id
+    #[serde(default)]
+    pub auth_time: i64, // when the user signed in
+}
+
+impl RefreshTokenClaims {
+    pub fn is_legacy(&self) -> bool {
+        self.iss.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationTokenClaims {
+    pub sub: String,        // users.id
+    pub email: String,      // the address being verified
+    pub exp: i64,           // exp
+    pub token_type: String, // verify_email
+}
+
+// proves the password step of a login, exchanged at /auth/login/2fa
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaTokenClaims {
+    pub sub: String,        // users.id
+    pub exp: i64,           // exp
+    pub token_type: String, // mfa
+    pub ver: i32,           // users.token_version
 }
 
+// OpenID Connect ID token handed to oauth clients, signed with the access token keys
+#[derive(Debug, Serialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub aud: String, // oauth_clients.client_id
+    pub exp: i64,
+    pub iat: i64,
+    pub auth_time: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    // `sub` and the claims the granted scopes allow
+    #[serde(flatten)]
+    pub profile: UserInfoReply,
+}
+
+// access tokens are signed by `SigningKeyService` instead
 pub struct JwtSecret {
-    pub access_secret: String,
+    pub issuer: String,
+    pub audience: String,
+    pub accept_legacy_tokens: bool,
     pub access_validity_period: i64,
     pub refresh_secret: String,
     pub refresh_validity_period: i64,
+    pub verification_secret: String,
+    pub verification_validity_period: i64,
+    pub mfa_secret: String,
+    pub mfa_validity_period: i64,
+}
+
+impl JwtSecret {
+    // checks `iss`, `aud` and `nbf` of access and refresh tokens, as HS256 for refresh tokens,
+    // legacy tokens lack them, so they are only required once legacy tokens are refused
+    pub fn validation(&self) -> Validation {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[&self.audience]);
+        validation.validate_nbf = true;
+        if self.accept_legacy_tokens {
+            validation.set_required_spec_claims(&["exp", "sub"]);
+        } else {
+            validation.set_required_spec_claims(&["exp", "sub", "iss", "aud", "nbf"]);
+        }
+        validation
+    }
 }
@@ -0,0 +1,11 @@
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+// failed logins counted against one account or client ip
+#[derive(Debug, Clone, FromRow)]
+pub struct LoginAttempt {
+    pub key: String,
+    pub failures: i32,
+    pub last_failure_at: OffsetDateTime,
+    pub locked_until: Option<OffsetDateTime>,
+}
@@ -0,0 +1,325 @@
+use axum::{
+    Json,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+use shared::error::error::AppError;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use validator::Validate;
+
+pub const GRANT_AUTHORIZATION_CODE: &str = "authorization_code";
+pub const GRANT_CLIENT_CREDENTIALS: &str = "client_credentials";
+pub const GRANT_REFRESH_TOKEN: &str = "refresh_token";
+
+// OpenID Connect scopes, `openid` turns an authorization into an authentication
+pub const SCOPE_OPENID: &str = "openid";
+pub const SCOPE_EMAIL: &str = "email";
+pub const SCOPE_PROFILE: &str = "profile";
+
+pub const TOKEN_TYPE_ACCESS: &str = "access";
+pub const TOKEN_TYPE_REFRESH: &str = "refresh";
+
+#[derive(Debug, Clone, FromRow)]
+pub struct OAuthClient {
+    pub id: i64,
+    pub client_id: String,
+    // public clients have no secret and must use PKCE
+    pub client_secret_hash: Option<String>,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub created_by: Option<i64>,
+    pub created_at: OffsetDateTime,
+}
+
+impl OAuthClient {
+    pub fn is_confidential(&self) -> bool {
+        self.client_secret_hash.is_some()
+    }
+
+    pub fn allows_grant(&self, grant_type: &str) -> bool {
+        self.grant_types.iter().any(|allowed| allowed == grant_type)
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct AuthorizationCode {
+    pub code_hash: String,
+    pub grant_id: i64,
+    pub client_id: i64,
+    pub user_id: i64,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub code_challenge: Option<String>,
+    // copied into the ID token
+    pub nonce: Option<String>,
+    pub auth_time: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub used_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+// opaque access and refresh tokens, resource servers look them up through introspection
+#[derive(Debug, Clone, FromRow)]
+pub struct OAuthToken {
+    pub id: i64,
+    pub token_hash: String,
+    pub token_type: String,
+    pub grant_id: i64,
+    pub client_id: i64,
+    pub user_id: Option<i64>,
+    pub scope: String,
+    pub expires_at: OffsetDateTime,
+    pub used_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RegisterClientRequest {
+    #[validate(length(min = 1, max = 128))]
+    pub name: String,
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    #[validate(length(min = 1))]
+    pub grant_types: Vec<String>,
+    #[validate(length(min = 1))]
+    pub scopes: Vec<String>,
+    // false for browser and native apps, which cannot keep a secret
+    pub confidential: bool,
+}
+
+// the secret is only ever shown here
+#[derive(Debug, Serialize)]
+pub struct RegisterClientReply {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+}
+
+// query of `GET /oauth/authorize`, as sent by the client application
+#[derive(Debug, Deserialize, Validate)]
+pub struct AuthorizeRequest {
+    pub response_type: String,
+    #[validate(length(min = 1))]
+    pub client_id: String,
+    #[validate(length(min = 1))]
+    pub redirect_uri: String,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    // OpenID Connect, echoed in the ID token to bind it to the client's session
+    #[validate(length(max = 255))]
+    pub nonce: Option<String>,
+}
+
+// what the consent screen shows
+#[derive(Debug, Serialize)]
+pub struct AuthorizeReply {
+    pub client_id: String,
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    // false when the user already agreed to these scopes
+    pub consent_required: bool,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AuthorizeDecisionRequest {
+    #[serde(flatten)]
+    #[validate(nested)]
+    pub request: AuthorizeRequest,
+    pub approve: bool,
+}
+
+// the browser is sent here, carrying either `code` or `error`
+#[derive(Debug, Serialize)]
+pub struct AuthorizeDecisionReply {
+    pub redirect_to: String,
+}
+
+// credentials from the `Authorization: Basic` header or the form body
+#[derive(Debug)]
+pub struct ClientCredentials {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenReply {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub scope: String,
+    // when `openid` was granted through an authorization code
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+// body of both introspection (RFC 7662) and revocation (RFC 7009)
+#[derive(Debug, Deserialize)]
+pub struct TokenLookupRequest {
+    pub token: String,
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct IntrospectReply {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+}
+
+// OpenID Connect standard claims of a user, limited to the granted scopes
+#[derive(Debug, Serialize)]
+pub struct UserInfoReply {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<i64>,
+}
+
+// `/.well-known/openid-configuration`, OpenID Connect Discovery 1.0
+#[derive(Debug, Serialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: Vec<&'static str>,
+    pub grant_types_supported: Vec<&'static str>,
+    pub subject_types_supported: Vec<&'static str>,
+    pub id_token_signing_alg_values_supported: Vec<Algorithm>,
+    pub token_endpoint_auth_methods_supported: Vec<&'static str>,
+    pub code_challenge_methods_supported: Vec<&'static str>,
+    pub claims_supported: Vec<&'static str>,
+}
+
+// RFC 6749 section 5.2 error, the token endpoints answer with these instead of `Reply`
+#[derive(Debug, Serialize)]
+pub struct OAuthError {
+    pub error: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+}
+
+impl OAuthError {
+    pub fn new(error: &'static str) -> Self {
+        OAuthError {
+            error,
+            error_description: None,
+        }
+    }
+
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.error_description = Some(description.into());
+        self
+    }
+
+    pub fn invalid_request(description: impl Into<String>) -> Self {
+        OAuthError::new("invalid_request").with_description(description)
+    }
+
+    pub fn invalid_client() -> Self {
+        OAuthError::new("invalid_client")
+    }
+
+    pub fn invalid_grant() -> Self {
+        OAuthError::new("invalid_grant")
+    }
+
+    pub fn server_error() -> Self {
+        OAuthError::new("server_error")
+    }
+
+    // RFC 6750, for requests made with an access token
+    pub fn invalid_token() -> Self {
+        OAuthError::new("invalid_token")
+    }
+
+    pub fn insufficient_scope() -> Self {
+        OAuthError::new("insufficient_scope")
+    }
+}
+
+impl From<AppError> for OAuthError {
+    fn from(error: AppError) -> Self {
+        match error {
+            AppError::Internal { .. } => OAuthError::server_error(),
+            _ => OAuthError::new("invalid_request"),
+        }
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let (status, challenge) = match self.error {
+            "invalid_client" => (StatusCode::UNAUTHORIZED, Some("Basic realm=\"oauth\"")),
+            "invalid_token" => (
+                StatusCode::UNAUTHORIZED,
+                Some("Bearer error=\"invalid_token\""),
+            ),
+            "insufficient_scope" => (
+                StatusCode::FORBIDDEN,
+                Some("Bearer error=\"insufficient_scope\", scope=\"openid\""),
+            ),
+            "server_error" => (StatusCode::INTERNAL_SERVER_ERROR, None),
+            _ => (StatusCode::BAD_REQUEST, None),
+        };
+
+        let mut response = (status, Json(self)).into_response();
+        if let Some(challenge) = challenge {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                header::HeaderValue::from_static(challenge),
+            );
+        }
+        response
+    }
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use validator::Validate;
+
+// links the subject of an identity provider to a local account
+#[derive(Debug, FromRow)]
+pub struct UserIdentity {
+    pub id: i64,
+    pub user_id: i64,
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub last_login_at: Option<OffsetDateTime>,
+}
+
+// the browser is sent to `authorization_url`, the provider redirects back with `code` and `state`
+#[derive(Debug, Serialize)]
+pub struct OidcAuthorizeReply {
+    pub authorization_url: String,
+    pub state: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct OidcCallbackRequest {
+    #[validate(length(min = 1))]
+    pub state: String,
+    #[validate(length(min = 1))]
+    pub code: String,
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, types::Json};
+use time::OffsetDateTime;
+use validator::Validate;
+use webauthn_rs::prelude::{
+    CreationChallengeResponse, Passkey, PublicKeyCredential, RegisterPublicKeyCredential,
+    RequestChallengeResponse,
+};
+
+#[derive(Debug, FromRow)]
+pub struct PasskeyCredential {
+    pub id: i64,
+    pub user_id: i64,
+    pub credential_id: Vec<u8>,
+    pub name: String,
+    pub passkey: Json<Passkey>,
+    pub sign_count: i64,
+    pub created_at: OffsetDateTime,
+    pub last_used_at: Option<OffsetDateTime>,
+}
+
+// `options` is handed to navigator.credentials.create() as is
+#[derive(Debug, Serialize)]
+pub struct PasskeyRegisterOptionsReply {
+    pub ceremony_id: String,
+    pub options: CreationChallengeResponse,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PasskeyRegisterVerifyRequest {
+    #[validate(length(min = 1))]
+    pub ceremony_id: String,
+    #[validate(length(min = 1, max = 64))]
+    pub name: Option<String>,
+    pub credential: RegisterPublicKeyCredential,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasskeyReply {
+    pub id: i64,
+    pub name: String,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+}
+
+impl From<PasskeyCredential> for PasskeyReply {
+    fn from(credential: PasskeyCredential) -> Self {
+        PasskeyReply {
+            id: credential.id,
+            name: credential.name,
+            created_at: credential.created_at.unix_timestamp(),
+            last_used_at: credential.last_used_at.map(|at| at.unix_timestamp()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PasskeyLoginOptionsRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+// `options` is handed to navigator.credentials.get() as is
+#[derive(Debug, Serialize)]
+pub struct PasskeyLoginOptionsReply {
+    pub ceremony_id: String,
+    pub options: RequestChallengeResponse,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PasskeyLoginVerifyRequest {
+    #[validate(length(min = 1))]
+    pub ceremony_id: String,
+    pub credential: PublicKeyCredential,
+}
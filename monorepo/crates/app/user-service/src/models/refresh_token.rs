@@ -0,0 +1,13 @@
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct RefreshToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub family_id: i64,
+    pub expires_at: OffsetDateTime,
+    pub used_at: Option<OffsetDateTime>,
+    pub revoked_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
@@ -0,0 +1,39 @@
+use serde::Serialize;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+// one per login, `id` is the refresh token family the login started
+#[derive(Debug, Clone, FromRow)]
+pub struct Session {
+    pub id: i64,
+    pub user_id: i64,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub last_seen_at: OffsetDateTime,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SessionReply {
+    pub id: i64,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    // the session the request was made with
+    pub current: bool,
+}
+
+impl SessionReply {
+    pub fn new(session: Session, current_id: Option<i64>) -> Self {
+        SessionReply {
+            id: session.id,
+            user_agent: session.user_agent,
+            ip: session.ip,
+            created_at: session.created_at.unix_timestamp(),
+            last_seen_at: session.last_seen_at.unix_timestamp(),
+            current: Some(session.id) == current_id,
+        }
+    }
+}
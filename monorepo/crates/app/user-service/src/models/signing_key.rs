@@ -0,0 +1,15 @@
+use jsonwebtoken::jwk::Jwk;
+use sqlx::{FromRow, types::Json};
+use time::OffsetDateTime;
+
+// a key pair access tokens are signed with, the newest active one signs
+#[derive(Debug, Clone, FromRow)]
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: String,
+    // PKCS#8 PEM sealed with `jwt.signing.encryption_key`, never leaves the service
+    pub private_key: String,
+    pub public_jwk: Json<Jwk>,
+    pub activates_at: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use validator::Validate;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct UserTotp {
+    pub user_id: i64,
+    pub secret: String,
+    pub enabled_at: Option<OffsetDateTime>,
+    pub last_used_step: i64,
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorSetupReply {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ConfirmTwoFactorRequest {
+    #[validate(length(min = 6, max = 6))]
+    pub code: String,
+}
+
+// shown once, only their digests are stored
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodesReply {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct DisableTwoFactorRequest {
+    #[validate(length(min = 1))]
+    pub password: String,
+    // a current TOTP code or an unused recovery code
+    #[validate(length(min = 6, max = 32))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct LoginTwoFactorRequest {
+    #[validate(length(min = 1))]
+    pub mfa_token: String,
+    // a current TOTP code or an unused recovery code
+    #[validate(length(min = 6, max = 32))]
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MfaChallengeReply {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub mfa_expire_time: i64,
+}
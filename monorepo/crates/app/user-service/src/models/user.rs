@@ -1,9 +1,10 @@
+use crate::models::two_factor::MfaChallengeReply;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use time::OffsetDateTime;
 use validator::Validate;
 
-#[derive(Debug, FromRow)]
+#[derive(Debug, Clone, FromRow)]
 pub struct User {
     pub id: i64,
     pub username: String,
@@ -13,6 +14,11 @@ pub struct User {
     pub updated_at: OffsetDateTime,
     pub is_active: bool,
     pub role: String,
+    pub token_version: i32,
+    pub locale: Option<String>,
+    pub email_verified_at: Option<OffsetDateTime>,
+    // requested new address, replaces `email` once its verification link is opened
+    pub pending_email: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -21,7 +27,7 @@ pub struct RegisterUserRequest {
     pub username: String,
     #[validate(email)]
     pub email: String,
-    #[validate(length(min = 6))]
+    // checked against the configured password policy
     pub password: String,
 }
 
@@ -50,8 +56,87 @@ pub struct LoginUserReply {
     pub refresh_expire_time: i64,
 }
 
+// accounts with 2fa get a challenge instead of tokens
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginReply {
+    Tokens(LoginUserReply),
+    MfaChallenge(MfaChallengeReply),
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyEmailRequest {
+    #[validate(length(min = 1))]
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResendVerificationRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct LogoutRequest {
+    #[validate(length(min = 1))]
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateProfileRequest {
+    #[validate(length(min = 1, max = 80))]
+    pub username: Option<String>,
+    #[validate(email)]
+    pub email: Option<String>,
+    // required when changing the email, which takes effect once the new address is verified
+    pub current_password: Option<String>,
+    #[validate(length(min = 2, max = 16))]
+    pub locale: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    #[validate(length(min = 1))]
+    pub current_password: String,
+    // checked against the configured password policy
+    pub new_password: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct RefreshTokenReply {
     pub access_token: String,
+    pub refresh_token: String,
     pub access_expire_time: i64,
+    pub refresh_expire_time: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserProfileReply {
+    pub id: i64,
+    pub username: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub pending_email: Option<String>,
+    pub role: String,
+    pub is_active: bool,
+    pub locale: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl From<User> for UserProfileReply {
+    fn from(user: User) -> Self {
+        UserProfileReply {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            email_verified: user.email_verified_at.is_some(),
+            pending_email: user.pending_email,
+            role: user.role,
+            is_active: user.is_active,
+            locale: user.locale,
+            created_at: user.created_at.unix_timestamp(),
+            updated_at: user.updated_at.unix_timestamp(),
+        }
+    }
 }
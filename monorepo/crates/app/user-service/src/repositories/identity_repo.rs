@@ -0,0 +1,21 @@
+use crate::models::oidc::UserIdentity;
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait IdentityRepo: Send + Sync {
+    async fn find(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<UserIdentity>, sqlx::Error>;
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        provider: &str,
+        subject: &str,
+        email: Option<&str>,
+    ) -> Result<(), sqlx::Error>;
+    // keeps the email the provider reported last
+    async fn record_login(&self, id: i64, email: Option<&str>) -> Result<(), sqlx::Error>;
+}
@@ -0,0 +1,17 @@
+use crate::models::login_attempt::LoginAttempt;
+use async_trait::async_trait;
+use time::OffsetDateTime;
+
+#[async_trait]
+pub trait LoginAttemptRepo: Send + Sync {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempt>, sqlx::Error>;
+    // counts a failure, restarting from one when the last failure is older than `stale_before`
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: OffsetDateTime,
+        stale_before: OffsetDateTime,
+    ) -> Result<LoginAttempt, sqlx::Error>;
+    async fn lock(&self, key: &str, until: OffsetDateTime) -> Result<(), sqlx::Error>;
+    async fn clear(&self, key: &str) -> Result<(), sqlx::Error>;
+}
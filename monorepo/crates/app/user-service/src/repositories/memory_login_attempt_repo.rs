@@ -0,0 +1,74 @@
+use crate::models::login_attempt::LoginAttempt;
+use crate::repositories::login_attempt_repo::LoginAttemptRepo;
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Mutex};
+use time::OffsetDateTime;
+
+// entries beyond this trigger a sweep of the stale ones
+const SWEEP_THRESHOLD: usize = 10_000;
+
+// per-process counters, each instance locks independently
+#[derive(Default)]
+pub struct MemoryLoginAttemptRepo {
+    attempts: Mutex<HashMap<String, LoginAttempt>>,
+}
+
+impl MemoryLoginAttemptRepo {
+    pub fn new() -> Self {
+        MemoryLoginAttemptRepo::default()
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepo for MemoryLoginAttemptRepo {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempt>, sqlx::Error> {
+        let attempts = self.attempts.lock().unwrap();
+        Ok(attempts.get(key).cloned())
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: OffsetDateTime,
+        stale_before: OffsetDateTime,
+    ) -> Result<LoginAttempt, sqlx::Error> {
+        let mut attempts = self.attempts.lock().unwrap();
+
+        if attempts.len() >= SWEEP_THRESHOLD {
+            attempts.retain(|_, attempt| {
+                attempt.last_failure_at >= stale_before
+                    || attempt.locked_until.is_some_and(|until| until > now)
+            });
+        }
+
+        let attempt = attempts
+            .entry(key.to_string())
+            .and_modify(|attempt| {
+                if attempt.last_failure_at < stale_before {
+                    attempt.failures = 0;
+                }
+                attempt.failures += 1;
+                attempt.last_failure_at = now;
+            })
+            .or_insert_with(|| LoginAttempt {
+                key: key.to_string(),
+                failures: 1,
+                last_failure_at: now,
+                locked_until: None,
+            });
+        Ok(attempt.clone())
+    }
+
+    async fn lock(&self, key: &str, until: OffsetDateTime) -> Result<(), sqlx::Error> {
+        let mut attempts = self.attempts.lock().unwrap();
+        if let Some(attempt) = attempts.get_mut(key) {
+            attempt.locked_until = Some(until);
+        }
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), sqlx::Error> {
+        self.attempts.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
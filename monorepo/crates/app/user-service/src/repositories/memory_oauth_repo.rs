@@ -0,0 +1,141 @@
+use crate::models::oauth::{AuthorizationCode, OAuthClient, OAuthToken};
+use crate::repositories::oauth_repo::OAuthRepo;
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Mutex};
+use time::OffsetDateTime;
+
+#[derive(Default)]
+pub struct MemoryOAuthRepo {
+    clients: Mutex<Vec<OAuthClient>>,
+    // (user_id, client_id) -> scope
+    consents: Mutex<HashMap<(i64, i64), String>>,
+    codes: Mutex<HashMap<String, AuthorizationCode>>,
+    tokens: Mutex<Vec<OAuthToken>>,
+}
+
+impl MemoryOAuthRepo {
+    pub fn new() -> Self {
+        MemoryOAuthRepo::default()
+    }
+
+    fn revoke_where(&self, matches: impl Fn(&OAuthToken) -> bool) -> u64 {
+        let now = OffsetDateTime::now_utc();
+        let mut revoked = 0;
+        for token in self.tokens.lock().unwrap().iter_mut() {
+            if token.revoked_at.is_none() && matches(token) {
+                token.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        revoked
+    }
+}
+
+#[async_trait]
+impl OAuthRepo for MemoryOAuthRepo {
+    async fn create_client(&self, client: &OAuthClient) -> Result<(), sqlx::Error> {
+        self.clients.lock().unwrap().push(client.clone());
+        Ok(())
+    }
+
+    async fn find_client(&self, client_id: &str) -> Result<Option<OAuthClient>, sqlx::Error> {
+        let clients = self.clients.lock().unwrap();
+        Ok(clients
+            .iter()
+            .find(|client| client.client_id == client_id)
+            .cloned())
+    }
+
+    async fn find_client_by_id(&self, id: i64) -> Result<Option<OAuthClient>, sqlx::Error> {
+        let clients = self.clients.lock().unwrap();
+        Ok(clients.iter().find(|client| client.id == id).cloned())
+    }
+
+    async fn find_consent(
+        &self,
+        user_id: i64,
+        client_id: i64,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let consents = self.consents.lock().unwrap();
+        Ok(consents.get(&(user_id, client_id)).cloned())
+    }
+
+    async fn save_consent(
+        &self,
+        user_id: i64,
+        client_id: i64,
+        scope: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut consents = self.consents.lock().unwrap();
+        consents.insert((user_id, client_id), scope.to_string());
+        Ok(())
+    }
+
+    async fn create_code(&self, code: &AuthorizationCode) -> Result<(), sqlx::Error> {
+        let mut codes = self.codes.lock().unwrap();
+        codes.insert(code.code_hash.clone(), code.clone());
+        Ok(())
+    }
+
+    async fn consume_code(
+        &self,
+        code_hash: &str,
+    ) -> Result<Option<AuthorizationCode>, sqlx::Error> {
+        let mut codes = self.codes.lock().unwrap();
+        Ok(codes
+            .get_mut(code_hash)
+            .filter(|code| code.used_at.is_none())
+            .map(|code| {
+                code.used_at = Some(OffsetDateTime::now_utc());
+                code.clone()
+            }))
+    }
+
+    async fn find_code(&self, code_hash: &str) -> Result<Option<AuthorizationCode>, sqlx::Error> {
+        Ok(self.codes.lock().unwrap().get(code_hash).cloned())
+    }
+
+    async fn create_token(&self, token: &OAuthToken) -> Result<(), sqlx::Error> {
+        self.tokens.lock().unwrap().push(token.clone());
+        Ok(())
+    }
+
+    async fn find_token(&self, token_hash: &str) -> Result<Option<OAuthToken>, sqlx::Error> {
+        let tokens = self.tokens.lock().unwrap();
+        Ok(tokens
+            .iter()
+            .find(|token| token.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn mark_token_used(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tokens = self.tokens.lock().unwrap();
+        match tokens.iter_mut().find(|token| token.id == id) {
+            Some(token) if token.used_at.is_none() => {
+                token.used_at = Some(OffsetDateTime::now_utc());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_token(&self, id: i64) -> Result<(), sqlx::Error> {
+        self.revoke_where(|token| token.id == id);
+        Ok(())
+    }
+
+    async fn revoke_grant(&self, grant_id: i64) -> Result<(), sqlx::Error> {
+        self.revoke_where(|token| token.grant_id == grant_id);
+        Ok(())
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        let now = OffsetDateTime::now_utc();
+        for code in self.codes.lock().unwrap().values_mut() {
+            if code.user_id == user_id && code.used_at.is_none() {
+                code.used_at = Some(now);
+            }
+        }
+        Ok(self.revoke_where(|token| token.user_id == Some(user_id)))
+    }
+}
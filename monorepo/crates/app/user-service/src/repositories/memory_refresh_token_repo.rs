@@ -0,0 +1,74 @@
+use crate::models::refresh_token::RefreshToken;
+use crate::repositories::refresh_token_repo::RefreshTokenRepo;
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Mutex};
+use time::OffsetDateTime;
+
+#[derive(Default)]
+pub struct MemoryRefreshTokenRepo {
+    tokens: Mutex<HashMap<i64, RefreshToken>>,
+}
+
+impl MemoryRefreshTokenRepo {
+    pub fn new() -> Self {
+        MemoryRefreshTokenRepo::default()
+    }
+
+    fn revoke_where(&self, matches: impl Fn(&RefreshToken) -> bool) -> u64 {
+        let now = OffsetDateTime::now_utc();
+        let mut revoked = 0;
+        for token in self.tokens.lock().unwrap().values_mut() {
+            if token.revoked_at.is_none() && matches(token) {
+                token.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        revoked
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepo for MemoryRefreshTokenRepo {
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        family_id: i64,
+        expires_at: OffsetDateTime,
+    ) -> Result<(), sqlx::Error> {
+        let token = RefreshToken {
+            id,
+            user_id,
+            family_id,
+            expires_at,
+            used_at: None,
+            revoked_at: None,
+            created_at: OffsetDateTime::now_utc(),
+        };
+        self.tokens.lock().unwrap().insert(id, token);
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: i64) -> Result<Option<RefreshToken>, sqlx::Error> {
+        Ok(self.tokens.lock().unwrap().get(&id).cloned())
+    }
+
+    async fn mark_used(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tokens = self.tokens.lock().unwrap();
+        match tokens.get_mut(&id) {
+            Some(token) if token.used_at.is_none() && token.revoked_at.is_none() => {
+                token.used_at = Some(OffsetDateTime::now_utc());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke_family(&self, family_id: i64) -> Result<u64, sqlx::Error> {
+        Ok(self.revoke_where(|token| token.family_id == family_id))
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        Ok(self.revoke_where(|token| token.user_id == user_id))
+    }
+}
@@ -0,0 +1,116 @@
+use crate::models::session::Session;
+use crate::repositories::session_repo::SessionRepo;
+use async_trait::async_trait;
+use shared::pagination::page_request::PageRequest;
+use std::{collections::HashMap, sync::Mutex};
+use time::OffsetDateTime;
+
+// sessions are listed without looking at their refresh tokens, unlike the postgres repo
+#[derive(Default)]
+pub struct MemorySessionRepo {
+    sessions: Mutex<HashMap<i64, Session>>,
+}
+
+impl MemorySessionRepo {
+    pub fn new() -> Self {
+        MemorySessionRepo::default()
+    }
+
+    fn revoke_where(&self, matches: impl Fn(&Session) -> bool) -> u64 {
+        let now = OffsetDateTime::now_utc();
+        let mut revoked = 0;
+        for session in self.sessions.lock().unwrap().values_mut() {
+            if session.revoked_at.is_none() && matches(session) {
+                session.revoked_at = Some(now);
+                revoked += 1;
+            }
+        }
+        revoked
+    }
+}
+
+#[async_trait]
+impl SessionRepo for MemorySessionRepo {
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        user_agent: Option<&str>,
+        ip: &str,
+    ) -> Result<(), sqlx::Error> {
+        let now = OffsetDateTime::now_utc();
+        let session = Session {
+            id,
+            user_id,
+            user_agent: user_agent.map(str::to_string),
+            ip: Some(ip.to_string()),
+            created_at: now,
+            last_seen_at: now,
+            revoked_at: None,
+        };
+        self.sessions.lock().unwrap().insert(id, session);
+        Ok(())
+    }
+
+    async fn find_by_id(&self, id: i64) -> Result<Option<Session>, sqlx::Error> {
+        Ok(self.sessions.lock().unwrap().get(&id).cloned())
+    }
+
+    async fn list_active(
+        &self,
+        user_id: i64,
+        page: &PageRequest,
+    ) -> Result<Vec<Session>, sqlx::Error> {
+        let mut active: Vec<Session> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|session| session.user_id == user_id && session.revoked_at.is_none())
+            .cloned()
+            .collect();
+        active.sort_by_key(|session| std::cmp::Reverse(session.id));
+        active.truncate(page.fetch_limit() as usize);
+        Ok(active)
+    }
+
+    async fn count_active(&self, user_id: i64) -> Result<i64, sqlx::Error> {
+        let sessions = self.sessions.lock().unwrap();
+        Ok(sessions
+            .values()
+            .filter(|session| session.user_id == user_id && session.revoked_at.is_none())
+            .count() as i64)
+    }
+
+    async fn touch(
+        &self,
+        id: i64,
+        user_agent: Option<&str>,
+        ip: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get_mut(&id) {
+            Some(session) if session.revoked_at.is_none() => {
+                session.last_seen_at = OffsetDateTime::now_utc();
+                if let Some(user_agent) = user_agent {
+                    session.user_agent = Some(user_agent.to_string());
+                }
+                session.ip = Some(ip.to_string());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn revoke(&self, id: i64, user_id: i64) -> Result<bool, sqlx::Error> {
+        Ok(self.revoke_where(|session| session.id == id && session.user_id == user_id) == 1)
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        Ok(self.revoke_where(|session| session.user_id == user_id))
+    }
+
+    async fn revoke_others(&self, user_id: i64, keep_id: i64) -> Result<u64, sqlx::Error> {
+        Ok(self.revoke_where(|session| session.user_id == user_id && session.id != keep_id))
+    }
+}
@@ -0,0 +1,65 @@
+use crate::models::signing_key::SigningKey;
+use crate::repositories::signing_key_repo::SigningKeyRepo;
+use async_trait::async_trait;
+use std::sync::Mutex;
+use time::OffsetDateTime;
+
+// kept sorted by `activates_at` like the postgres listing
+#[derive(Default)]
+pub struct MemorySigningKeyRepo {
+    keys: Mutex<Vec<SigningKey>>,
+}
+
+impl MemorySigningKeyRepo {
+    pub fn new() -> Self {
+        MemorySigningKeyRepo::default()
+    }
+}
+
+#[async_trait]
+impl SigningKeyRepo for MemorySigningKeyRepo {
+    async fn list(&self) -> Result<Vec<SigningKey>, sqlx::Error> {
+        Ok(self.keys.lock().unwrap().clone())
+    }
+
+    async fn create_after(
+        &self,
+        key: &SigningKey,
+        newest: Option<OffsetDateTime>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut keys = self.keys.lock().unwrap();
+        let superseded = keys
+            .iter()
+            .any(|stored| newest.is_none_or(|newest| stored.activates_at > newest));
+        if superseded {
+            return Ok(false);
+        }
+        keys.push(key.clone());
+        keys.sort_by_key(|stored| stored.activates_at);
+        Ok(true)
+    }
+
+    async fn update_private_key(&self, kid: &str, private_key: &str) -> Result<(), sqlx::Error> {
+        let mut keys = self.keys.lock().unwrap();
+        if let Some(key) = keys.iter_mut().find(|key| key.kid == kid) {
+            key.private_key = private_key.to_string();
+        }
+        Ok(())
+    }
+
+    async fn delete_replaced_before(&self, cutoff: OffsetDateTime) -> Result<u64, sqlx::Error> {
+        let mut keys = self.keys.lock().unwrap();
+        // everything older than the key that was signing at `cutoff`
+        let Some(signing) = keys
+            .iter()
+            .map(|key| key.activates_at)
+            .filter(|activates_at| *activates_at <= cutoff)
+            .max()
+        else {
+            return Ok(0);
+        };
+        let before = keys.len();
+        keys.retain(|key| key.activates_at >= signing);
+        Ok((before - keys.len()) as u64)
+    }
+}
@@ -0,0 +1,99 @@
+use crate::models::two_factor::UserTotp;
+use crate::repositories::two_factor_repo::TwoFactorRepo;
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Mutex};
+use time::OffsetDateTime;
+
+#[derive(Default)]
+pub struct MemoryTwoFactorRepo {
+    totps: Mutex<HashMap<i64, UserTotp>>,
+    // (user_id, code_hash) -> used
+    recovery_codes: Mutex<HashMap<(i64, String), bool>>,
+}
+
+impl MemoryTwoFactorRepo {
+    pub fn new() -> Self {
+        MemoryTwoFactorRepo::default()
+    }
+}
+
+#[async_trait]
+impl TwoFactorRepo for MemoryTwoFactorRepo {
+    async fn find_totp(&self, user_id: i64) -> Result<Option<UserTotp>, sqlx::Error> {
+        Ok(self.totps.lock().unwrap().get(&user_id).cloned())
+    }
+
+    async fn save_pending(&self, user_id: i64, secret: &str) -> Result<bool, sqlx::Error> {
+        let mut totps = self.totps.lock().unwrap();
+        if totps
+            .get(&user_id)
+            .is_some_and(|totp| totp.enabled_at.is_some())
+        {
+            return Ok(false);
+        }
+        totps.insert(
+            user_id,
+            UserTotp {
+                user_id,
+                secret: secret.to_string(),
+                enabled_at: None,
+                last_used_step: 0,
+                created_at: OffsetDateTime::now_utc(),
+            },
+        );
+        Ok(true)
+    }
+
+    async fn enable(
+        &self,
+        user_id: i64,
+        recovery_codes: &[(i64, String)],
+    ) -> Result<bool, sqlx::Error> {
+        let mut totps = self.totps.lock().unwrap();
+        let Some(totp) = totps
+            .get_mut(&user_id)
+            .filter(|totp| totp.enabled_at.is_none())
+        else {
+            return Ok(false);
+        };
+        totp.enabled_at = Some(OffsetDateTime::now_utc());
+
+        let mut codes = self.recovery_codes.lock().unwrap();
+        codes.retain(|(owner, _), _| *owner != user_id);
+        for (_, code_hash) in recovery_codes {
+            codes.insert((user_id, code_hash.clone()), false);
+        }
+        Ok(true)
+    }
+
+    async fn mark_step_used(&self, user_id: i64, step: i64) -> Result<bool, sqlx::Error> {
+        let mut totps = self.totps.lock().unwrap();
+        match totps.get_mut(&user_id) {
+            Some(totp) if totp.last_used_step < step => {
+                totp.last_used_step = step;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn use_recovery_code(&self, user_id: i64, code_hash: &str) -> Result<bool, sqlx::Error> {
+        let mut codes = self.recovery_codes.lock().unwrap();
+        match codes.get_mut(&(user_id, code_hash.to_string())) {
+            Some(used) if !*used => {
+                *used = true;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete(&self, user_id: i64) -> Result<(), sqlx::Error> {
+        self.totps.lock().unwrap().remove(&user_id);
+        self.recovery_codes
+            .lock()
+            .unwrap()
+            .retain(|(owner, _), _| *owner != user_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::models::admin::UserFilter;
+use crate::models::user::User;
+use crate::repositories::user_repo::UserRepo;
+use async_trait::async_trait;
+use shared::pagination::page_request::PageRequest;
+use std::{collections::HashMap, sync::Mutex};
+use time::OffsetDateTime;
+
+// backs the service tests, admin search is left to the postgres repo
+#[derive(Default)]
+pub struct MemoryUserRepo {
+    users: Mutex<HashMap<i64, User>>,
+}
+
+impl MemoryUserRepo {
+    pub fn new() -> Self {
+        MemoryUserRepo::default()
+    }
+
+    pub fn insert(&self, user: User) {
+        self.users.lock().unwrap().insert(user.id, user);
+    }
+}
+
+#[async_trait]
+impl UserRepo for MemoryUserRepo {
+    async fn find_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
+        let users = self.users.lock().unwrap();
+        Ok(users
+            .values()
+            .find(|user| user.username == username)
+            .cloned())
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
+        let users = self.users.lock().unwrap();
+        Ok(users.values().find(|user| user.email == email).cloned())
+    }
+
+    async fn find_by_id(&self, id: i64) -> Result<Option<User>, sqlx::Error> {
+        Ok(self.users.lock().unwrap().get(&id).cloned())
+    }
+
+    async fn create(
+        &self,
+        id: i64,
+        username: String,
+        email: String,
+        password_hash: String,
+    ) -> Result<(), sqlx::Error> {
+        let now = OffsetDateTime::now_utc();
+        self.insert(User {
+            id,
+            username,
+            email,
+            password_hash,
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+            role: "user".to_string(),
+            token_version: 0,
+            locale: None,
+            email_verified_at: None,
+            pending_email: None,
+        });
+        Ok(())
+    }
+
+    async fn update_profile(
+        &self,
+        id: i64,
+        username: &str,
+        locale: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut users = self.users.lock().unwrap();
+        Ok(users.get_mut(&id).map(|user| {
+            user.username = username.to_string();
+            user.locale = locale.map(str::to_string);
+            user.updated_at = OffsetDateTime::now_utc();
+            user.clone()
+        }))
+    }
+
+    async fn set_pending_email(&self, id: i64, email: &str) -> Result<(), sqlx::Error> {
+        if let Some(user) = self.users.lock().unwrap().get_mut(&id) {
+            user.pending_email = Some(email.to_string());
+        }
+        Ok(())
+    }
+
+    async fn mark_email_verified(&self, id: i64, email: &str) -> Result<bool, sqlx::Error> {
+        let mut users = self.users.lock().unwrap();
+        match users.get_mut(&id) {
+            Some(user) if user.email == email && user.email_verified_at.is_none() => {
+                user.email_verified_at = Some(OffsetDateTime::now_utc());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn confirm_pending_email(&self, id: i64, email: &str) -> Result<bool, sqlx::Error> {
+        let mut users = self.users.lock().unwrap();
+        match users.get_mut(&id) {
+            Some(user) if user.pending_email.as_deref() == Some(email) => {
+                user.email = user.pending_email.take().unwrap_or_default();
+                user.email_verified_at = Some(OffsetDateTime::now_utc());
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn update_password(&self, id: i64, password_hash: &str) -> Result<(), sqlx::Error> {
+        if let Some(user) = self.users.lock().unwrap().get_mut(&id) {
+            user.password_hash = password_hash.to_string();
+        }
+        Ok(())
+    }
+
+    async fn increment_token_version(&self, id: i64) -> Result<(), sqlx::Error> {
+        if let Some(user) = self.users.lock().unwrap().get_mut(&id) {
+            user.token_version += 1;
+        }
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        _filter: &UserFilter,
+        _page: &PageRequest,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        unimplemented!("admin search is only backed by postgres")
+    }
+
+    async fn count(&self, _filter: &UserFilter) -> Result<i64, sqlx::Error> {
+        unimplemented!("admin search is only backed by postgres")
+    }
+
+    async fn update_role_and_status(
+        &self,
+        id: i64,
+        role: Option<&str>,
+        is_active: Option<bool>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let mut users = self.users.lock().unwrap();
+        Ok(users.get_mut(&id).map(|user| {
+            if let Some(role) = role {
+                user.role = role.to_string();
+            }
+            if let Some(is_active) = is_active {
+                user.is_active = is_active;
+            }
+            user.clone()
+        }))
+    }
+}
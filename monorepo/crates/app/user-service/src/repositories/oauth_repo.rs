@@ -0,0 +1,35 @@
+use crate::models::oauth::{AuthorizationCode, OAuthClient, OAuthToken};
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait OAuthRepo: Send + Sync {
+    async fn create_client(&self, client: &OAuthClient) -> Result<(), sqlx::Error>;
+    async fn find_client(&self, client_id: &str) -> Result<Option<OAuthClient>, sqlx::Error>;
+    async fn find_client_by_id(&self, id: i64) -> Result<Option<OAuthClient>, sqlx::Error>;
+    // space separated scopes the user agreed to
+    async fn find_consent(
+        &self,
+        user_id: i64,
+        client_id: i64,
+    ) -> Result<Option<String>, sqlx::Error>;
+    async fn save_consent(
+        &self,
+        user_id: i64,
+        client_id: i64,
+        scope: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn create_code(&self, code: &AuthorizationCode) -> Result<(), sqlx::Error>;
+    // marks the code used, `None` when it does not exist or was already used
+    async fn consume_code(&self, code_hash: &str)
+    -> Result<Option<AuthorizationCode>, sqlx::Error>;
+    async fn find_code(&self, code_hash: &str) -> Result<Option<AuthorizationCode>, sqlx::Error>;
+    async fn create_token(&self, token: &OAuthToken) -> Result<(), sqlx::Error>;
+    async fn find_token(&self, token_hash: &str) -> Result<Option<OAuthToken>, sqlx::Error>;
+    // atomically marks a refresh token rotated, false when it already was
+    async fn mark_token_used(&self, id: i64) -> Result<bool, sqlx::Error>;
+    async fn revoke_token(&self, id: i64) -> Result<(), sqlx::Error>;
+    // revokes every token issued under the grant
+    async fn revoke_grant(&self, grant_id: i64) -> Result<(), sqlx::Error>;
+    // revokes every token of the user and burns their unused codes, for sign-outs everywhere
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error>;
+}
@@ -0,0 +1,26 @@
+use crate::models::passkey::PasskeyCredential;
+use async_trait::async_trait;
+use webauthn_rs::prelude::Passkey;
+
+#[async_trait]
+pub trait PasskeyRepo: Send + Sync {
+    async fn list_by_user(&self, user_id: i64) -> Result<Vec<PasskeyCredential>, sqlx::Error>;
+    async fn find_by_credential_id(
+        &self,
+        credential_id: &[u8],
+    ) -> Result<Option<PasskeyCredential>, sqlx::Error>;
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        name: &str,
+        passkey: &Passkey,
+    ) -> Result<Option<PasskeyCredential>, sqlx::Error>;
+    // stores the advanced sign counter after a successful login
+    async fn update_usage(
+        &self,
+        id: i64,
+        passkey: &Passkey,
+        sign_count: i64,
+    ) -> Result<(), sqlx::Error>;
+}
@@ -0,0 +1,18 @@
+use async_trait::async_trait;
+use time::OffsetDateTime;
+
+#[async_trait]
+pub trait PasswordResetRepo: Send + Sync {
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        token_hash: &str,
+        expires_at: OffsetDateTime,
+    ) -> Result<(), sqlx::Error>;
+    // user of an unexpired, unused token, without consuming it
+    async fn find_active(&self, token_hash: &str) -> Result<Option<i64>, sqlx::Error>;
+    // marks an unexpired, unused token as used and returns its user
+    async fn consume(&self, token_hash: &str) -> Result<Option<i64>, sqlx::Error>;
+    async fn invalidate_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error>;
+}
@@ -0,0 +1,66 @@
+use crate::models::oidc::UserIdentity;
+use crate::repositories::identity_repo::IdentityRepo;
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+const IDENTITY_COLUMNS: &str = "id, user_id, provider, subject, email, created_at, last_login_at";
+
+pub struct PgIdentityRepo {
+    pool: PgPool,
+}
+
+impl PgIdentityRepo {
+    pub fn new(pool: PgPool) -> Self {
+        PgIdentityRepo { pool }
+    }
+}
+
+#[async_trait]
+impl IdentityRepo for PgIdentityRepo {
+    async fn find(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<Option<UserIdentity>, sqlx::Error> {
+        sqlx::query_as::<_, UserIdentity>(&format!(
+            "SELECT {} FROM user_identities WHERE provider = $1 AND subject = $2",
+            IDENTITY_COLUMNS
+        ))
+        .bind(provider)
+        .bind(subject)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        provider: &str,
+        subject: &str,
+        email: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO user_identities (id, user_id, provider, subject, email, last_login_at) VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP)",
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(provider)
+        .bind(subject)
+        .bind(email)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn record_login(&self, id: i64, email: Option<&str>) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE user_identities SET email = COALESCE($2, email), last_login_at = CURRENT_TIMESTAMP WHERE id = $1",
+        )
+        .bind(id)
+        .bind(email)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+}
@@ -0,0 +1,65 @@
+use crate::models::login_attempt::LoginAttempt;
+use crate::repositories::login_attempt_repo::LoginAttemptRepo;
+use async_trait::async_trait;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+pub struct PgLoginAttemptRepo {
+    pool: PgPool,
+}
+
+impl PgLoginAttemptRepo {
+    pub fn new(pool: PgPool) -> Self {
+        PgLoginAttemptRepo { pool }
+    }
+}
+
+#[async_trait]
+impl LoginAttemptRepo for PgLoginAttemptRepo {
+    async fn find(&self, key: &str) -> Result<Option<LoginAttempt>, sqlx::Error> {
+        sqlx::query_as::<_, LoginAttempt>(
+            "SELECT key, failures, last_failure_at, locked_until FROM login_attempts WHERE key = $1",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn record_failure(
+        &self,
+        key: &str,
+        now: OffsetDateTime,
+        stale_before: OffsetDateTime,
+    ) -> Result<LoginAttempt, sqlx::Error> {
+        // a single upsert, so concurrent failures are all counted
+        sqlx::query_as::<_, LoginAttempt>(
+            "INSERT INTO login_attempts (key, failures, last_failure_at) VALUES ($1, 1, $2) \
+             ON CONFLICT (key) DO UPDATE SET \
+             failures = CASE WHEN login_attempts.last_failure_at < $3 THEN 1 ELSE login_attempts.failures + 1 END, \
+             last_failure_at = $2 \
+             RETURNING key, failures, last_failure_at, locked_until",
+        )
+        .bind(key)
+        .bind(now)
+        .bind(stale_before)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    async fn lock(&self, key: &str, until: OffsetDateTime) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE login_attempts SET locked_until = $2 WHERE key = $1")
+            .bind(key)
+            .bind(until)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+    }
+
+    async fn clear(&self, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM login_attempts WHERE key = $1")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,202 @@
+use crate::models::oauth::{AuthorizationCode, OAuthClient, OAuthToken};
+use crate::repositories::oauth_repo::OAuthRepo;
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+const CLIENT_COLUMNS: &str = "id, client_id, client_secret_hash, name, redirect_uris, grant_types, scopes, created_by, created_at";
+const CODE_COLUMNS: &str = "code_hash, grant_id, client_id, user_id, redirect_uri, scope, code_challenge, nonce, auth_time, expires_at, used_at, created_at";
+const TOKEN_COLUMNS: &str = "id, token_hash, token_type, grant_id, client_id, user_id, scope, expires_at, used_at, revoked_at, created_at";
+
+pub struct PgOAuthRepo {
+    pool: PgPool,
+}
+
+impl PgOAuthRepo {
+    pub fn new(pool: PgPool) -> Self {
+        PgOAuthRepo { pool }
+    }
+}
+
+#[async_trait]
+impl OAuthRepo for PgOAuthRepo {
+    async fn create_client(&self, client: &OAuthClient) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO oauth_clients (id, client_id, client_secret_hash, name, redirect_uris, grant_types, scopes, created_by) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(client.id)
+        .bind(&client.client_id)
+        .bind(&client.client_secret_hash)
+        .bind(&client.name)
+        .bind(&client.redirect_uris)
+        .bind(&client.grant_types)
+        .bind(&client.scopes)
+        .bind(client.created_by)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn find_client(&self, client_id: &str) -> Result<Option<OAuthClient>, sqlx::Error> {
+        sqlx::query_as::<_, OAuthClient>(&format!(
+            "SELECT {} FROM oauth_clients WHERE client_id = $1",
+            CLIENT_COLUMNS
+        ))
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn find_client_by_id(&self, id: i64) -> Result<Option<OAuthClient>, sqlx::Error> {
+        sqlx::query_as::<_, OAuthClient>(&format!(
+            "SELECT {} FROM oauth_clients WHERE id = $1",
+            CLIENT_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn find_consent(
+        &self,
+        user_id: i64,
+        client_id: i64,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT scope FROM oauth_consents WHERE user_id = $1 AND client_id = $2")
+            .bind(user_id)
+            .bind(client_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn save_consent(
+        &self,
+        user_id: i64,
+        client_id: i64,
+        scope: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO oauth_consents (user_id, client_id, scope) VALUES ($1, $2, $3) \
+             ON CONFLICT (user_id, client_id) DO UPDATE SET scope = EXCLUDED.scope, granted_at = CURRENT_TIMESTAMP",
+        )
+        .bind(user_id)
+        .bind(client_id)
+        .bind(scope)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn create_code(&self, code: &AuthorizationCode) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO oauth_authorization_codes (code_hash, grant_id, client_id, user_id, redirect_uri, scope, code_challenge, nonce, auth_time, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        )
+        .bind(&code.code_hash)
+        .bind(code.grant_id)
+        .bind(code.client_id)
+        .bind(code.user_id)
+        .bind(&code.redirect_uri)
+        .bind(&code.scope)
+        .bind(&code.code_challenge)
+        .bind(&code.nonce)
+        .bind(code.auth_time)
+        .bind(code.expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn consume_code(
+        &self,
+        code_hash: &str,
+    ) -> Result<Option<AuthorizationCode>, sqlx::Error> {
+        sqlx::query_as::<_, AuthorizationCode>(&format!(
+            "UPDATE oauth_authorization_codes SET used_at = CURRENT_TIMESTAMP WHERE code_hash = $1 AND used_at IS NULL RETURNING {}",
+            CODE_COLUMNS
+        ))
+        .bind(code_hash)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn find_code(&self, code_hash: &str) -> Result<Option<AuthorizationCode>, sqlx::Error> {
+        sqlx::query_as::<_, AuthorizationCode>(&format!(
+            "SELECT {} FROM oauth_authorization_codes WHERE code_hash = $1",
+            CODE_COLUMNS
+        ))
+        .bind(code_hash)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn create_token(&self, token: &OAuthToken) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO oauth_tokens (id, token_hash, token_type, grant_id, client_id, user_id, scope, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(token.id)
+        .bind(&token.token_hash)
+        .bind(&token.token_type)
+        .bind(token.grant_id)
+        .bind(token.client_id)
+        .bind(token.user_id)
+        .bind(&token.scope)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn find_token(&self, token_hash: &str) -> Result<Option<OAuthToken>, sqlx::Error> {
+        sqlx::query_as::<_, OAuthToken>(&format!(
+            "SELECT {} FROM oauth_tokens WHERE token_hash = $1",
+            TOKEN_COLUMNS
+        ))
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn mark_token_used(&self, id: i64) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "UPDATE oauth_tokens SET used_at = CURRENT_TIMESTAMP WHERE id = $1 AND used_at IS NULL",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() == 1)
+    }
+
+    async fn revoke_token(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE oauth_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND revoked_at IS NULL")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+    }
+
+    async fn revoke_grant(&self, grant_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE oauth_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE grant_id = $1 AND revoked_at IS NULL")
+            .bind(grant_id)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE oauth_authorization_codes SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND used_at IS NULL")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("UPDATE oauth_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND revoked_at IS NULL")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+}
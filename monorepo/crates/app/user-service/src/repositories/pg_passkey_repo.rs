@@ -0,0 +1,83 @@
+use crate::models::passkey::PasskeyCredential;
+use crate::repositories::passkey_repo::PasskeyRepo;
+use async_trait::async_trait;
+use sqlx::{PgPool, types::Json};
+use webauthn_rs::prelude::Passkey;
+
+const PASSKEY_COLUMNS: &str =
+    "id, user_id, credential_id, name, passkey, sign_count, created_at, last_used_at";
+
+pub struct PgPasskeyRepo {
+    pool: PgPool,
+}
+
+impl PgPasskeyRepo {
+    pub fn new(pool: PgPool) -> Self {
+        PgPasskeyRepo { pool }
+    }
+}
+
+#[async_trait]
+impl PasskeyRepo for PgPasskeyRepo {
+    async fn list_by_user(&self, user_id: i64) -> Result<Vec<PasskeyCredential>, sqlx::Error> {
+        sqlx::query_as::<_, PasskeyCredential>(&format!(
+            "SELECT {} FROM webauthn_credentials WHERE user_id = $1 ORDER BY created_at",
+            PASSKEY_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn find_by_credential_id(
+        &self,
+        credential_id: &[u8],
+    ) -> Result<Option<PasskeyCredential>, sqlx::Error> {
+        sqlx::query_as::<_, PasskeyCredential>(&format!(
+            "SELECT {} FROM webauthn_credentials WHERE credential_id = $1",
+            PASSKEY_COLUMNS
+        ))
+        .bind(credential_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    // `None` when the credential is already registered
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        name: &str,
+        passkey: &Passkey,
+    ) -> Result<Option<PasskeyCredential>, sqlx::Error> {
+        sqlx::query_as::<_, PasskeyCredential>(&format!(
+            "INSERT INTO webauthn_credentials (id, user_id, credential_id, name, passkey) VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (credential_id) DO NOTHING RETURNING {}",
+            PASSKEY_COLUMNS
+        ))
+        .bind(id)
+        .bind(user_id)
+        .bind(passkey.cred_id().as_ref())
+        .bind(name)
+        .bind(Json(passkey))
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn update_usage(
+        &self,
+        id: i64,
+        passkey: &Passkey,
+        sign_count: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE webauthn_credentials SET passkey = $2, sign_count = $3, last_used_at = CURRENT_TIMESTAMP WHERE id = $1",
+        )
+        .bind(id)
+        .bind(Json(passkey))
+        .bind(sign_count)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+}
@@ -0,0 +1,64 @@
+use crate::repositories::password_reset_repo::PasswordResetRepo;
+use async_trait::async_trait;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+pub struct PgPasswordResetRepo {
+    pool: PgPool,
+}
+
+impl PgPasswordResetRepo {
+    pub fn new(pool: PgPool) -> Self {
+        PgPasswordResetRepo { pool }
+    }
+}
+
+#[async_trait]
+impl PasswordResetRepo for PgPasswordResetRepo {
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        token_hash: &str,
+        expires_at: OffsetDateTime,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO password_reset_tokens (id, user_id, token_hash, expires_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn find_active(&self, token_hash: &str) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            "SELECT user_id FROM password_reset_tokens WHERE token_hash = $1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn consume(&self, token_hash: &str) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar::<_, i64>(
+            "UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP WHERE token_hash = $1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP RETURNING user_id",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn invalidate_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        sqlx::query(
+            "UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND used_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+    }
+}
@@ -0,0 +1,74 @@
+use crate::models::refresh_token::RefreshToken;
+use crate::repositories::refresh_token_repo::RefreshTokenRepo;
+use async_trait::async_trait;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+pub struct PgRefreshTokenRepo {
+    pool: PgPool,
+}
+
+impl PgRefreshTokenRepo {
+    pub fn new(pool: PgPool) -> Self {
+        PgRefreshTokenRepo { pool }
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepo for PgRefreshTokenRepo {
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        family_id: i64,
+        expires_at: OffsetDateTime,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO refresh_tokens (id, user_id, family_id, expires_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(family_id)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn find_by_id(&self, id: i64) -> Result<Option<RefreshToken>, sqlx::Error> {
+        sqlx::query_as::<_, RefreshToken>("SELECT id, user_id, family_id, expires_at, used_at, revoked_at, created_at FROM refresh_tokens WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    async fn mark_used(&self, id: i64) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "UPDATE refresh_tokens SET used_at = CURRENT_TIMESTAMP WHERE id = $1 AND used_at IS NULL AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() == 1)
+    }
+
+    async fn revoke_family(&self, family_id: i64) -> Result<u64, sqlx::Error> {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE family_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(family_id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+    }
+}
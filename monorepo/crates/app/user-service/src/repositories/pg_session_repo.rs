@@ -0,0 +1,129 @@
+use crate::models::session::Session;
+use crate::repositories::session_repo::SessionRepo;
+use async_trait::async_trait;
+use shared::pagination::{keyset::keyset_sql, page_request::PageRequest};
+use sqlx::PgPool;
+
+const SESSION_COLUMNS: &str = "id, user_id, user_agent, ip, created_at, last_seen_at, revoked_at";
+
+// not revoked and a refresh token of the family is still usable
+const ACTIVE_SESSION: &str = "user_id = $1 AND revoked_at IS NULL AND EXISTS (SELECT 1 FROM refresh_tokens r WHERE r.family_id = s.id AND r.used_at IS NULL AND r.revoked_at IS NULL AND r.expires_at > CURRENT_TIMESTAMP)";
+
+pub struct PgSessionRepo {
+    pool: PgPool,
+}
+
+impl PgSessionRepo {
+    pub fn new(pool: PgPool) -> Self {
+        PgSessionRepo { pool }
+    }
+}
+
+#[async_trait]
+impl SessionRepo for PgSessionRepo {
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        user_agent: Option<&str>,
+        ip: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO sessions (id, user_id, user_agent, ip) VALUES ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(user_id)
+            .bind(user_agent)
+            .bind(ip)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+    }
+
+    async fn find_by_id(&self, id: i64) -> Result<Option<Session>, sqlx::Error> {
+        sqlx::query_as::<_, Session>(&format!(
+            "SELECT {} FROM sessions WHERE id = $1",
+            SESSION_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn list_active(
+        &self,
+        user_id: i64,
+        page: &PageRequest,
+    ) -> Result<Vec<Session>, sqlx::Error> {
+        sqlx::query_as::<_, Session>(&format!(
+            "SELECT {} FROM sessions s WHERE {} AND {}",
+            SESSION_COLUMNS,
+            ACTIVE_SESSION,
+            keyset_sql("id", 2)
+        ))
+        .bind(user_id)
+        .bind(page.after())
+        .bind(page.fetch_limit())
+        .bind(page.offset())
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn count_active(&self, user_id: i64) -> Result<i64, sqlx::Error> {
+        sqlx::query_as::<_, (i64,)>(&format!(
+            "SELECT COUNT(*) FROM sessions s WHERE {}",
+            ACTIVE_SESSION
+        ))
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
+        .map(|(count,)| count)
+    }
+
+    async fn touch(
+        &self,
+        id: i64,
+        user_agent: Option<&str>,
+        ip: &str,
+    ) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "UPDATE sessions SET last_seen_at = CURRENT_TIMESTAMP, user_agent = COALESCE($2, user_agent), ip = $3 WHERE id = $1 AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .bind(user_agent)
+        .bind(ip)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() == 1)
+    }
+
+    async fn revoke(&self, id: i64, user_id: i64) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() == 1)
+    }
+
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        sqlx::query(
+            "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+    }
+
+    async fn revoke_others(&self, user_id: i64, keep_id: i64) -> Result<u64, sqlx::Error> {
+        sqlx::query(
+            "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND id <> $2 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .bind(keep_id)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected())
+    }
+}
@@ -0,0 +1,81 @@
+use crate::models::signing_key::SigningKey;
+use crate::repositories::signing_key_repo::SigningKeyRepo;
+use async_trait::async_trait;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+const SIGNING_KEY_COLUMNS: &str =
+    "kid, algorithm, private_key, public_jwk, activates_at, created_at";
+// advisory lock serializing key creation, instances starting together would each add a key
+const CREATE_LOCK_ID: i64 = 0x0073_6967_6e69_6e67;
+
+pub struct PgSigningKeyRepo {
+    pool: PgPool,
+}
+
+impl PgSigningKeyRepo {
+    pub fn new(pool: PgPool) -> Self {
+        PgSigningKeyRepo { pool }
+    }
+}
+
+#[async_trait]
+impl SigningKeyRepo for PgSigningKeyRepo {
+    async fn list(&self) -> Result<Vec<SigningKey>, sqlx::Error> {
+        sqlx::query_as::<_, SigningKey>(&format!(
+            "SELECT {} FROM signing_keys ORDER BY activates_at",
+            SIGNING_KEY_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    async fn create_after(
+        &self,
+        key: &SigningKey,
+        newest: Option<OffsetDateTime>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        // the check below only sees keys committed before the lock was granted
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(CREATE_LOCK_ID)
+            .execute(&mut *tx)
+            .await?;
+
+        let result = sqlx::query(
+            "INSERT INTO signing_keys (kid, algorithm, private_key, public_jwk, activates_at) SELECT $1, $2, $3, $4, $5 WHERE NOT EXISTS (SELECT 1 FROM signing_keys WHERE $6::timestamptz IS NULL OR activates_at > $6)",
+        )
+        .bind(&key.kid)
+        .bind(&key.algorithm)
+        .bind(&key.private_key)
+        .bind(&key.public_jwk)
+        .bind(key.activates_at)
+        .bind(newest)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn update_private_key(&self, kid: &str, private_key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE signing_keys SET private_key = $2 WHERE kid = $1")
+            .bind(kid)
+            .bind(private_key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_replaced_before(&self, cutoff: OffsetDateTime) -> Result<u64, sqlx::Error> {
+        // everything older than the key that was signing at `cutoff`
+        let result = sqlx::query(
+            "DELETE FROM signing_keys WHERE activates_at < (SELECT MAX(activates_at) FROM signing_keys WHERE activates_at <= $1)",
+        )
+        .bind(cutoff)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
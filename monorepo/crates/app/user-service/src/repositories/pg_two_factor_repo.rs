@@ -0,0 +1,110 @@
+use crate::models::two_factor::UserTotp;
+use crate::repositories::two_factor_repo::TwoFactorRepo;
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+pub struct PgTwoFactorRepo {
+    pool: PgPool,
+}
+
+impl PgTwoFactorRepo {
+    pub fn new(pool: PgPool) -> Self {
+        PgTwoFactorRepo { pool }
+    }
+}
+
+#[async_trait]
+impl TwoFactorRepo for PgTwoFactorRepo {
+    async fn find_totp(&self, user_id: i64) -> Result<Option<UserTotp>, sqlx::Error> {
+        sqlx::query_as::<_, UserTotp>(
+            "SELECT user_id, secret, enabled_at, last_used_step, created_at FROM user_totp WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn save_pending(&self, user_id: i64, secret: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO user_totp (user_id, secret) VALUES ($1, $2) \
+             ON CONFLICT (user_id) DO UPDATE SET secret = $2, last_used_step = 0, created_at = CURRENT_TIMESTAMP \
+             WHERE user_totp.enabled_at IS NULL",
+        )
+        .bind(user_id)
+        .bind(secret)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+    }
+
+    async fn enable(
+        &self,
+        user_id: i64,
+        recovery_codes: &[(i64, String)],
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let enabled = sqlx::query(
+            "UPDATE user_totp SET enabled_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND enabled_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+        if !enabled {
+            return Ok(false);
+        }
+
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        for (id, code_hash) in recovery_codes {
+            sqlx::query("INSERT INTO recovery_codes (id, user_id, code_hash) VALUES ($1, $2, $3)")
+                .bind(id)
+                .bind(user_id)
+                .bind(code_hash)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn mark_step_used(&self, user_id: i64, step: i64) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "UPDATE user_totp SET last_used_step = $2 WHERE user_id = $1 AND last_used_step < $2",
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+    }
+
+    async fn use_recovery_code(&self, user_id: i64, code_hash: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "UPDATE recovery_codes SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL",
+        )
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() > 0)
+    }
+
+    async fn delete(&self, user_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM user_totp WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+}
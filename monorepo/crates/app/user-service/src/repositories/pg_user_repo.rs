@@ -1,7 +1,14 @@
+use crate::models::admin::UserFilter;
 use crate::models::user::User;
 use crate::repositories::user_repo::UserRepo;
 use async_trait::async_trait;
-use sqlx::PgPool;
+use shared::pagination::{keyset::keyset_sql, page_request::PageRequest};
+use sqlx::{PgPool, Postgres, postgres::PgArguments, query::QueryAs};
+
+const USER_COLUMNS: &str = "id, username, email, password_hash, created_at, updated_at, is_active, role, token_version, locale, email_verified_at, pending_email";
+
+// a NULL parameter leaves its filter out, bound in order by `bind_filter`
+const USER_FILTER: &str = "($1::text IS NULL OR email ILIKE $1) AND ($2::text IS NULL OR username ILIKE $2) AND ($3::text IS NULL OR role = $3) AND ($4::boolean IS NULL OR is_active = $4) AND ($5::timestamptz IS NULL OR created_at >= $5) AND ($6::timestamptz IS NULL OR created_at < $6)";
 
 pub struct PgUserRepo {
     pool: PgPool,
@@ -16,21 +23,27 @@ impl PgUserRepo {
 #[async_trait]
 impl UserRepo for PgUserRepo {
     async fn find_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
-        sqlx::query_as::<_, User>("SELECT id, username, email, password_hash, created_at, updated_at, is_active, role FROM users WHERE username = $1")
-            .bind(username)
-            .fetch_optional(&self.pool)
-            .await
+        sqlx::query_as::<_, User>(&format!(
+            "SELECT {} FROM users WHERE username = $1",
+            USER_COLUMNS
+        ))
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await
     }
 
     async fn find_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error> {
-        sqlx::query_as::<_, User>("SELECT id, username, email, password_hash, created_at, updated_at, is_active, role FROM users WHERE email = $1")
-            .bind(email)
-            .fetch_optional(&self.pool)
-            .await
+        sqlx::query_as::<_, User>(&format!(
+            "SELECT {} FROM users WHERE email = $1",
+            USER_COLUMNS
+        ))
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await
     }
 
-    async fn find_by_id(&self, id: i32) -> Result<Option<User>, sqlx::Error> {
-        sqlx::query_as::<_, User>("SELECT id, username, email, password_hash, created_at, updated_at, is_active, role FROM users WHERE id = $1")
+    async fn find_by_id(&self, id: i64) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>(&format!("SELECT {} FROM users WHERE id = $1", USER_COLUMNS))
             .bind(id)
             .fetch_optional(&self.pool)
             .await
@@ -54,4 +67,141 @@ impl UserRepo for PgUserRepo {
         .await
         .map(|_| ())
     }
+
+    async fn update_profile(
+        &self,
+        id: i64,
+        username: &str,
+        locale: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>(&format!(
+            "UPDATE users SET username = $2, locale = $3, updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(id)
+        .bind(username)
+        .bind(locale)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    async fn set_pending_email(&self, id: i64, email: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE users SET pending_email = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $1",
+        )
+        .bind(id)
+        .bind(email)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn update_password(&self, id: i64, password_hash: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE users SET password_hash = $2, updated_at = CURRENT_TIMESTAMP WHERE id = $1",
+        )
+        .bind(id)
+        .bind(password_hash)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn increment_token_version(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE users SET token_version = token_version + 1, updated_at = CURRENT_TIMESTAMP WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map(|_| ())
+    }
+
+    async fn mark_email_verified(&self, id: i64, email: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "UPDATE users SET email_verified_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND email = $2 AND email_verified_at IS NULL",
+        )
+        .bind(id)
+        .bind(email)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() == 1)
+    }
+
+    async fn confirm_pending_email(&self, id: i64, email: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query(
+            "UPDATE users SET email = pending_email, pending_email = NULL, email_verified_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND pending_email = $2",
+        )
+        .bind(id)
+        .bind(email)
+        .execute(&self.pool)
+        .await
+        .map(|result| result.rows_affected() == 1)
+    }
+
+    async fn search(
+        &self,
+        filter: &UserFilter,
+        page: &PageRequest,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {} FROM users WHERE {} AND {}",
+            USER_COLUMNS,
+            USER_FILTER,
+            keyset_sql("id", 7)
+        );
+        bind_filter(sqlx::query_as::<_, User>(&sql), filter)
+            .bind(page.after())
+            .bind(page.fetch_limit())
+            .bind(page.offset())
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    async fn count(&self, filter: &UserFilter) -> Result<i64, sqlx::Error> {
+        let sql = format!("SELECT COUNT(*) FROM users WHERE {}", USER_FILTER);
+        bind_filter(sqlx::query_as::<_, (i64,)>(&sql), filter)
+            .fetch_one(&self.pool)
+            .await
+            .map(|(count,)| count)
+    }
+
+    async fn update_role_and_status(
+        &self,
+        id: i64,
+        role: Option<&str>,
+        is_active: Option<bool>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>(&format!(
+            "UPDATE users SET role = COALESCE($2, role), is_active = COALESCE($3, is_active), updated_at = CURRENT_TIMESTAMP WHERE id = $1 RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(id)
+        .bind(role)
+        .bind(is_active)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
+
+fn bind_filter<'q, O>(
+    query: QueryAs<'q, Postgres, O, PgArguments>,
+    filter: &'q UserFilter,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    query
+        .bind(filter.email.as_deref().map(contains_pattern))
+        .bind(filter.username.as_deref().map(contains_pattern))
+        .bind(filter.role.as_deref())
+        .bind(filter.is_active)
+        .bind(filter.created_from)
+        .bind(filter.created_to)
+}
+
+// ILIKE pattern matching `value` anywhere, with its wildcards taken literally
+fn contains_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
 }
@@ -0,0 +1,19 @@
+use crate::models::refresh_token::RefreshToken;
+use async_trait::async_trait;
+use time::OffsetDateTime;
+
+#[async_trait]
+pub trait RefreshTokenRepo: Send + Sync {
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        family_id: i64,
+        expires_at: OffsetDateTime,
+    ) -> Result<(), sqlx::Error>;
+    async fn find_by_id(&self, id: i64) -> Result<Option<RefreshToken>, sqlx::Error>;
+    // returns false when the token was already used or revoked
+    async fn mark_used(&self, id: i64) -> Result<bool, sqlx::Error>;
+    async fn revoke_family(&self, family_id: i64) -> Result<u64, sqlx::Error>;
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error>;
+}
@@ -0,0 +1,31 @@
+use crate::models::session::Session;
+use async_trait::async_trait;
+use shared::pagination::page_request::PageRequest;
+
+#[async_trait]
+pub trait SessionRepo: Send + Sync {
+    async fn create(
+        &self,
+        id: i64,
+        user_id: i64,
+        user_agent: Option<&str>,
+        ip: &str,
+    ) -> Result<(), sqlx::Error>;
+    async fn find_by_id(&self, id: i64) -> Result<Option<Session>, sqlx::Error>;
+    // sessions not revoked whose refresh token family can still be refreshed, newest first,
+    // up to `PageRequest::fetch_limit` rows
+    async fn list_active(
+        &self,
+        user_id: i64,
+        page: &PageRequest,
+    ) -> Result<Vec<Session>, sqlx::Error>;
+    async fn count_active(&self, user_id: i64) -> Result<i64, sqlx::Error>;
+    // records a refresh, returns false when the session is missing or revoked
+    async fn touch(&self, id: i64, user_agent: Option<&str>, ip: &str)
+    -> Result<bool, sqlx::Error>;
+    // returns false when the user has no such session
+    async fn revoke(&self, id: i64, user_id: i64) -> Result<bool, sqlx::Error>;
+    async fn revoke_all_for_user(&self, user_id: i64) -> Result<u64, sqlx::Error>;
+    // every session of the user but `keep_id`
+    async fn revoke_others(&self, user_id: i64, keep_id: i64) -> Result<u64, sqlx::Error>;
+}
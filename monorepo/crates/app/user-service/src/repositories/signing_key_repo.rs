@@ -0,0 +1,19 @@
+use crate::models::signing_key::SigningKey;
+use async_trait::async_trait;
+use time::OffsetDateTime;
+
+#[async_trait]
+pub trait SigningKeyRepo: Send + Sync {
+    // oldest first
+    async fn list(&self) -> Result<Vec<SigningKey>, sqlx::Error>;
+    // false when another instance already added a key activating after `newest`
+    async fn create_after(
+        &self,
+        key: &SigningKey,
+        newest: Option<OffsetDateTime>,
+    ) -> Result<bool, sqlx::Error>;
+    // used to seal keys stored before encryption at rest
+    async fn update_private_key(&self, kid: &str, private_key: &str) -> Result<(), sqlx::Error>;
+    // drops the keys that were replaced before `cutoff`
+    async fn delete_replaced_before(&self, cutoff: OffsetDateTime) -> Result<u64, sqlx::Error>;
+}
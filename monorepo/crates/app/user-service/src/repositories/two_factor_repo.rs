@@ -0,0 +1,19 @@
+use crate::models::two_factor::UserTotp;
+use async_trait::async_trait;
+
+#[async_trait]
+pub trait TwoFactorRepo: Send + Sync {
+    async fn find_totp(&self, user_id: i64) -> Result<Option<UserTotp>, sqlx::Error>;
+    // replaces an unconfirmed secret, never an enabled one
+    async fn save_pending(&self, user_id: i64, secret: &str) -> Result<bool, sqlx::Error>;
+    // enables the secret and replaces the recovery codes, given as (id, code_hash)
+    async fn enable(
+        &self,
+        user_id: i64,
+        recovery_codes: &[(i64, String)],
+    ) -> Result<bool, sqlx::Error>;
+    // false when the step or a later one was already used
+    async fn mark_step_used(&self, user_id: i64, step: i64) -> Result<bool, sqlx::Error>;
+    async fn use_recovery_code(&self, user_id: i64, code_hash: &str) -> Result<bool, sqlx::Error>;
+    async fn delete(&self, user_id: i64) -> Result<(), sqlx::Error>;
+}
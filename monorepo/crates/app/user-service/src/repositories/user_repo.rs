@@ -1,10 +1,47 @@
+use crate::models::admin::UserFilter;
 use crate::models::user::User;
 use async_trait::async_trait;
+use shared::pagination::page_request::PageRequest;
 
 #[async_trait]
-pub trait UserRepo: Send + Sync  {
+pub trait UserRepo: Send + Sync {
     async fn find_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error>;
     async fn find_by_email(&self, email: &str) -> Result<Option<User>, sqlx::Error>;
-    async fn find_by_id(&self, id: i32) -> Result<Option<User>, sqlx::Error>;
-    async fn create(&self, id: i64, username: String, email: String, password_hash: String) -> Result<(), sqlx::Error>;
+    async fn find_by_id(&self, id: i64) -> Result<Option<User>, sqlx::Error>;
+    async fn create(
+        &self,
+        id: i64,
+        username: String,
+        email: String,
+        password_hash: String,
+    ) -> Result<(), sqlx::Error>;
+    async fn update_profile(
+        &self,
+        id: i64,
+        username: &str,
+        locale: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error>;
+    // replaces an earlier pending address, the current one stays until `confirm_pending_email`
+    async fn set_pending_email(&self, id: i64, email: &str) -> Result<(), sqlx::Error>;
+    // leaves `is_active` alone, returns false when already verified or the email changed since
+    async fn mark_email_verified(&self, id: i64, email: &str) -> Result<bool, sqlx::Error>;
+    // swaps in the pending address as verified, false when `email` is no longer the pending one
+    async fn confirm_pending_email(&self, id: i64, email: &str) -> Result<bool, sqlx::Error>;
+    async fn update_password(&self, id: i64, password_hash: &str) -> Result<(), sqlx::Error>;
+    // invalidates every access token issued before the call
+    async fn increment_token_version(&self, id: i64) -> Result<(), sqlx::Error>;
+    // newest first, up to `PageRequest::fetch_limit` rows
+    async fn search(
+        &self,
+        filter: &UserFilter,
+        page: &PageRequest,
+    ) -> Result<Vec<User>, sqlx::Error>;
+    async fn count(&self, filter: &UserFilter) -> Result<i64, sqlx::Error>;
+    // fields left as None keep their value
+    async fn update_role_and_status(
+        &self,
+        id: i64,
+        role: Option<&str>,
+        is_active: Option<bool>,
+    ) -> Result<Option<User>, sqlx::Error>;
 }
@@ -0,0 +1,133 @@
+use std::sync::Arc;
+
+use shared::{
+    constants::constants,
+    error::error::AppError,
+    pagination::{page::Page, page_request::PageRequest},
+};
+use time::OffsetDateTime;
+
+use crate::middleware::role_middleware::RoleHierarchy;
+use crate::models::{
+    admin::{ListUsersRequest, UpdateUserRequest, UserFilter},
+    user::UserProfileReply,
+};
+use crate::repositories::user_repo::UserRepo;
+use crate::services::user_service::UserService;
+
+// user management for operators, every route is behind `require_role("admin")`
+pub struct AdminService {
+    repo: Arc<dyn UserRepo>,
+    users: Arc<UserService>,
+    roles: Arc<RoleHierarchy>,
+}
+
+impl AdminService {
+    pub fn new(
+        repo: Arc<dyn UserRepo>,
+        users: Arc<UserService>,
+        roles: Arc<RoleHierarchy>,
+    ) -> Self {
+        AdminService { repo, users, roles }
+    }
+
+    pub async fn list_users(
+        &self,
+        req: ListUsersRequest,
+        page: PageRequest,
+    ) -> Result<Page<UserProfileReply>, AppError> {
+        let filter = UserFilter {
+            email: req.email,
+            username: req.username,
+            role: req.role,
+            is_active: req.is_active,
+            created_from: req.created_from.map(unix_time).transpose()?,
+            created_to: req.created_to.map(unix_time).transpose()?,
+        };
+
+        let users = self.repo.search(&filter, &page).await.map_err(|e| {
+            tracing::error!("database search users error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+        let total = if page.wants_total() {
+            Some(self.repo.count(&filter).await.map_err(|e| {
+                tracing::error!("database count users error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?)
+        } else {
+            None
+        };
+
+        Ok(Page::new(users, &page, |user| user.id, total).map(UserProfileReply::from))
+    }
+
+    pub async fn get_user(&self, user_id: i64) -> Result<UserProfileReply, AppError> {
+        self.repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .map(UserProfileReply::from)
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))
+    }
+
+    // role changes apply on the next request, the auth middleware reads the role from the database
+    pub async fn update_user(
+        &self,
+        admin_id: i64,
+        user_id: i64,
+        req: UpdateUserRequest,
+    ) -> Result<UserProfileReply, AppError> {
+        if let Some(role) = &req.role
+            && !self.roles.contains(role)
+        {
+            return Err(AppError::bad_request(constants::CODE_PARAMETER_ERROR)
+                .with_detail(format!("unknown role `{}`", role)));
+        }
+        // an operator locking themselves out leaves nobody to undo it
+        if admin_id == user_id {
+            return Err(AppError::bad_request(constants::CODE_PARAMETER_ERROR)
+                .with_detail("cannot change your own role or status"));
+        }
+
+        let updated = self
+            .repo
+            .update_role_and_status(user_id, req.role.as_deref(), req.is_active)
+            .await
+            .map_err(|e| {
+                tracing::error!("database update user error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))?;
+        tracing::info!(
+            "admin {} updated user {}: role {:?}, is_active {:?}",
+            admin_id,
+            user_id,
+            req.role,
+            req.is_active
+        );
+
+        if req.is_active == Some(false) {
+            self.users.logout_all(user_id).await?;
+        }
+
+        Ok(UserProfileReply::from(updated))
+    }
+
+    // revokes every session and access token of the user
+    pub async fn logout_user(&self, admin_id: i64, user_id: i64) -> Result<(), AppError> {
+        self.get_user(user_id).await?;
+        self.users.logout_all(user_id).await?;
+        tracing::info!("admin {} signed out user {}", admin_id, user_id);
+
+        Ok(())
+    }
+}
+
+fn unix_time(timestamp: i64) -> Result<OffsetDateTime, AppError> {
+    OffsetDateTime::from_unix_timestamp(timestamp).map_err(|_| {
+        AppError::bad_request(constants::CODE_PARAMETER_ERROR).with_detail("invalid timestamp")
+    })
+}
@@ -0,0 +1,226 @@
+use std::{net::IpAddr, sync::Arc};
+
+use shared::{
+    config::config::{LockoutConfig, LoginProtectionConfig},
+    constants::constants,
+    error::error::AppError,
+};
+use time::{Duration, OffsetDateTime};
+
+use crate::repositories::login_attempt_repo::LoginAttemptRepo;
+
+// brute-force protection for login, failures are tracked per account and per client ip
+pub struct LoginGuard {
+    repo: Arc<dyn LoginAttemptRepo>,
+    config: LoginProtectionConfig,
+}
+
+impl LoginGuard {
+    pub fn new(repo: Arc<dyn LoginAttemptRepo>, config: LoginProtectionConfig) -> Self {
+        LoginGuard { repo, config }
+    }
+
+    // rejects the attempt before the password is even checked
+    pub async fn check(&self, email: &str, ip: IpAddr) -> Result<(), AppError> {
+        let now = OffsetDateTime::now_utc();
+        let keys = [
+            (account_key(email), constants::CODE_ACCOUNT_LOCKED),
+            (ip_key(ip), constants::CODE_TOO_MANY_ATTEMPTS),
+        ];
+
+        for (key, code) in keys {
+            let attempt = self.repo.find(&key).await.map_err(|e| {
+                tracing::error!("database find login attempt error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+            if let Some(until) = attempt.and_then(|attempt| attempt.locked_until)
+                && until > now
+            {
+                let retry_after = (until - now).whole_seconds().max(0) as u64 + 1;
+                return Err(AppError::too_many_requests(code, retry_after));
+            }
+        }
+
+        Ok(())
+    }
+
+    // unknown emails are counted as well, so locking does not reveal which accounts exist
+    pub async fn record_failure(&self, email: &str, ip: IpAddr) -> Result<(), AppError> {
+        let now = OffsetDateTime::now_utc();
+        let stale_before = now - Duration::seconds(self.config.window_secs);
+        let keys = [
+            (account_key(email), &self.config.account),
+            (ip_key(ip), &self.config.ip),
+        ];
+
+        for (key, lockout) in keys {
+            let attempt = self
+                .repo
+                .record_failure(&key, now, stale_before)
+                .await
+                .map_err(|e| {
+                    tracing::error!("database record login attempt error: {}", e);
+                    AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                })?;
+
+            if let Some(duration) = lockout_duration(lockout, attempt.failures) {
+                tracing::warn!(
+                    "{} locked for {} after {} failed logins",
+                    key,
+                    duration,
+                    attempt.failures
+                );
+                self.repo.lock(&key, now + duration).await.map_err(|e| {
+                    tracing::error!("database lock login attempt error: {}", e);
+                    AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // the ip counter is kept, one valid account must not unlock guessing on others
+    pub async fn record_success(&self, email: &str) -> Result<(), AppError> {
+        self.repo.clear(&account_key(email)).await.map_err(|e| {
+            tracing::error!("database clear login attempts error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })
+    }
+}
+
+fn account_key(email: &str) -> String {
+    format!("account:{}", email.to_lowercase())
+}
+
+fn ip_key(ip: IpAddr) -> String {
+    format!("ip:{}", ip)
+}
+
+// `lockout_secs` once `max_failures` is reached, doubled for every further failure
+fn lockout_duration(lockout: &LockoutConfig, failures: i32) -> Option<Duration> {
+    let extra = failures
+        .checked_sub(lockout.max_failures)
+        .filter(|extra| *extra >= 0)?;
+    let seconds = lockout
+        .lockout_secs
+        .saturating_mul(1i64 << extra.min(32))
+        .min(lockout.max_lockout_secs);
+    Some(Duration::seconds(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::memory_login_attempt_repo::MemoryLoginAttemptRepo;
+    use std::net::Ipv4Addr;
+
+    fn lockout(max_failures: i32) -> LockoutConfig {
+        LockoutConfig {
+            max_failures,
+            lockout_secs: 60,
+            max_lockout_secs: 600,
+        }
+    }
+
+    fn guard() -> LoginGuard {
+        LoginGuard::new(
+            Arc::new(MemoryLoginAttemptRepo::new()),
+            LoginProtectionConfig {
+                store: "memory".to_string(),
+                window_secs: 900,
+                account: lockout(3),
+                ip: lockout(5),
+            },
+        )
+    }
+
+    fn locked_code(result: Result<(), AppError>) -> Option<u16> {
+        match result {
+            Ok(()) => None,
+            Err(AppError::TooManyRequests {
+                code, retry_after, ..
+            }) => {
+                assert!(retry_after > 0);
+                Some(code)
+            }
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn no_lockout_below_max_failures() {
+        assert_eq!(lockout_duration(&lockout(3), 0), None);
+        assert_eq!(lockout_duration(&lockout(3), 2), None);
+    }
+
+    #[test]
+    fn lockout_doubles_per_failure_up_to_the_cap() {
+        let lockout = lockout(3);
+        assert_eq!(lockout_duration(&lockout, 3), Some(Duration::seconds(60)));
+        assert_eq!(lockout_duration(&lockout, 4), Some(Duration::seconds(120)));
+        assert_eq!(lockout_duration(&lockout, 5), Some(Duration::seconds(240)));
+        assert_eq!(lockout_duration(&lockout, 6), Some(Duration::seconds(480)));
+        assert_eq!(lockout_duration(&lockout, 7), Some(Duration::seconds(600)));
+        assert_eq!(
+            lockout_duration(&lockout, 1000),
+            Some(Duration::seconds(600))
+        );
+    }
+
+    #[tokio::test]
+    async fn account_locks_at_max_failures() {
+        let guard = guard();
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        for _ in 0..2 {
+            guard.record_failure("a@x.com", ip).await.unwrap();
+            assert_eq!(locked_code(guard.check("a@x.com", ip).await), None);
+        }
+        guard.record_failure("A@X.com", ip).await.unwrap();
+        assert_eq!(
+            locked_code(guard.check("a@x.com", ip).await),
+            Some(constants::CODE_ACCOUNT_LOCKED)
+        );
+        // other accounts from the same ip are not locked yet
+        assert_eq!(locked_code(guard.check("b@x.com", ip).await), None);
+    }
+
+    #[tokio::test]
+    async fn ip_locks_across_accounts() {
+        let guard = guard();
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        for n in 0..5 {
+            guard
+                .record_failure(&format!("user{}@x.com", n), ip)
+                .await
+                .unwrap();
+        }
+        assert_eq!(
+            locked_code(guard.check("fresh@x.com", ip).await),
+            Some(constants::CODE_TOO_MANY_ATTEMPTS)
+        );
+        let other_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(
+            locked_code(guard.check("fresh@x.com", other_ip).await),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn success_clears_the_account_counter() {
+        let guard = guard();
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        for _ in 0..2 {
+            guard.record_failure("a@x.com", ip).await.unwrap();
+        }
+        guard.record_success("a@x.com").await.unwrap();
+        for _ in 0..2 {
+            guard.record_failure("a@x.com", ip).await.unwrap();
+        }
+        assert_eq!(locked_code(guard.check("a@x.com", ip).await), None);
+    }
+}
@@ -0,0 +1,42 @@
+use shared::{
+    constants::constants,
+    error::error::AppError,
+    i18n::i18n,
+    mail::{mail_queue::MailQueue, template::MailTemplates},
+};
+
+// renders templated mails in the request locale and queues them for delivery
+pub struct MailService {
+    queue: MailQueue,
+    templates: MailTemplates,
+    app_url: String,
+}
+
+impl MailService {
+    pub fn new(queue: MailQueue, templates: MailTemplates, app_url: String) -> Self {
+        MailService {
+            queue,
+            templates,
+            app_url,
+        }
+    }
+
+    // `app_url` is always available to templates
+    pub fn send(&self, to: &str, template: &str, vars: &[(&str, &str)]) -> Result<(), AppError> {
+        let mut vars = vars.to_vec();
+        vars.push(("app_url", &self.app_url));
+
+        let locale = i18n::current_locale();
+        let mail = self
+            .templates
+            .render(template, locale.as_deref(), to, &vars)
+            .ok_or_else(|| {
+                tracing::error!("mail template `{}` not found", template);
+                AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+            })?;
+
+        self.queue
+            .enqueue(mail)
+            .map_err(|_| AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR))
+    }
+}
@@ -0,0 +1,1156 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use reqwest::Url;
+use shared::{
+    config::config::OAuthConfig,
+    constants::constants,
+    crypto::crypto::{generate_token, pkce_challenge, sha256_hex},
+    error::error::AppError,
+};
+use time::{Duration, OffsetDateTime};
+
+use crate::models::claims::IdTokenClaims;
+use crate::models::oauth::{
+    AuthorizationCode, AuthorizeDecisionReply, AuthorizeDecisionRequest, AuthorizeReply,
+    AuthorizeRequest, ClientCredentials, GRANT_AUTHORIZATION_CODE, GRANT_CLIENT_CREDENTIALS,
+    GRANT_REFRESH_TOKEN, IntrospectReply, OAuthClient, OAuthError, OAuthToken, OpenIdConfiguration,
+    RegisterClientReply, RegisterClientRequest, SCOPE_EMAIL, SCOPE_OPENID, SCOPE_PROFILE,
+    TOKEN_TYPE_ACCESS, TOKEN_TYPE_REFRESH, TokenLookupRequest, TokenReply, TokenRequest,
+    UserInfoReply,
+};
+use crate::models::user::User;
+use crate::repositories::{oauth_repo::OAuthRepo, user_repo::UserRepo};
+use crate::services::signing_key_service::SigningKeyService;
+use idgenerator::*;
+
+const CLIENT_ID_BYTES: usize = 16;
+const SECRET_BYTES: usize = 32;
+
+// RFC 7636: 43 to 128 characters, a base64url encoded SHA-256 is 43
+const CODE_CHALLENGE_LEN: std::ops::RangeInclusive<usize> = 43..=128;
+
+const GRANT_TYPES: [&str; 3] = [
+    GRANT_AUTHORIZATION_CODE,
+    GRANT_CLIENT_CREDENTIALS,
+    GRANT_REFRESH_TOKEN,
+];
+
+const CLAIMS_SUPPORTED: [&str; 12] = [
+    "iss",
+    "sub",
+    "aud",
+    "exp",
+    "iat",
+    "auth_time",
+    "nonce",
+    "email",
+    "email_verified",
+    "name",
+    "locale",
+    "updated_at",
+];
+
+// OAuth 2.0 authorization server and OpenID provider for our other services,
+// access and refresh tokens are opaque and stored hashed, ID tokens are signed JWTs
+pub struct OAuthService {
+    repo: Arc<dyn OAuthRepo>,
+    user_repo: Arc<dyn UserRepo>,
+    keys: Arc<SigningKeyService>,
+    config: OAuthConfig,
+}
+
+impl OAuthService {
+    pub fn new(
+        repo: Arc<dyn OAuthRepo>,
+        user_repo: Arc<dyn UserRepo>,
+        keys: Arc<SigningKeyService>,
+        config: OAuthConfig,
+    ) -> Self {
+        OAuthService {
+            repo,
+            user_repo,
+            keys,
+            config,
+        }
+    }
+
+    pub fn discovery(&self) -> OpenIdConfiguration {
+        let api = format!("{}/api/v1/oauth", self.config.issuer);
+        OpenIdConfiguration {
+            issuer: self.config.issuer.clone(),
+            authorization_endpoint: self
+                .config
+                .authorization_endpoint
+                .clone()
+                .unwrap_or_else(|| format!("{}/authorize", api)),
+            token_endpoint: format!("{}/token", api),
+            userinfo_endpoint: format!("{}/userinfo", api),
+            jwks_uri: format!("{}/.well-known/jwks.json", self.config.issuer),
+            introspection_endpoint: format!("{}/introspect", api),
+            revocation_endpoint: format!("{}/revoke", api),
+            scopes_supported: self.config.scopes.clone(),
+            response_types_supported: vec!["code"],
+            grant_types_supported: GRANT_TYPES.to_vec(),
+            subject_types_supported: vec!["public"],
+            id_token_signing_alg_values_supported: self.keys.algorithms(),
+            token_endpoint_auth_methods_supported: vec![
+                "client_secret_basic",
+                "client_secret_post",
+                "none",
+            ],
+            code_challenge_methods_supported: vec!["S256"],
+            claims_supported: CLAIMS_SUPPORTED.to_vec(),
+        }
+    }
+
+    pub async fn register_client(
+        &self,
+        created_by: i64,
+        req: RegisterClientRequest,
+    ) -> Result<RegisterClientReply, AppError> {
+        let invalid = |detail: String| {
+            AppError::bad_request(constants::CODE_PARAMETER_ERROR).with_detail(detail)
+        };
+
+        for grant_type in &req.grant_types {
+            if !GRANT_TYPES.contains(&grant_type.as_str()) {
+                return Err(invalid(format!("unsupported grant type `{}`", grant_type)));
+            }
+        }
+        let allows = |grant_type: &str| req.grant_types.iter().any(|allowed| allowed == grant_type);
+        if allows(GRANT_CLIENT_CREDENTIALS) && !req.confidential {
+            return Err(invalid(
+                "client_credentials requires a confidential client".to_string(),
+            ));
+        }
+        if allows(GRANT_AUTHORIZATION_CODE) && req.redirect_uris.is_empty() {
+            return Err(invalid(
+                "authorization_code requires a redirect uri".to_string(),
+            ));
+        }
+        for uri in &req.redirect_uris {
+            let parsed =
+                Url::parse(uri).map_err(|_| invalid(format!("invalid redirect uri `{}`", uri)))?;
+            if parsed.fragment().is_some() {
+                return Err(invalid(format!("redirect uri `{}` has a fragment", uri)));
+            }
+        }
+        for scope in &req.scopes {
+            if !self.config.scopes.contains(scope) {
+                return Err(AppError::bad_request(constants::CODE_OAUTH_SCOPE_INVALID)
+                    .with_detail(format!("unknown scope `{}`", scope)));
+            }
+        }
+
+        let client_secret = req.confidential.then(|| generate_token(SECRET_BYTES));
+        let client = OAuthClient {
+            id: IdInstance::next_id(),
+            client_id: generate_token(CLIENT_ID_BYTES),
+            client_secret_hash: client_secret.as_deref().map(sha256_hex),
+            name: req.name,
+            redirect_uris: req.redirect_uris,
+            grant_types: req.grant_types,
+            scopes: req.scopes,
+            created_by: Some(created_by),
+            created_at: OffsetDateTime::now_utc(),
+        };
+        self.repo.create_client(&client).await.map_err(|e| {
+            tracing::error!("database insert oauth client error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+        tracing::info!(
+            "user {} registered oauth client {}",
+            created_by,
+            client.client_id
+        );
+
+        Ok(RegisterClientReply {
+            client_id: client.client_id,
+            client_secret,
+            name: client.name,
+            redirect_uris: client.redirect_uris,
+            grant_types: client.grant_types,
+            scopes: client.scopes,
+        })
+    }
+
+    // validates the request for the consent screen
+    pub async fn authorize(
+        &self,
+        user_id: i64,
+        req: AuthorizeRequest,
+    ) -> Result<AuthorizeReply, AppError> {
+        let (client, scopes) = self.check_authorization(&req).await?;
+        let consented = self.find_consent(user_id, client.id).await?;
+
+        Ok(AuthorizeReply {
+            client_id: client.client_id,
+            client_name: client.name,
+            consent_required: !scopes.is_subset(&consented),
+            scopes: scopes.into_iter().collect(),
+        })
+    }
+
+    // records the user's answer and hands back where to send the browser
+    pub async fn decide(
+        &self,
+        user_id: i64,
+        auth_time: i64,
+        req: AuthorizeDecisionRequest,
+    ) -> Result<AuthorizeDecisionReply, AppError> {
+        let (client, scopes) = self.check_authorization(&req.request).await?;
+        let state = req.request.state.as_deref();
+
+        if !req.approve {
+            return Ok(AuthorizeDecisionReply {
+                redirect_to: redirect_with(
+                    &req.request.redirect_uri,
+                    &[("error", Some("access_denied")), ("state", state)],
+                )?,
+            });
+        }
+
+        // later requests for the same scopes skip the consent screen
+        let mut consented = self.find_consent(user_id, client.id).await?;
+        consented.extend(scopes.iter().cloned());
+        self.repo
+            .save_consent(user_id, client.id, &join_scopes(&consented))
+            .await
+            .map_err(|e| {
+                tracing::error!("database save oauth consent error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        let code = generate_token(SECRET_BYTES);
+        let authorization_code = AuthorizationCode {
+            code_hash: sha256_hex(&code),
+            grant_id: IdInstance::next_id(),
+            client_id: client.id,
+            user_id,
+            redirect_uri: req.request.redirect_uri.clone(),
+            scope: join_scopes(&scopes),
+            code_challenge: req.request.code_challenge.clone(),
+            nonce: req.request.nonce.clone(),
+            auth_time: OffsetDateTime::from_unix_timestamp(auth_time)
+                .unwrap_or_else(|_| OffsetDateTime::now_utc()),
+            expires_at: OffsetDateTime::now_utc()
+                + Duration::seconds(self.config.code_validity_period),
+            used_at: None,
+            created_at: OffsetDateTime::now_utc(),
+        };
+        self.repo
+            .create_code(&authorization_code)
+            .await
+            .map_err(|e| {
+                tracing::error!("database insert authorization code error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        Ok(AuthorizeDecisionReply {
+            redirect_to: redirect_with(
+                &req.request.redirect_uri,
+                &[("code", Some(&code)), ("state", state)],
+            )?,
+        })
+    }
+
+    pub async fn token(
+        &self,
+        credentials: Option<ClientCredentials>,
+        req: TokenRequest,
+    ) -> Result<TokenReply, OAuthError> {
+        let client = self.authenticate_client(credentials).await?;
+        let grant_type = req.grant_type.as_str();
+        if !GRANT_TYPES.contains(&grant_type) {
+            return Err(OAuthError::new("unsupported_grant_type"));
+        }
+        if !client.allows_grant(grant_type) {
+            return Err(OAuthError::new("unauthorized_client"));
+        }
+
+        match grant_type {
+            GRANT_AUTHORIZATION_CODE => self.exchange_code(&client, req).await,
+            GRANT_REFRESH_TOKEN => self.refresh(&client, req).await,
+            _ => {
+                // the client acts on its own behalf, so no refresh token
+                let scopes = requested_scopes(req.scope.as_deref(), &client.scopes)
+                    .ok_or_else(|| OAuthError::new("invalid_scope"))?;
+                Ok(self
+                    .issue_tokens(&client, None, IdInstance::next_id(), &scopes, false)
+                    .await?)
+            }
+        }
+    }
+
+    // RFC 7662, for resource servers, which authenticate as confidential clients
+    pub async fn introspect(
+        &self,
+        credentials: Option<ClientCredentials>,
+        req: TokenLookupRequest,
+    ) -> Result<IntrospectReply, OAuthError> {
+        let caller = self.authenticate_client(credentials).await?;
+        if !caller.is_confidential() {
+            return Err(OAuthError::invalid_client());
+        }
+
+        let Some(token) = self.find_token(&req.token).await? else {
+            return Ok(IntrospectReply::default());
+        };
+        let now = OffsetDateTime::now_utc();
+        let active = token.revoked_at.is_none()
+            && token.expires_at > now
+            && (token.token_type == TOKEN_TYPE_ACCESS || token.used_at.is_none());
+        if !active {
+            return Ok(IntrospectReply::default());
+        }
+
+        let client = self.find_client_by_id(token.client_id).await?;
+        let user = match token.user_id {
+            Some(user_id) => self.find_user(user_id).await?,
+            None => None,
+        };
+        // a disabled account takes its tokens down with it
+        if token.user_id.is_some() && !user.as_ref().is_some_and(|user| user.is_active) {
+            return Ok(IntrospectReply::default());
+        }
+
+        Ok(IntrospectReply {
+            active: true,
+            scope: Some(token.scope),
+            client_id: client.as_ref().map(|client| client.client_id.clone()),
+            username: user.as_ref().map(|user| user.username.clone()),
+            token_type: Some(
+                match token.token_type.as_str() {
+                    TOKEN_TYPE_ACCESS => "Bearer",
+                    _ => TOKEN_TYPE_REFRESH,
+                }
+                .to_string(),
+            ),
+            exp: Some(token.expires_at.unix_timestamp()),
+            iat: Some(token.created_at.unix_timestamp()),
+            sub: match user {
+                Some(user) => Some(user.id.to_string()),
+                None => client.map(|client| client.client_id),
+            },
+        })
+    }
+
+    // OpenID Connect userinfo, answers for the user an access token with `openid` was issued to
+    pub async fn userinfo(&self, access_token: Option<&str>) -> Result<UserInfoReply, OAuthError> {
+        let access_token = access_token.ok_or_else(OAuthError::invalid_token)?;
+        let token = self
+            .find_token(access_token)
+            .await?
+            .filter(|token| {
+                token.token_type == TOKEN_TYPE_ACCESS
+                    && token.revoked_at.is_none()
+                    && token.expires_at > OffsetDateTime::now_utc()
+            })
+            .ok_or_else(OAuthError::invalid_token)?;
+
+        let scopes = split_scopes(&token.scope);
+        if !scopes.contains(SCOPE_OPENID) {
+            return Err(OAuthError::insufficient_scope());
+        }
+        // client_credentials tokens stand for no user
+        let user_id = token.user_id.ok_or_else(OAuthError::invalid_token)?;
+        let user = self
+            .find_user(user_id)
+            .await?
+            .filter(|user| user.is_active)
+            .ok_or_else(OAuthError::invalid_token)?;
+
+        Ok(user_info(&user, &scopes))
+    }
+
+    // RFC 7009, unknown tokens and tokens of other clients are silently ignored
+    pub async fn revoke(
+        &self,
+        credentials: Option<ClientCredentials>,
+        req: TokenLookupRequest,
+    ) -> Result<(), OAuthError> {
+        let client = self.authenticate_client(credentials).await?;
+
+        let Some(token) = self
+            .find_token(&req.token)
+            .await?
+            .filter(|token| token.client_id == client.id)
+        else {
+            return Ok(());
+        };
+
+        // a refresh token stands for the whole grant, access tokens only for themselves
+        let revoked = if token.token_type == TOKEN_TYPE_REFRESH {
+            self.repo.revoke_grant(token.grant_id).await
+        } else {
+            self.repo.revoke_token(token.id).await
+        };
+        revoked.map_err(|e| {
+            tracing::error!("database revoke oauth token error: {}", e);
+            OAuthError::server_error()
+        })
+    }
+
+    async fn exchange_code(
+        &self,
+        client: &OAuthClient,
+        req: TokenRequest,
+    ) -> Result<TokenReply, OAuthError> {
+        let code = req
+            .code
+            .ok_or_else(|| OAuthError::invalid_request("missing code"))?;
+        let redirect_uri = req
+            .redirect_uri
+            .ok_or_else(|| OAuthError::invalid_request("missing redirect_uri"))?;
+        let code_hash = sha256_hex(&code);
+
+        let consumed = self.repo.consume_code(&code_hash).await.map_err(|e| {
+            tracing::error!("database consume authorization code error: {}", e);
+            OAuthError::server_error()
+        })?;
+        let Some(authorization_code) = consumed else {
+            // a replayed code means it leaked, whatever it was exchanged for goes too
+            if let Some(used) = self.repo.find_code(&code_hash).await.map_err(|e| {
+                tracing::error!("database find authorization code error: {}", e);
+                OAuthError::server_error()
+            })? {
+                tracing::warn!(
+                    "authorization code reuse detected, revoking grant {}",
+                    used.grant_id
+                );
+                self.repo.revoke_grant(used.grant_id).await.map_err(|e| {
+                    tracing::error!("database revoke oauth grant error: {}", e);
+                    OAuthError::server_error()
+                })?;
+            }
+            return Err(OAuthError::invalid_grant());
+        };
+
+        if authorization_code.client_id != client.id
+            || authorization_code.redirect_uri != redirect_uri
+            || authorization_code.expires_at <= OffsetDateTime::now_utc()
+        {
+            return Err(OAuthError::invalid_grant());
+        }
+        if let Some(challenge) = &authorization_code.code_challenge {
+            let verifier = req
+                .code_verifier
+                .ok_or_else(|| OAuthError::invalid_request("missing code_verifier"))?;
+            if pkce_challenge(&verifier) != *challenge {
+                return Err(
+                    OAuthError::invalid_grant().with_description("PKCE verification failed")
+                );
+            }
+        }
+
+        let user = self.active_user(authorization_code.user_id).await?;
+        let scopes = split_scopes(&authorization_code.scope);
+        let with_refresh = client.allows_grant(GRANT_REFRESH_TOKEN);
+        let mut reply = self
+            .issue_tokens(
+                client,
+                Some(user.id),
+                authorization_code.grant_id,
+                &scopes,
+                with_refresh,
+            )
+            .await?;
+
+        if scopes.contains(SCOPE_OPENID) {
+            reply.id_token = Some(self.issue_id_token(client, &user, &authorization_code)?);
+        }
+        Ok(reply)
+    }
+
+    fn issue_id_token(
+        &self,
+        client: &OAuthClient,
+        user: &User,
+        authorization_code: &AuthorizationCode,
+    ) -> Result<String, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let claims = IdTokenClaims {
+            iss: self.config.issuer.clone(),
+            aud: client.client_id.clone(),
+            exp: (now + Duration::seconds(self.config.id_token_validity_period)).unix_timestamp(),
+            iat: now.unix_timestamp(),
+            auth_time: authorization_code.auth_time.unix_timestamp(),
+            nonce: authorization_code.nonce.clone(),
+            profile: user_info(user, &split_scopes(&authorization_code.scope)),
+        };
+
+        self.keys.sign(&claims)
+    }
+
+    // rotates the refresh token, presenting a rotated one again revokes the grant
+    async fn refresh(
+        &self,
+        client: &OAuthClient,
+        req: TokenRequest,
+    ) -> Result<TokenReply, OAuthError> {
+        let refresh_token = req
+            .refresh_token
+            .ok_or_else(|| OAuthError::invalid_request("missing refresh_token"))?;
+
+        let token = self
+            .find_token(&refresh_token)
+            .await?
+            .filter(|token| {
+                token.token_type == TOKEN_TYPE_REFRESH
+                    && token.client_id == client.id
+                    && token.revoked_at.is_none()
+                    && token.expires_at > OffsetDateTime::now_utc()
+            })
+            .ok_or_else(OAuthError::invalid_grant)?;
+
+        let rotated = self.repo.mark_token_used(token.id).await.map_err(|e| {
+            tracing::error!("database update oauth token error: {}", e);
+            OAuthError::server_error()
+        })?;
+        if !rotated {
+            tracing::warn!(
+                "oauth refresh token reuse detected, revoking grant {}",
+                token.grant_id
+            );
+            self.repo.revoke_grant(token.grant_id).await.map_err(|e| {
+                tracing::error!("database revoke oauth grant error: {}", e);
+                OAuthError::server_error()
+            })?;
+            return Err(OAuthError::invalid_grant());
+        }
+
+        // the scope may only be narrowed
+        let granted: Vec<String> = split_scopes(&token.scope).into_iter().collect();
+        let scopes = requested_scopes(req.scope.as_deref(), &granted)
+            .ok_or_else(|| OAuthError::new("invalid_scope"))?;
+
+        let user_id = token.user_id.ok_or_else(OAuthError::invalid_grant)?;
+        self.active_user(user_id).await?;
+        Ok(self
+            .issue_tokens(client, Some(user_id), token.grant_id, &scopes, true)
+            .await?)
+    }
+
+    async fn issue_tokens(
+        &self,
+        client: &OAuthClient,
+        user_id: Option<i64>,
+        grant_id: i64,
+        scopes: &BTreeSet<String>,
+        with_refresh: bool,
+    ) -> Result<TokenReply, AppError> {
+        let scope = join_scopes(scopes);
+        let access_token = self
+            .create_token(
+                TOKEN_TYPE_ACCESS,
+                client,
+                user_id,
+                grant_id,
+                &scope,
+                self.config.access_validity_period,
+            )
+            .await?;
+        let refresh_token = if with_refresh {
+            Some(
+                self.create_token(
+                    TOKEN_TYPE_REFRESH,
+                    client,
+                    user_id,
+                    grant_id,
+                    &scope,
+                    self.config.refresh_validity_period,
+                )
+                .await?,
+            )
+        } else {
+            None
+        };
+
+        Ok(TokenReply {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: self.config.access_validity_period,
+            refresh_token,
+            scope,
+            id_token: None,
+        })
+    }
+
+    async fn create_token(
+        &self,
+        token_type: &str,
+        client: &OAuthClient,
+        user_id: Option<i64>,
+        grant_id: i64,
+        scope: &str,
+        validity_period: i64,
+    ) -> Result<String, AppError> {
+        let token = generate_token(SECRET_BYTES);
+        let now = OffsetDateTime::now_utc();
+        let stored = OAuthToken {
+            id: IdInstance::next_id(),
+            token_hash: sha256_hex(&token),
+            token_type: token_type.to_string(),
+            grant_id,
+            client_id: client.id,
+            user_id,
+            scope: scope.to_string(),
+            expires_at: now + Duration::seconds(validity_period),
+            used_at: None,
+            revoked_at: None,
+            created_at: now,
+        };
+        self.repo.create_token(&stored).await.map_err(|e| {
+            tracing::error!("database insert oauth token error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+
+        Ok(token)
+    }
+
+    // public clients only identify themselves, confidential ones must present their secret
+    async fn authenticate_client(
+        &self,
+        credentials: Option<ClientCredentials>,
+    ) -> Result<OAuthClient, OAuthError> {
+        let credentials = credentials.ok_or_else(OAuthError::invalid_client)?;
+        let client = self
+            .repo
+            .find_client(&credentials.client_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find oauth client error: {}", e);
+                OAuthError::server_error()
+            })?
+            .ok_or_else(OAuthError::invalid_client)?;
+
+        let secret_hash = credentials.client_secret.as_deref().map(sha256_hex);
+        if secret_hash != client.client_secret_hash {
+            tracing::warn!("oauth client {} failed to authenticate", client.client_id);
+            return Err(OAuthError::invalid_client());
+        }
+
+        Ok(client)
+    }
+
+    // problems with the client or redirect uri must not redirect, so all of them are errors here
+    async fn check_authorization(
+        &self,
+        req: &AuthorizeRequest,
+    ) -> Result<(OAuthClient, BTreeSet<String>), AppError> {
+        let client = self
+            .repo
+            .find_client(&req.client_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find oauth client error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .filter(|client| client.allows_grant(GRANT_AUTHORIZATION_CODE))
+            .ok_or_else(|| AppError::bad_request(constants::CODE_OAUTH_CLIENT_INVALID))?;
+
+        if !client.redirect_uris.contains(&req.redirect_uri) {
+            return Err(AppError::bad_request(
+                constants::CODE_OAUTH_REDIRECT_URI_INVALID,
+            ));
+        }
+
+        let invalid = |detail: &str| {
+            AppError::bad_request(constants::CODE_OAUTH_REQUEST_INVALID).with_detail(detail)
+        };
+        if req.response_type != "code" {
+            return Err(invalid("response_type must be `code`"));
+        }
+        match &req.code_challenge {
+            Some(challenge) => {
+                if req.code_challenge_method.as_deref() != Some("S256") {
+                    return Err(invalid("code_challenge_method must be `S256`"));
+                }
+                if !CODE_CHALLENGE_LEN.contains(&challenge.len()) {
+                    return Err(invalid("malformed code_challenge"));
+                }
+            }
+            // without a secret, PKCE is all that binds the code to the client
+            None if !client.is_confidential() => {
+                return Err(invalid("public clients must send a code_challenge"));
+            }
+            None => {}
+        }
+
+        let scopes = requested_scopes(req.scope.as_deref(), &client.scopes)
+            .ok_or_else(|| AppError::bad_request(constants::CODE_OAUTH_SCOPE_INVALID))?;
+
+        Ok((client, scopes))
+    }
+
+    async fn find_consent(
+        &self,
+        user_id: i64,
+        client_id: i64,
+    ) -> Result<BTreeSet<String>, AppError> {
+        let consent = self
+            .repo
+            .find_consent(user_id, client_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find oauth consent error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        Ok(consent.as_deref().map(split_scopes).unwrap_or_default())
+    }
+
+    async fn find_token(&self, token: &str) -> Result<Option<OAuthToken>, AppError> {
+        self.repo.find_token(&sha256_hex(token)).await.map_err(|e| {
+            tracing::error!("database find oauth token error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })
+    }
+
+    async fn find_client_by_id(&self, id: i64) -> Result<Option<OAuthClient>, AppError> {
+        self.repo.find_client_by_id(id).await.map_err(|e| {
+            tracing::error!("database find oauth client error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })
+    }
+
+    async fn find_user(&self, user_id: i64) -> Result<Option<User>, AppError> {
+        self.user_repo.find_by_id(user_id).await.map_err(|e| {
+            tracing::error!("database find id error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })
+    }
+
+    // a grant dies with the account it was made for
+    async fn active_user(&self, user_id: i64) -> Result<User, OAuthError> {
+        self.find_user(user_id)
+            .await?
+            .filter(|user| user.is_active)
+            .ok_or_else(OAuthError::invalid_grant)
+    }
+}
+
+// the standard claims `scopes` let the client see
+fn user_info(user: &User, scopes: &BTreeSet<String>) -> UserInfoReply {
+    let email = scopes.contains(SCOPE_EMAIL);
+    let profile = scopes.contains(SCOPE_PROFILE);
+
+    UserInfoReply {
+        sub: user.id.to_string(),
+        email: email.then(|| user.email.clone()),
+        email_verified: email.then_some(user.email_verified_at.is_some()),
+        name: profile.then(|| user.username.clone()),
+        locale: user.locale.clone().filter(|_| profile),
+        updated_at: profile.then_some(user.updated_at.unix_timestamp()),
+    }
+}
+
+// `None` when a requested scope is not in `allowed`, no request means all of `allowed`
+fn requested_scopes(requested: Option<&str>, allowed: &[String]) -> Option<BTreeSet<String>> {
+    let Some(requested) = requested.filter(|requested| !requested.trim().is_empty()) else {
+        return Some(allowed.iter().cloned().collect());
+    };
+
+    let scopes = split_scopes(requested);
+    scopes
+        .iter()
+        .all(|scope| allowed.contains(scope))
+        .then_some(scopes)
+}
+
+fn split_scopes(scope: &str) -> BTreeSet<String> {
+    scope.split_whitespace().map(str::to_string).collect()
+}
+
+fn join_scopes(scopes: &BTreeSet<String>) -> String {
+    scopes.iter().cloned().collect::<Vec<_>>().join(" ")
+}
+
+fn redirect_with(redirect_uri: &str, params: &[(&str, Option<&str>)]) -> Result<String, AppError> {
+    let mut url = Url::parse(redirect_uri).map_err(|e| {
+        tracing::error!("redirect uri {} error: {}", redirect_uri, e);
+        AppError::bad_request(constants::CODE_OAUTH_REDIRECT_URI_INVALID)
+    })?;
+    {
+        let mut query = url.query_pairs_mut();
+        for (name, value) in params {
+            if let Some(value) = value {
+                query.append_pair(name, value);
+            }
+        }
+    }
+    Ok(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::{
+        memory_oauth_repo::MemoryOAuthRepo, memory_signing_key_repo::MemorySigningKeyRepo,
+        memory_user_repo::MemoryUserRepo,
+    };
+    use shared::config::config::JwtSigningConfig;
+
+    const REDIRECT_URI: &str = "https://app.example.com/callback";
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+    struct Harness {
+        service: OAuthService,
+        repo: Arc<MemoryOAuthRepo>,
+        user_id: i64,
+    }
+
+    async fn harness() -> Harness {
+        let repo = Arc::new(MemoryOAuthRepo::new());
+        let users = Arc::new(MemoryUserRepo::new());
+        let user_id = IdInstance::next_id();
+        let now = OffsetDateTime::now_utc();
+        users.insert(User {
+            id: user_id,
+            username: "alice".to_string(),
+            email: "alice@x.com".to_string(),
+            password_hash: String::new(),
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+            role: "user".to_string(),
+            token_version: 0,
+            locale: None,
+            email_verified_at: Some(now),
+            pending_email: None,
+        });
+        let keys = SigningKeyService::new(
+            Arc::new(MemorySigningKeyRepo::new()),
+            JwtSigningConfig {
+                algorithm: "EdDSA".to_string(),
+                encryption_key: "d2Db7M7TqkW5b4J23voREksskX28Ppq6XSEG7tsuAr8=".to_string(),
+                legacy_secret: None,
+                rotation_period: 86400,
+                prepublish_period: 3600,
+                retention_period: 7200,
+                refresh_interval_secs: 60,
+            },
+            600,
+        )
+        .await
+        .unwrap();
+        let service = OAuthService::new(
+            repo.clone(),
+            users,
+            Arc::new(keys),
+            OAuthConfig {
+                issuer: "http://localhost:8080".to_string(),
+                authorization_endpoint: None,
+                code_validity_period: 60,
+                access_validity_period: 600,
+                refresh_validity_period: 3600,
+                id_token_validity_period: 600,
+                scopes: vec![
+                    SCOPE_OPENID.to_string(),
+                    SCOPE_EMAIL.to_string(),
+                    SCOPE_PROFILE.to_string(),
+                ],
+            },
+        );
+        Harness {
+            service,
+            repo,
+            user_id,
+        }
+    }
+
+    async fn register(harness: &Harness, confidential: bool) -> ClientCredentials {
+        let grant_types = if confidential {
+            vec![GRANT_AUTHORIZATION_CODE, GRANT_CLIENT_CREDENTIALS]
+        } else {
+            vec![GRANT_AUTHORIZATION_CODE, GRANT_REFRESH_TOKEN]
+        };
+        let reply = harness
+            .service
+            .register_client(
+                harness.user_id,
+                RegisterClientRequest {
+                    name: "app".to_string(),
+                    redirect_uris: vec![REDIRECT_URI.to_string()],
+                    grant_types: grant_types.into_iter().map(str::to_string).collect(),
+                    scopes: vec![SCOPE_OPENID.to_string(), SCOPE_EMAIL.to_string()],
+                    confidential,
+                },
+            )
+            .await
+            .unwrap();
+        ClientCredentials {
+            client_id: reply.client_id,
+            client_secret: reply.client_secret,
+        }
+    }
+
+    fn copy(credentials: &ClientCredentials) -> Option<ClientCredentials> {
+        Some(ClientCredentials {
+            client_id: credentials.client_id.clone(),
+            client_secret: credentials.client_secret.clone(),
+        })
+    }
+
+    // the code handed back in the redirect, bound to `VERIFIER`
+    async fn authorize(harness: &Harness, client: &ClientCredentials) -> String {
+        let reply = harness
+            .service
+            .decide(
+                harness.user_id,
+                OffsetDateTime::now_utc().unix_timestamp(),
+                AuthorizeDecisionRequest {
+                    request: AuthorizeRequest {
+                        response_type: "code".to_string(),
+                        client_id: client.client_id.clone(),
+                        redirect_uri: REDIRECT_URI.to_string(),
+                        scope: Some("openid email".to_string()),
+                        state: Some("state".to_string()),
+                        code_challenge: Some(pkce_challenge(VERIFIER)),
+                        code_challenge_method: Some("S256".to_string()),
+                        nonce: None,
+                    },
+                    approve: true,
+                },
+            )
+            .await
+            .unwrap();
+        Url::parse(&reply.redirect_to)
+            .unwrap()
+            .query_pairs()
+            .find(|(name, _)| name == "code")
+            .map(|(_, code)| code.into_owned())
+            .unwrap()
+    }
+
+    fn exchange(code: &str, redirect_uri: &str, verifier: &str) -> TokenRequest {
+        TokenRequest {
+            grant_type: GRANT_AUTHORIZATION_CODE.to_string(),
+            code: Some(code.to_string()),
+            redirect_uri: Some(redirect_uri.to_string()),
+            code_verifier: Some(verifier.to_string()),
+            refresh_token: None,
+            scope: None,
+            client_id: None,
+            client_secret: None,
+        }
+    }
+
+    fn refresh(refresh_token: &str, scope: Option<&str>) -> TokenRequest {
+        TokenRequest {
+            grant_type: GRANT_REFRESH_TOKEN.to_string(),
+            code: None,
+            redirect_uri: None,
+            code_verifier: None,
+            refresh_token: Some(refresh_token.to_string()),
+            scope: scope.map(str::to_string),
+            client_id: None,
+            client_secret: None,
+        }
+    }
+
+    async fn revoked(harness: &Harness, token: &str) -> bool {
+        let stored = harness.repo.find_token(&sha256_hex(token)).await.unwrap();
+        stored.unwrap().revoked_at.is_some()
+    }
+
+    #[tokio::test]
+    async fn pkce_verifier_must_match_the_challenge() {
+        let harness = harness().await;
+        let client = register(&harness, false).await;
+
+        let code = authorize(&harness, &client).await;
+        let error = harness
+            .service
+            .token(
+                copy(&client),
+                exchange(&code, REDIRECT_URI, "another-verifier"),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.error, "invalid_grant");
+
+        let code = authorize(&harness, &client).await;
+        let reply = harness
+            .service
+            .token(copy(&client), exchange(&code, REDIRECT_URI, VERIFIER))
+            .await
+            .unwrap();
+        assert!(reply.id_token.is_some());
+        assert!(reply.refresh_token.is_some());
+    }
+
+    #[tokio::test]
+    async fn redirect_uri_must_match_the_authorization() {
+        let harness = harness().await;
+        let client = register(&harness, false).await;
+
+        let code = authorize(&harness, &client).await;
+        let error = harness
+            .service
+            .token(
+                copy(&client),
+                exchange(&code, "https://evil.example.com/callback", VERIFIER),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.error, "invalid_grant");
+    }
+
+    #[tokio::test]
+    async fn replayed_code_revokes_the_grant() {
+        let harness = harness().await;
+        let client = register(&harness, false).await;
+
+        let code = authorize(&harness, &client).await;
+        let reply = harness
+            .service
+            .token(copy(&client), exchange(&code, REDIRECT_URI, VERIFIER))
+            .await
+            .unwrap();
+        assert!(!revoked(&harness, &reply.access_token).await);
+
+        let error = harness
+            .service
+            .token(copy(&client), exchange(&code, REDIRECT_URI, VERIFIER))
+            .await
+            .unwrap_err();
+        assert_eq!(error.error, "invalid_grant");
+        assert!(revoked(&harness, &reply.access_token).await);
+        assert!(revoked(&harness, &reply.refresh_token.unwrap()).await);
+    }
+
+    #[tokio::test]
+    async fn reused_refresh_token_revokes_the_grant() {
+        let harness = harness().await;
+        let client = register(&harness, false).await;
+        let code = authorize(&harness, &client).await;
+        let first = harness
+            .service
+            .token(copy(&client), exchange(&code, REDIRECT_URI, VERIFIER))
+            .await
+            .unwrap()
+            .refresh_token
+            .unwrap();
+
+        let rotated = harness
+            .service
+            .token(copy(&client), refresh(&first, None))
+            .await
+            .unwrap();
+        assert!(!revoked(&harness, &rotated.access_token).await);
+
+        let error = harness
+            .service
+            .token(copy(&client), refresh(&first, None))
+            .await
+            .unwrap_err();
+        assert_eq!(error.error, "invalid_grant");
+        assert!(revoked(&harness, &rotated.access_token).await);
+        assert!(revoked(&harness, &rotated.refresh_token.unwrap()).await);
+    }
+
+    #[tokio::test]
+    async fn confidential_clients_must_present_their_secret() {
+        let harness = harness().await;
+        let confidential = register(&harness, true).await;
+        let public = register(&harness, false).await;
+        let client_credentials = || TokenRequest {
+            grant_type: GRANT_CLIENT_CREDENTIALS.to_string(),
+            code: None,
+            redirect_uri: None,
+            code_verifier: None,
+            refresh_token: None,
+            scope: None,
+            client_id: None,
+            client_secret: None,
+        };
+        let with_secret = |credentials: &ClientCredentials, secret: Option<&str>| {
+            Some(ClientCredentials {
+                client_id: credentials.client_id.clone(),
+                client_secret: secret.map(str::to_string),
+            })
+        };
+
+        for credentials in [
+            None,
+            with_secret(&confidential, None),
+            with_secret(&confidential, Some("wrong-secret")),
+            // public clients have no secret to present
+            with_secret(&public, Some("any-secret")),
+        ] {
+            let error = harness
+                .service
+                .token(credentials, client_credentials())
+                .await
+                .unwrap_err();
+            assert_eq!(error.error, "invalid_client");
+        }
+
+        let reply = harness
+            .service
+            .token(copy(&confidential), client_credentials())
+            .await
+            .unwrap();
+        assert!(reply.refresh_token.is_none());
+        // a public client identifies itself, but may not use a grant it was not registered for
+        let error = harness
+            .service
+            .token(with_secret(&public, None), client_credentials())
+            .await
+            .unwrap_err();
+        assert_eq!(error.error, "unauthorized_client");
+    }
+
+    #[tokio::test]
+    async fn refresh_can_only_narrow_the_scope() {
+        let harness = harness().await;
+        let client = register(&harness, false).await;
+        let code = authorize(&harness, &client).await;
+        let reply = harness
+            .service
+            .token(copy(&client), exchange(&code, REDIRECT_URI, VERIFIER))
+            .await
+            .unwrap();
+        assert_eq!(reply.scope, "email openid");
+
+        let narrowed = harness
+            .service
+            .token(
+                copy(&client),
+                refresh(&reply.refresh_token.unwrap(), Some("openid")),
+            )
+            .await
+            .unwrap();
+        assert_eq!(narrowed.scope, "openid");
+
+        let error = harness
+            .service
+            .token(
+                copy(&client),
+                refresh(&narrowed.refresh_token.unwrap(), Some("openid email")),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.error, "invalid_scope");
+    }
+
+    #[test]
+    fn requested_scopes_defaults_to_all_allowed() {
+        let allowed = vec![SCOPE_OPENID.to_string(), SCOPE_EMAIL.to_string()];
+
+        assert_eq!(
+            requested_scopes(None, &allowed),
+            Some(split_scopes("openid email"))
+        );
+        assert_eq!(
+            requested_scopes(Some("  "), &allowed),
+            Some(split_scopes("openid email"))
+        );
+        assert_eq!(
+            requested_scopes(Some("email  email"), &allowed),
+            Some(split_scopes("email"))
+        );
+        assert_eq!(requested_scopes(Some("openid profile"), &allowed), None);
+    }
+}
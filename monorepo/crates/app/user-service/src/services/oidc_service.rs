@@ -0,0 +1,833 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header, jwk::JwkSet};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use shared::{
+    config::config::{OidcConfig, OidcProviderConfig},
+    constants::constants,
+    crypto::crypto::{generate_token, pkce_challenge},
+    error::error::AppError,
+    extract::client_device::ClientDevice,
+};
+
+use crate::models::oidc::{OidcAuthorizeReply, OidcCallbackRequest};
+use crate::models::user::{LoginReply, User};
+use crate::repositories::{identity_repo::IdentityRepo, user_repo::UserRepo};
+use crate::services::user_service::UserService;
+use idgenerator::*;
+
+use bcrypt::{DEFAULT_COST, hash};
+
+const STATE_BYTES: usize = 32;
+const MAX_USERNAME_LEN: usize = 80;
+// `name`, `name2`, ... `name5` are tried before falling back to a unique suffix
+const MAX_USERNAME_ATTEMPTS: u32 = 5;
+
+// the subset of the discovery document the relying party needs
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    name: Option<String>,
+    preferred_username: Option<String>,
+}
+
+// what has to come back with the provider's redirect
+struct PendingLogin {
+    started: Instant,
+    provider: String,
+    nonce: String,
+    code_verifier: String,
+}
+
+struct Cached<T> {
+    fetched: Instant,
+    value: Arc<T>,
+}
+
+// relying party of the configured OpenID Connect providers, authorization code flow with PKCE
+pub struct OidcService {
+    repo: Arc<dyn IdentityRepo>,
+    user_repo: Arc<dyn UserRepo>,
+    users: Arc<UserService>,
+    client: OidcClient,
+}
+
+// the provider facing half: discovery, authorization requests and ID token checks
+struct OidcClient {
+    config: OidcConfig,
+    http: Client,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+    metadata: Mutex<HashMap<String, Cached<ProviderMetadata>>>,
+    jwks: Mutex<HashMap<String, Cached<JwkSet>>>,
+}
+
+impl OidcService {
+    pub fn new(
+        repo: Arc<dyn IdentityRepo>,
+        user_repo: Arc<dyn UserRepo>,
+        users: Arc<UserService>,
+        config: OidcConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(OidcService {
+            repo,
+            user_repo,
+            users,
+            client: OidcClient::new(config)?,
+        })
+    }
+
+    pub async fn authorize(&self, provider: &str) -> Result<OidcAuthorizeReply, AppError> {
+        self.client.authorize(provider).await
+    }
+
+    // signs in the linked account, linking or creating one on the first login
+    pub async fn callback(
+        &self,
+        provider: &str,
+        req: OidcCallbackRequest,
+        device: ClientDevice,
+    ) -> Result<LoginReply, AppError> {
+        let claims = self.client.verify_callback(provider, &req).await?;
+        let user = self.resolve_user(provider, &claims).await?;
+        self.users.complete_login(user, device).await
+    }
+
+    async fn resolve_user(&self, provider: &str, claims: &IdTokenClaims) -> Result<User, AppError> {
+        let email = claims.email.as_deref().filter(|_| claims.email_verified);
+
+        let identity = self.repo.find(provider, &claims.sub).await.map_err(|e| {
+            tracing::error!("database find identity error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+        if let Some(identity) = identity {
+            self.repo
+                .record_login(identity.id, email)
+                .await
+                .map_err(|e| {
+                    tracing::error!("database update identity error: {}", e);
+                    AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                })?;
+            return self.find_user(identity.user_id).await;
+        }
+
+        // linking goes by email, so it has to be one the provider vouches for
+        let email =
+            email.ok_or_else(|| AppError::forbidden(constants::CODE_OIDC_EMAIL_UNVERIFIED))?;
+        let existing_user = self.user_repo.find_by_email(email).await.map_err(|e| {
+            tracing::error!("database find email error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+
+        let user_id = match existing_user {
+            // whoever registered an unverified account never proved they own the address
+            Some(user) if user.email_verified_at.is_none() => {
+                return Err(AppError::conflict(constants::CODE_ACCOUNT_ALREADY_EXISTS)
+                    .with_detail("verify the email of the existing account before linking"));
+            }
+            Some(user) => user.id,
+            None => self.create_user(claims, email).await?,
+        };
+
+        self.repo
+            .create(
+                IdInstance::next_id(),
+                user_id,
+                provider,
+                &claims.sub,
+                Some(email),
+            )
+            .await
+            .map_err(|e| {
+                tracing::error!("database insert identity error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        tracing::info!(
+            "linked {} identity {} to user {}",
+            provider,
+            claims.sub,
+            user_id
+        );
+
+        self.find_user(user_id).await
+    }
+
+    // the account gets a random password, one can be set through the reset flow
+    async fn create_user(&self, claims: &IdTokenClaims, email: &str) -> Result<i64, AppError> {
+        let name = claims
+            .preferred_username
+            .as_deref()
+            .or(claims.name.as_deref())
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| email.split('@').next().unwrap_or(email))
+            .trim();
+        let username = self.free_username(name).await?;
+
+        let hashed = hash(generate_token(STATE_BYTES), DEFAULT_COST).map_err(|e| {
+            tracing::error!("hash error: {}", e);
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        })?;
+
+        let id = IdInstance::next_id();
+        self.user_repo
+            .create(id, username, email.to_string(), hashed)
+            .await
+            .map_err(|e| {
+                // a concurrent registration took the email after the lookup
+                if e.as_database_error()
+                    .is_some_and(|e| e.is_unique_violation())
+                {
+                    return AppError::conflict(constants::CODE_ACCOUNT_ALREADY_EXISTS);
+                }
+                tracing::error!("database insert error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        // the provider already verified the address
+        self.user_repo
+            .mark_email_verified(id, email)
+            .await
+            .map_err(|e| {
+                tracing::error!("database verify email error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        Ok(id)
+    }
+
+    // provider names are not unique, taken ones get a numeric suffix
+    async fn free_username(&self, name: &str) -> Result<String, AppError> {
+        for attempt in 1..=MAX_USERNAME_ATTEMPTS {
+            let suffix = if attempt == 1 {
+                String::new()
+            } else {
+                attempt.to_string()
+            };
+            let candidate = username_with_suffix(name, &suffix);
+            let existing = self
+                .user_repo
+                .find_by_username(&candidate)
+                .await
+                .map_err(|e| {
+                    tracing::error!("database find username error: {}", e);
+                    AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                })?;
+            if existing.is_none() {
+                return Ok(candidate);
+            }
+        }
+
+        Ok(username_with_suffix(
+            name,
+            &IdInstance::next_id().to_string(),
+        ))
+    }
+
+    async fn find_user(&self, user_id: i64) -> Result<User, AppError> {
+        self.user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))
+    }
+}
+
+impl OidcClient {
+    fn new(config: OidcConfig) -> Result<Self, Box<dyn Error>> {
+        let http = Client::builder()
+            .timeout(Duration::from_secs(config.http_timeout_secs))
+            .build()?;
+
+        Ok(OidcClient {
+            config,
+            http,
+            pending: Mutex::new(HashMap::new()),
+            metadata: Mutex::new(HashMap::new()),
+            jwks: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn authorize(&self, provider: &str) -> Result<OidcAuthorizeReply, AppError> {
+        let provider_config = self.provider(provider)?;
+        let metadata = self.metadata(provider, provider_config).await?;
+
+        let state = generate_token(STATE_BYTES);
+        let nonce = generate_token(STATE_BYTES);
+        let code_verifier = generate_token(STATE_BYTES);
+
+        let mut url = Url::parse(&metadata.authorization_endpoint).map_err(|e| {
+            tracing::error!("oidc {} authorization endpoint error: {}", provider, e);
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        })?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider_config.client_id)
+            .append_pair("redirect_uri", &provider_config.redirect_uri)
+            .append_pair("scope", &provider_config.scopes.join(" "))
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &pkce_challenge(&code_verifier))
+            .append_pair("code_challenge_method", "S256");
+
+        let ttl = self.state_ttl();
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, login| login.started.elapsed() < ttl);
+        pending.insert(
+            state.clone(),
+            PendingLogin {
+                started: Instant::now(),
+                provider: provider.to_string(),
+                nonce,
+                code_verifier,
+            },
+        );
+
+        Ok(OidcAuthorizeReply {
+            authorization_url: url.to_string(),
+            state,
+        })
+    }
+
+    // claims of the ID token the provider issued for this callback
+    async fn verify_callback(
+        &self,
+        provider: &str,
+        req: &OidcCallbackRequest,
+    ) -> Result<IdTokenClaims, AppError> {
+        let provider_config = self.provider(provider)?;
+
+        // a state is good for one attempt, and only with the provider it was issued for
+        let ttl = self.state_ttl();
+        let pending = self
+            .pending
+            .lock()
+            .unwrap()
+            .remove(&req.state)
+            .filter(|login| login.provider == provider && login.started.elapsed() < ttl)
+            .ok_or_else(|| AppError::bad_request(constants::CODE_OIDC_STATE_INVALID))?;
+
+        let metadata = self.metadata(provider, provider_config).await?;
+        let id_token = self
+            .exchange_code(provider, provider_config, &metadata, &req.code, &pending)
+            .await?;
+        let claims = self
+            .verify_id_token(provider, provider_config, &metadata, &id_token)
+            .await?;
+        if claims.nonce.as_deref() != Some(pending.nonce.as_str()) {
+            tracing::warn!("oidc {} id token nonce mismatch", provider);
+            return Err(AppError::unauthorized(constants::CODE_OIDC_LOGIN_FAILED));
+        }
+
+        Ok(claims)
+    }
+
+    async fn exchange_code(
+        &self,
+        provider: &str,
+        provider_config: &OidcProviderConfig,
+        metadata: &ProviderMetadata,
+        code: &str,
+        pending: &PendingLogin,
+    ) -> Result<String, AppError> {
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .basic_auth(
+                &provider_config.client_id,
+                Some(&provider_config.client_secret),
+            )
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", provider_config.redirect_uri.as_str()),
+                ("code_verifier", pending.code_verifier.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| {
+                tracing::error!("oidc {} token request error: {}", provider, e);
+                AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+            })?;
+
+        // an expired or replayed code is the usual reason, the body says which
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            tracing::warn!(
+                "oidc {} token endpoint returned {}: {}",
+                provider,
+                status,
+                body
+            );
+            return Err(AppError::unauthorized(constants::CODE_OIDC_LOGIN_FAILED));
+        }
+
+        response
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| {
+                tracing::warn!("oidc {} token response error: {}", provider, e);
+                AppError::unauthorized(constants::CODE_OIDC_LOGIN_FAILED)
+            })?
+            .id_token
+            .ok_or_else(|| {
+                tracing::warn!("oidc {} token response without id_token", provider);
+                AppError::unauthorized(constants::CODE_OIDC_LOGIN_FAILED)
+            })
+    }
+
+    async fn verify_id_token(
+        &self,
+        provider: &str,
+        provider_config: &OidcProviderConfig,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let rejected = |e: &dyn std::fmt::Display| {
+            tracing::warn!("oidc {} id token rejected: {}", provider, e);
+            AppError::unauthorized(constants::CODE_OIDC_LOGIN_FAILED)
+        };
+
+        let header = decode_header(id_token).map_err(|e| rejected(&e))?;
+        // only keys published by the provider are trusted, never a shared secret
+        if matches!(
+            header.alg,
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+        ) {
+            return Err(rejected(&format!("{:?} is not allowed", header.alg)));
+        }
+
+        // an unknown kid usually means the provider rotated its keys since the last fetch
+        let mut jwks = self.jwks(provider, metadata, false).await?;
+        if find_key(&jwks, header.kid.as_deref()).is_none() {
+            jwks = self.jwks(provider, metadata, true).await?;
+        }
+        let jwk = find_key(&jwks, header.kid.as_deref())
+            .ok_or_else(|| rejected(&format!("no signing key {:?}", header.kid)))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|e| rejected(&e))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&provider_config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| rejected(&e))
+    }
+
+    fn provider(&self, provider: &str) -> Result<&OidcProviderConfig, AppError> {
+        self.config
+            .providers
+            .get(provider)
+            .ok_or_else(|| AppError::not_found(constants::CODE_OIDC_PROVIDER_NOT_FOUND))
+    }
+
+    fn state_ttl(&self) -> Duration {
+        Duration::from_secs(self.config.state_ttl_secs)
+    }
+
+    fn metadata_ttl(&self) -> Duration {
+        Duration::from_secs(self.config.metadata_ttl_secs)
+    }
+
+    async fn metadata(
+        &self,
+        provider: &str,
+        provider_config: &OidcProviderConfig,
+    ) -> Result<Arc<ProviderMetadata>, AppError> {
+        if let Some(cached) = self.metadata.lock().unwrap().get(provider)
+            && cached.fetched.elapsed() < self.metadata_ttl()
+        {
+            return Ok(cached.value.clone());
+        }
+
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            provider_config.issuer.trim_end_matches('/')
+        );
+        let metadata: ProviderMetadata = self.fetch_json(provider, &url).await?;
+        // a document announcing another issuer would let that issuer's tokens through
+        if metadata.issuer != provider_config.issuer {
+            tracing::error!(
+                "oidc {} discovery issuer {} does not match {}",
+                provider,
+                metadata.issuer,
+                provider_config.issuer
+            );
+            return Err(AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR));
+        }
+
+        let value = Arc::new(metadata);
+        self.metadata.lock().unwrap().insert(
+            provider.to_string(),
+            Cached {
+                fetched: Instant::now(),
+                value: value.clone(),
+            },
+        );
+        Ok(value)
+    }
+
+    async fn jwks(
+        &self,
+        provider: &str,
+        metadata: &ProviderMetadata,
+        refresh: bool,
+    ) -> Result<Arc<JwkSet>, AppError> {
+        if !refresh
+            && let Some(cached) = self.jwks.lock().unwrap().get(provider)
+            && cached.fetched.elapsed() < self.metadata_ttl()
+        {
+            return Ok(cached.value.clone());
+        }
+
+        let value = Arc::new(
+            self.fetch_json::<JwkSet>(provider, &metadata.jwks_uri)
+                .await?,
+        );
+        self.jwks.lock().unwrap().insert(
+            provider.to_string(),
+            Cached {
+                fetched: Instant::now(),
+                value: value.clone(),
+            },
+        );
+        Ok(value)
+    }
+
+    async fn fetch_json<T: for<'de> Deserialize<'de>>(
+        &self,
+        provider: &str,
+        url: &str,
+    ) -> Result<T, AppError> {
+        let failed = |e: reqwest::Error| {
+            tracing::error!("oidc {} fetch {} error: {}", provider, url, e);
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        };
+
+        self.http
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(failed)?
+            .json::<T>()
+            .await
+            .map_err(failed)
+    }
+}
+
+// `name` shortened so that the suffix still fits the username column
+fn username_with_suffix(name: &str, suffix: &str) -> String {
+    let keep = MAX_USERNAME_LEN.saturating_sub(suffix.chars().count());
+    name.chars().take(keep).chain(suffix.chars()).collect()
+}
+
+// a token without kid is accepted when the provider publishes a single key
+fn find_key<'a>(jwks: &'a JwkSet, kid: Option<&str>) -> Option<&'a jsonwebtoken::jwk::Jwk> {
+    match kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::signing_key_service::generate_key_pair;
+    use axum::{
+        Form, Json, Router,
+        extract::State,
+        http::StatusCode,
+        response::{IntoResponse, Response},
+        routing::{get, post},
+    };
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use serde::Serialize;
+    use time::OffsetDateTime;
+    use tokio::net::TcpListener;
+
+    const CLIENT_ID: &str = "relying-party";
+    const KID: &str = "mock-key";
+
+    #[derive(Serialize)]
+    struct Discovery {
+        issuer: String,
+        authorization_endpoint: String,
+        token_endpoint: String,
+        jwks_uri: String,
+    }
+
+    #[derive(Serialize)]
+    struct TokenReply {
+        id_token: String,
+    }
+
+    #[derive(Serialize)]
+    struct TestClaims {
+        iss: String,
+        aud: String,
+        sub: String,
+        exp: i64,
+        nonce: String,
+        email: String,
+        email_verified: bool,
+    }
+
+    // in-process provider, codes map to the PKCE challenge and the ID token they redeem for
+    #[derive(Clone)]
+    struct MockIssuer {
+        base: String,
+        jwks: Arc<JwkSet>,
+        key: Arc<EncodingKey>,
+        codes: Arc<Mutex<HashMap<String, (String, String)>>>,
+    }
+
+    impl MockIssuer {
+        async fn start() -> MockIssuer {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let (pem, jwk) = generate_key_pair(Algorithm::EdDSA, KID.to_string()).unwrap();
+            let issuer = MockIssuer {
+                base: format!("http://{}", listener.local_addr().unwrap()),
+                jwks: Arc::new(JwkSet { keys: vec![jwk] }),
+                key: Arc::new(EncodingKey::from_ed_pem(pem.as_bytes()).unwrap()),
+                codes: Arc::new(Mutex::new(HashMap::new())),
+            };
+
+            // `/other` announces the issuer of `/` in its discovery document
+            let app = Router::new()
+                .route("/.well-known/openid-configuration", get(discovery))
+                .route("/other/.well-known/openid-configuration", get(discovery))
+                .route("/jwks", get(jwks))
+                .route("/token", post(token))
+                .with_state(issuer.clone());
+            tokio::spawn(async move { axum::serve(listener, app).await });
+            issuer
+        }
+
+        fn client(&self) -> OidcClient {
+            let provider = |issuer: String| OidcProviderConfig {
+                issuer,
+                client_id: CLIENT_ID.to_string(),
+                client_secret: "secret".to_string(),
+                redirect_uri: "http://localhost/callback".to_string(),
+                scopes: vec!["openid".to_string(), "email".to_string()],
+            };
+            OidcClient::new(OidcConfig {
+                state_ttl_secs: 600,
+                metadata_ttl_secs: 3600,
+                http_timeout_secs: 5,
+                providers: HashMap::from([
+                    ("mock".to_string(), provider(self.base.clone())),
+                    (
+                        "other".to_string(),
+                        provider(format!("{}/other", self.base)),
+                    ),
+                ]),
+            })
+            .unwrap()
+        }
+
+        fn claims(&self, nonce: &str) -> TestClaims {
+            TestClaims {
+                iss: self.base.clone(),
+                aud: CLIENT_ID.to_string(),
+                sub: "subject-1".to_string(),
+                exp: OffsetDateTime::now_utc().unix_timestamp() + 300,
+                nonce: nonce.to_string(),
+                email: "alice@example.com".to_string(),
+                email_verified: true,
+            }
+        }
+
+        // plays the browser: follows the authorization url and comes back with a code
+        async fn login(
+            &self,
+            client: &OidcClient,
+            edit: impl FnOnce(&mut TestClaims),
+        ) -> OidcCallbackRequest {
+            let reply = client.authorize("mock").await.unwrap();
+            let url = Url::parse(&reply.authorization_url).unwrap();
+            let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            assert_eq!(query["response_type"], "code");
+            assert_eq!(query["client_id"], CLIENT_ID);
+            assert_eq!(query["state"], reply.state);
+            assert_eq!(query["code_challenge_method"], "S256");
+
+            let mut claims = self.claims(&query["nonce"]);
+            edit(&mut claims);
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = Some(KID.to_string());
+            let id_token = encode(&header, &claims, &self.key).unwrap();
+
+            let code = generate_token(16);
+            self.codes
+                .lock()
+                .unwrap()
+                .insert(code.clone(), (query["code_challenge"].clone(), id_token));
+            OidcCallbackRequest {
+                state: reply.state,
+                code,
+            }
+        }
+    }
+
+    async fn discovery(State(issuer): State<MockIssuer>) -> Json<Discovery> {
+        Json(Discovery {
+            issuer: issuer.base.clone(),
+            authorization_endpoint: format!("{}/authorize", issuer.base),
+            token_endpoint: format!("{}/token", issuer.base),
+            jwks_uri: format!("{}/jwks", issuer.base),
+        })
+    }
+
+    async fn jwks(State(issuer): State<MockIssuer>) -> Json<JwkSet> {
+        Json(issuer.jwks.as_ref().clone())
+    }
+
+    async fn token(
+        State(issuer): State<MockIssuer>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Response {
+        let redeemed = form
+            .get("code")
+            .and_then(|code| issuer.codes.lock().unwrap().remove(code));
+        match (redeemed, form.get("code_verifier")) {
+            (Some((challenge, id_token)), Some(verifier))
+                if form.get("grant_type").map(String::as_str) == Some("authorization_code")
+                    && pkce_challenge(verifier) == challenge =>
+            {
+                Json(TokenReply { id_token }).into_response()
+            }
+            _ => (StatusCode::BAD_REQUEST, "invalid_grant").into_response(),
+        }
+    }
+
+    fn assert_login_failed(result: Result<IdTokenClaims, AppError>) {
+        let e = result.unwrap_err();
+        assert_eq!(e.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(e.code(), constants::CODE_OIDC_LOGIN_FAILED);
+    }
+
+    #[tokio::test]
+    async fn callback_yields_the_verified_claims_once() {
+        let issuer = MockIssuer::start().await;
+        let client = issuer.client();
+
+        let req = issuer.login(&client, |_| {}).await;
+        let claims = client.verify_callback("mock", &req).await.unwrap();
+        assert_eq!(claims.sub, "subject-1");
+        assert_eq!(claims.email.as_deref(), Some("alice@example.com"));
+        assert!(claims.email_verified);
+
+        // the state was spent by the first callback
+        let e = client.verify_callback("mock", &req).await.unwrap_err();
+        assert_eq!(e.code(), constants::CODE_OIDC_STATE_INVALID);
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_state_and_state_of_another_provider() {
+        let issuer = MockIssuer::start().await;
+        let client = issuer.client();
+
+        let mut req = issuer.login(&client, |_| {}).await;
+        let e = client.verify_callback("other", &req).await.unwrap_err();
+        assert_eq!(e.code(), constants::CODE_OIDC_STATE_INVALID);
+
+        req.state = generate_token(STATE_BYTES);
+        let e = client.verify_callback("mock", &req).await.unwrap_err();
+        assert_eq!(e.code(), constants::CODE_OIDC_STATE_INVALID);
+    }
+
+    #[tokio::test]
+    async fn rejects_nonce_mismatch() {
+        let issuer = MockIssuer::start().await;
+        let client = issuer.client();
+
+        let req = issuer
+            .login(&client, |claims| claims.nonce = "replayed".to_string())
+            .await;
+        assert_login_failed(client.verify_callback("mock", &req).await);
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_issuer_audience_and_expired_tokens() {
+        let issuer = MockIssuer::start().await;
+        let client = issuer.client();
+
+        let req = issuer
+            .login(&client, |claims| {
+                claims.iss = "https://evil.example".to_string()
+            })
+            .await;
+        assert_login_failed(client.verify_callback("mock", &req).await);
+
+        let req = issuer
+            .login(&client, |claims| claims.aud = "another-client".to_string())
+            .await;
+        assert_login_failed(client.verify_callback("mock", &req).await);
+
+        let req = issuer.login(&client, |claims| claims.exp -= 3600).await;
+        assert_login_failed(client.verify_callback("mock", &req).await);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_code_the_token_endpoint_refuses() {
+        let issuer = MockIssuer::start().await;
+        let client = issuer.client();
+
+        // the provider expects another PKCE verifier
+        let req = issuer.login(&client, |_| {}).await;
+        issuer.codes.lock().unwrap().get_mut(&req.code).unwrap().0 = pkce_challenge("other");
+        assert_login_failed(client.verify_callback("mock", &req).await);
+    }
+
+    #[tokio::test]
+    async fn rejects_discovery_announcing_another_issuer() {
+        let issuer = MockIssuer::start().await;
+        let client = issuer.client();
+
+        let e = client.authorize("other").await.unwrap_err();
+        assert_eq!(e.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let e = client.authorize("unknown").await.unwrap_err();
+        assert_eq!(e.code(), constants::CODE_OIDC_PROVIDER_NOT_FOUND);
+    }
+
+    #[test]
+    fn suffixed_usernames_fit_the_column() {
+        assert_eq!(username_with_suffix("alice", ""), "alice");
+        assert_eq!(username_with_suffix("alice", "2"), "alice2");
+
+        let long = "名".repeat(MAX_USERNAME_LEN);
+        let username = username_with_suffix(&long, "12");
+        assert_eq!(username.chars().count(), MAX_USERNAME_LEN);
+        assert!(username.ends_with("12"));
+    }
+}
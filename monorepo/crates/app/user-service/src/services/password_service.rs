@@ -0,0 +1,224 @@
+use std::sync::Arc;
+
+use shared::{
+    constants::constants,
+    crypto::crypto::{generate_token, sha256_hex},
+    error::error::AppError,
+    i18n::i18n,
+    password::policy::PasswordPolicy,
+};
+use time::{Duration, OffsetDateTime};
+
+use crate::models::password_reset::{ForgotPasswordRequest, ResetPasswordRequest};
+use crate::repositories::{
+    oauth_repo::OAuthRepo, password_reset_repo::PasswordResetRepo,
+    refresh_token_repo::RefreshTokenRepo, session_repo::SessionRepo, user_repo::UserRepo,
+};
+use crate::services::mail_service::MailService;
+use idgenerator::*;
+
+use bcrypt::{DEFAULT_COST, hash};
+
+// bytes of entropy in an emailed reset token
+const RESET_TOKEN_BYTES: usize = 32;
+
+pub struct PasswordService {
+    repo: Arc<dyn UserRepo>,
+    refresh_repo: Arc<dyn RefreshTokenRepo>,
+    session_repo: Arc<dyn SessionRepo>,
+    oauth_repo: Arc<dyn OAuthRepo>,
+    reset_repo: Arc<dyn PasswordResetRepo>,
+    mail: Arc<MailService>,
+    policy: Arc<PasswordPolicy>,
+    reset_validity_period: i64,
+}
+
+impl PasswordService {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        repo: Arc<dyn UserRepo>,
+        refresh_repo: Arc<dyn RefreshTokenRepo>,
+        session_repo: Arc<dyn SessionRepo>,
+        oauth_repo: Arc<dyn OAuthRepo>,
+        reset_repo: Arc<dyn PasswordResetRepo>,
+        mail: Arc<MailService>,
+        policy: Arc<PasswordPolicy>,
+        reset_validity_period: i64,
+    ) -> Self {
+        PasswordService {
+            repo,
+            refresh_repo,
+            session_repo,
+            oauth_repo,
+            reset_repo,
+            mail,
+            policy,
+            reset_validity_period,
+        }
+    }
+
+    // succeeds at once whether or not the email is registered, so neither the reply nor its
+    // timing can be used to probe accounts, the link is sent in the background
+    pub async fn forgot_password(
+        self: &Arc<Self>,
+        req: ForgotPasswordRequest,
+    ) -> Result<(), AppError> {
+        let service = self.clone();
+        let locale = i18n::current_locale();
+        tokio::spawn(async move {
+            let send = service.send_reset_link(&req.email);
+            let result = match locale {
+                Some(locale) => i18n::scope(locale, send).await,
+                None => send.await,
+            };
+            if let Err(e) = result {
+                tracing::error!("password reset mail for {} failed: {}", req.email, e);
+            }
+        });
+
+        Ok(())
+    }
+
+    async fn send_reset_link(&self, email: &str) -> Result<(), AppError> {
+        let existing_user = self.repo.find_by_email(email).await.map_err(|e| {
+            tracing::error!("database find email error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+        let Some(user) = existing_user else {
+            return Ok(());
+        };
+
+        // only the most recently requested link stays valid
+        self.reset_repo
+            .invalidate_all_for_user(user.id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database invalidate reset tokens error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        let token = generate_token(RESET_TOKEN_BYTES);
+        let expires_at = OffsetDateTime::now_utc() + Duration::seconds(self.reset_validity_period);
+        self.reset_repo
+            .create(
+                IdInstance::next_id(),
+                user.id,
+                &sha256_hex(&token),
+                expires_at,
+            )
+            .await
+            .map_err(|e| {
+                tracing::error!("database insert reset token error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        let minutes = (self.reset_validity_period / 60).to_string();
+        self.mail.send(
+            &user.email,
+            "reset_password",
+            &[
+                ("username", &user.username),
+                ("token", &token),
+                ("minutes", &minutes),
+            ],
+        )
+    }
+
+    pub async fn reset_password(&self, req: ResetPasswordRequest) -> Result<(), AppError> {
+        let token_hash = sha256_hex(&req.token);
+
+        // a rejected password must not burn the token, so check before consuming
+        let user_id = self
+            .reset_repo
+            .find_active(&token_hash)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find reset token error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::bad_request(constants::CODE_RESET_TOKEN_INVALID))?;
+        let user = self
+            .repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::bad_request(constants::CODE_RESET_TOKEN_INVALID))?;
+        self.policy.check(
+            "new_password",
+            &req.new_password,
+            &[&user.username, &user.email],
+        )?;
+
+        // consuming is atomic, a token can only ever be redeemed once
+        let consumed = self.reset_repo.consume(&token_hash).await.map_err(|e| {
+            tracing::error!("database consume reset token error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+        if consumed != Some(user_id) {
+            return Err(AppError::bad_request(constants::CODE_RESET_TOKEN_INVALID));
+        }
+
+        let hashed = hash(req.new_password, DEFAULT_COST).map_err(|e| {
+            tracing::error!("hash error: {}", e);
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        })?;
+
+        self.repo
+            .update_password(user_id, &hashed)
+            .await
+            .map_err(|e| {
+                tracing::error!("database update password error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        self.revoke_sessions(user_id).await
+    }
+
+    // whoever held the old password loses every session, oauth token and outstanding reset link
+    async fn revoke_sessions(&self, user_id: i64) -> Result<(), AppError> {
+        self.repo
+            .increment_token_version(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database update token version error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        self.refresh_repo
+            .revoke_all_for_user(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database revoke refresh tokens error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        self.session_repo
+            .revoke_all_for_user(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database revoke sessions error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        self.oauth_repo
+            .revoke_all_for_user(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database revoke oauth tokens error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        self.reset_repo
+            .invalidate_all_for_user(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database invalidate reset tokens error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        Ok(())
+    }
+}
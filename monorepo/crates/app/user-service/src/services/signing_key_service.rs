@@ -0,0 +1,558 @@
+use std::{
+    error::Error,
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration as StdDuration,
+};
+
+use base64::{
+    Engine,
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+};
+use ed25519_dalek::pkcs8::{EncodePrivateKey, spki::der::pem::LineEnding};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation, decode, decode_header,
+    encode,
+    errors::{Error as JwtError, ErrorKind},
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+};
+use rand::{RngCore, rngs::OsRng};
+use rsa::traits::PublicKeyParts;
+use serde::{Serialize, de::DeserializeOwned};
+use shared::{
+    config::config::JwtSigningConfig,
+    constants::constants,
+    crypto::crypto::{generate_token, open, seal},
+    error::error::AppError,
+};
+use sqlx::types::Json;
+use time::{Duration, OffsetDateTime};
+
+use crate::models::signing_key::SigningKey;
+use crate::repositories::signing_key_repo::SigningKeyRepo;
+
+const KID_BYTES: usize = 16;
+const RSA_KEY_BITS: usize = 2048;
+// marks a private key sealed with `jwt.signing.encryption_key`, older rows hold the bare PEM
+const SEALED_PREFIX: &str = "sealed:";
+
+// a stored key, parsed once when loaded
+struct LoadedKey {
+    kid: String,
+    algorithm: Algorithm,
+    activates_at: OffsetDateTime,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    jwk: Jwk,
+}
+
+// asymmetric keys for access tokens, shared by every instance through the database
+pub struct SigningKeyService {
+    repo: Arc<dyn SigningKeyRepo>,
+    config: JwtSigningConfig,
+    algorithm: Algorithm,
+    encryption_key: [u8; 32],
+    // verifies HS256 access tokens issued before signing keys
+    legacy_key: Option<DecodingKey>,
+    keys: RwLock<Vec<LoadedKey>>,
+}
+
+impl SigningKeyService {
+    // loads the stored keys, generating the first one on a fresh database
+    pub async fn new(
+        repo: Arc<dyn SigningKeyRepo>,
+        config: JwtSigningConfig,
+        access_validity_period: i64,
+    ) -> Result<Self, Box<dyn Error>> {
+        let algorithm = match Algorithm::from_str(&config.algorithm)? {
+            algorithm @ (Algorithm::EdDSA | Algorithm::RS256) => algorithm,
+            algorithm => {
+                return Err(format!("unsupported jwt signing algorithm `{:?}`", algorithm).into());
+            }
+        };
+        if config.retention_period < access_validity_period {
+            return Err(
+                "jwt.signing.retention_period is shorter than the access token lifetime".into(),
+            );
+        }
+        let encryption_key = STANDARD
+            .decode(&config.encryption_key)
+            .ok()
+            .and_then(|key| <[u8; 32]>::try_from(key).ok())
+            .ok_or("jwt.signing.encryption_key is not the base64 of 32 bytes")?;
+
+        let legacy_key = config
+            .legacy_secret
+            .as_deref()
+            .filter(|secret| !secret.is_empty())
+            .map(|secret| DecodingKey::from_secret(secret.as_bytes()));
+
+        let service = SigningKeyService {
+            repo,
+            config,
+            algorithm,
+            encryption_key,
+            legacy_key,
+            keys: RwLock::new(Vec::new()),
+        };
+        service.refresh().await?;
+        if service.keys.read().unwrap().is_empty() {
+            return Err(
+                "no usable signing key, were they stored under another jwt.signing.encryption_key?"
+                    .into(),
+            );
+        }
+        Ok(service)
+    }
+
+    // reloads and rotates in the background, so keys added by other instances are picked up
+    pub fn start(self: Arc<Self>) {
+        let period = StdDuration::from_secs(self.config.refresh_interval_secs);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            // the first tick completes at once, `new` has just loaded the keys
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.refresh().await {
+                    tracing::error!("signing key refresh error: {}", e);
+                }
+            }
+        });
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let keys = self.keys.read().unwrap();
+        let key = keys
+            .iter()
+            .rev()
+            .find(|key| key.activates_at <= now)
+            .ok_or_else(|| {
+                tracing::error!("no active signing key");
+                AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+            })?;
+
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(key.kid.clone());
+        encode(&header, claims, &key.encoding).map_err(|e| {
+            tracing::error!("jwt encode error: {}", e);
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        })
+    }
+
+    // picks the key named by `kid`, the algorithm comes from the key and never from the token,
+    // `validation` only decides which claims are checked, HS256 only while `legacy_secret` is set
+    pub fn verify<T: DeserializeOwned + Clone>(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<TokenData<T>, JwtError> {
+        let header = decode_header(token)?;
+        if header.alg == Algorithm::HS256
+            && let Some(legacy_key) = &self.legacy_key
+        {
+            let mut validation = validation.clone();
+            validation.algorithms = vec![Algorithm::HS256];
+            return decode::<T>(token, legacy_key, &validation);
+        }
+
+        let kid = header
+            .kid
+            .ok_or_else(|| JwtError::from(ErrorKind::InvalidToken))?;
+        let keys = self.keys.read().unwrap();
+        let key = keys
+            .iter()
+            .find(|key| key.kid == kid)
+            .ok_or_else(|| JwtError::from(ErrorKind::InvalidToken))?;
+
+        let mut validation = validation.clone();
+        validation.algorithms = vec![key.algorithm];
+        decode::<T>(token, &key.decoding, &validation)
+    }
+
+    // algorithms of the published keys, more than one while switching algorithms
+    pub fn algorithms(&self) -> Vec<Algorithm> {
+        let mut algorithms = Vec::new();
+        for key in self.keys.read().unwrap().iter() {
+            if !algorithms.contains(&key.algorithm) {
+                algorithms.push(key.algorithm);
+            }
+        }
+        algorithms
+    }
+
+    // upcoming, current and recently replaced keys
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .keys
+                .read()
+                .unwrap()
+                .iter()
+                .map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+
+    async fn refresh(&self) -> Result<(), AppError> {
+        let now = OffsetDateTime::now_utc();
+        let deleted = self
+            .repo
+            .delete_replaced_before(now - Duration::seconds(self.config.retention_period))
+            .await
+            .map_err(|e| {
+                tracing::error!("database delete signing keys error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        if deleted > 0 {
+            tracing::info!("removed {} replaced signing keys", deleted);
+        }
+
+        let mut stored = self.list().await?;
+        if let Some(activates_at) = self.next_activation(&stored, now) {
+            let newest = stored.last().map(|key| key.activates_at);
+            let key = self.generate(activates_at).await?;
+            let created = self.repo.create_after(&key, newest).await.map_err(|e| {
+                tracing::error!("database insert signing key error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+            if created {
+                tracing::info!("new signing key {} activates at {}", key.kid, activates_at);
+            }
+            stored = self.list().await?;
+        }
+
+        // keys from before encryption at rest are sealed on the first load
+        for key in stored
+            .iter_mut()
+            .filter(|key| !key.private_key.starts_with(SEALED_PREFIX))
+        {
+            let sealed = self.seal_private_key(&key.kid, &key.private_key);
+            self.repo
+                .update_private_key(&key.kid, &sealed)
+                .await
+                .map_err(|e| {
+                    tracing::error!("database update signing key error: {}", e);
+                    AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                })?;
+            tracing::info!("sealed the private key of signing key {}", key.kid);
+            key.private_key = sealed;
+        }
+
+        let loaded = stored
+            .into_iter()
+            .filter_map(|key| match load_key(&key, &self.encryption_key) {
+                Ok(loaded) => Some(loaded),
+                Err(e) => {
+                    tracing::error!("signing key {} is unusable: {}", key.kid, e);
+                    None
+                }
+            })
+            .collect();
+        *self.keys.write().unwrap() = loaded;
+        Ok(())
+    }
+
+    // when the next key should start signing, if it is time to publish it
+    fn next_activation(
+        &self,
+        stored: &[SigningKey],
+        now: OffsetDateTime,
+    ) -> Option<OffsetDateTime> {
+        let Some(newest) = stored.last() else {
+            // nothing can be verified yet, so the first key signs right away
+            return Some(now);
+        };
+
+        let prepublish = Duration::seconds(self.config.prepublish_period);
+        // a changed algorithm takes over after the usual prepublish period
+        if Algorithm::from_str(&newest.algorithm).ok() != Some(self.algorithm) {
+            return Some(now + prepublish);
+        }
+
+        let scheduled = newest.activates_at + Duration::seconds(self.config.rotation_period);
+        // an instance that was down past the schedule still prepublishes, the old key signs meanwhile
+        (scheduled - prepublish <= now).then(|| scheduled.max(now + prepublish))
+    }
+
+    async fn generate(&self, activates_at: OffsetDateTime) -> Result<SigningKey, AppError> {
+        let algorithm = self.algorithm;
+        let kid = generate_token(KID_BYTES);
+        let jwk_kid = kid.clone();
+        // an RSA key takes a while to find, keep it off the runtime threads
+        let (private_key, public_jwk) =
+            tokio::task::spawn_blocking(move || generate_key_pair(algorithm, jwk_kid))
+                .await
+                .map_err(|e| e.to_string())
+                .and_then(|generated| generated)
+                .map_err(|e| {
+                    tracing::error!("signing key generation error: {}", e);
+                    AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+                })?;
+
+        Ok(SigningKey {
+            private_key: self.seal_private_key(&kid, &private_key),
+            kid,
+            algorithm: self.config.algorithm.clone(),
+            public_jwk: Json(public_jwk),
+            activates_at,
+            created_at: OffsetDateTime::now_utc(),
+        })
+    }
+
+    // bound to the kid, so a sealed key cannot be moved to another row
+    fn seal_private_key(&self, kid: &str, pem: &str) -> String {
+        format!(
+            "{}{}",
+            SEALED_PREFIX,
+            seal(&self.encryption_key, pem.as_bytes(), kid.as_bytes())
+        )
+    }
+
+    async fn list(&self) -> Result<Vec<SigningKey>, AppError> {
+        self.repo.list().await.map_err(|e| {
+            tracing::error!("database list signing keys error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })
+    }
+}
+
+fn load_key(key: &SigningKey, encryption_key: &[u8; 32]) -> Result<LoadedKey, JwtError> {
+    let algorithm = Algorithm::from_str(&key.algorithm)?;
+    let pem = key
+        .private_key
+        .strip_prefix(SEALED_PREFIX)
+        .and_then(|sealed| open(encryption_key, sealed, key.kid.as_bytes()))
+        .ok_or_else(|| JwtError::from(ErrorKind::InvalidKeyFormat))?;
+    let encoding = match algorithm {
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(&pem)?,
+        _ => EncodingKey::from_rsa_pem(&pem)?,
+    };
+
+    Ok(LoadedKey {
+        kid: key.kid.clone(),
+        algorithm,
+        activates_at: key.activates_at,
+        encoding,
+        decoding: DecodingKey::from_jwk(&key.public_jwk)?,
+        jwk: key.public_jwk.0.clone(),
+    })
+}
+
+// PKCS#8 PEM private key and the matching public JWK
+pub(crate) fn generate_key_pair(
+    algorithm: Algorithm,
+    kid: String,
+) -> Result<(String, Jwk), String> {
+    let common = CommonParameters {
+        public_key_use: Some(PublicKeyUse::Signature),
+        key_id: Some(kid),
+        ..Default::default()
+    };
+
+    match algorithm {
+        Algorithm::EdDSA => {
+            let mut seed = [0u8; 32];
+            OsRng.fill_bytes(&mut seed);
+            let private = ed25519_dalek::SigningKey::from_bytes(&seed);
+            let pem = private
+                .to_pkcs8_pem(LineEnding::LF)
+                .map_err(|e| e.to_string())?;
+
+            let jwk = Jwk {
+                common: CommonParameters {
+                    key_algorithm: Some(KeyAlgorithm::EdDSA),
+                    ..common
+                },
+                algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(private.verifying_key().as_bytes()),
+                }),
+            };
+            Ok((pem.to_string(), jwk))
+        }
+        _ => {
+            let private =
+                rsa::RsaPrivateKey::new(&mut OsRng, RSA_KEY_BITS).map_err(|e| e.to_string())?;
+            let pem = rsa::pkcs8::EncodePrivateKey::to_pkcs8_pem(&private, LineEnding::LF)
+                .map_err(|e| e.to_string())?;
+
+            let jwk = Jwk {
+                common: CommonParameters {
+                    key_algorithm: Some(KeyAlgorithm::RS256),
+                    ..common
+                },
+                algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(private.n().to_bytes_be()),
+                    e: URL_SAFE_NO_PAD.encode(private.e().to_bytes_be()),
+                }),
+            };
+            Ok((pem.to_string(), jwk))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::memory_signing_key_repo::MemorySigningKeyRepo;
+    use serde::Deserialize;
+
+    const ROTATION: i64 = 86400;
+    const PREPUBLISH: i64 = 3600;
+    const RETENTION: i64 = 7200;
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    struct Claims {
+        sub: String,
+        exp: i64,
+    }
+
+    fn config(legacy_secret: Option<&str>) -> JwtSigningConfig {
+        JwtSigningConfig {
+            algorithm: "EdDSA".to_string(),
+            encryption_key: "d2Db7M7TqkW5b4J23voREksskX28Ppq6XSEG7tsuAr8=".to_string(),
+            legacy_secret: legacy_secret.map(str::to_string),
+            rotation_period: ROTATION,
+            prepublish_period: PREPUBLISH,
+            retention_period: RETENTION,
+            refresh_interval_secs: 60,
+        }
+    }
+
+    async fn service(
+        repo: Arc<MemorySigningKeyRepo>,
+        legacy_secret: Option<&str>,
+    ) -> SigningKeyService {
+        SigningKeyService::new(repo, config(legacy_secret), 600)
+            .await
+            .unwrap()
+    }
+
+    // stores keys activating at each offset from now, oldest first
+    async fn stored_keys(offsets: &[i64]) -> (Arc<MemorySigningKeyRepo>, Vec<SigningKey>) {
+        let scratch = service(Arc::new(MemorySigningKeyRepo::new()), None).await;
+        let repo = Arc::new(MemorySigningKeyRepo::new());
+        let now = OffsetDateTime::now_utc();
+        let mut keys: Vec<SigningKey> = Vec::new();
+        for offset in offsets {
+            let key = scratch
+                .generate(now + Duration::seconds(*offset))
+                .await
+                .unwrap();
+            let newest = keys.last().map(|key| key.activates_at);
+            assert!(repo.create_after(&key, newest).await.unwrap());
+            keys.push(key);
+        }
+        (repo, keys)
+    }
+
+    fn claims() -> Claims {
+        Claims {
+            sub: "42".to_string(),
+            exp: (OffsetDateTime::now_utc() + Duration::minutes(10)).unix_timestamp(),
+        }
+    }
+
+    fn kids(service: &SigningKeyService) -> Vec<String> {
+        service
+            .jwks()
+            .keys
+            .into_iter()
+            .filter_map(|jwk| jwk.common.key_id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn first_key_signs_right_away() {
+        let service = service(Arc::new(MemorySigningKeyRepo::new()), None).await;
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(service.next_activation(&[], now), Some(now));
+
+        let token = service.sign(&claims()).unwrap();
+        let data = service
+            .verify::<Claims>(&token, &Validation::new(Algorithm::EdDSA))
+            .unwrap();
+        assert_eq!(data.claims.sub, "42");
+    }
+
+    #[tokio::test]
+    async fn next_key_is_published_ahead_of_the_rotation() {
+        let (_, keys) = stored_keys(&[0]).await;
+        let service = service(Arc::new(MemorySigningKeyRepo::new()), None).await;
+        let activates_at = keys[0].activates_at;
+        let scheduled = activates_at + Duration::seconds(ROTATION);
+
+        let early = scheduled - Duration::seconds(PREPUBLISH + 1);
+        assert_eq!(service.next_activation(&keys, early), None);
+        let due = scheduled - Duration::seconds(PREPUBLISH);
+        assert_eq!(service.next_activation(&keys, due), Some(scheduled));
+    }
+
+    #[tokio::test]
+    async fn overdue_and_switched_keys_still_prepublish() {
+        let (_, mut keys) = stored_keys(&[0]).await;
+        let service = service(Arc::new(MemorySigningKeyRepo::new()), None).await;
+        let prepublish = Duration::seconds(PREPUBLISH);
+
+        // nothing was rotated while every instance was down
+        let late = keys[0].activates_at + Duration::seconds(3 * ROTATION);
+        assert_eq!(
+            service.next_activation(&keys, late),
+            Some(late + prepublish)
+        );
+
+        let now = OffsetDateTime::now_utc();
+        keys[0].algorithm = "RS256".to_string();
+        assert_eq!(service.next_activation(&keys, now), Some(now + prepublish));
+    }
+
+    #[tokio::test]
+    async fn replaced_keys_are_kept_for_the_retention_period() {
+        // replaced long ago, replaced within the retention period, and signing
+        let (repo, keys) = stored_keys(&[-10 * 3600, -5 * 3600, -3600]).await;
+        let service = service(repo, None).await;
+
+        assert_eq!(kids(&service), [keys[1].kid.clone(), keys[2].kid.clone()]);
+        let header = decode_header(service.sign(&claims()).unwrap()).unwrap();
+        assert_eq!(header.kid, Some(keys[2].kid.clone()));
+    }
+
+    #[tokio::test]
+    async fn legacy_tokens_need_the_legacy_secret() {
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims(),
+            &EncodingKey::from_secret(b"legacy"),
+        )
+        .unwrap();
+        let validation = Validation::new(Algorithm::EdDSA);
+
+        let accepting = service(Arc::new(MemorySigningKeyRepo::new()), Some("legacy")).await;
+        assert!(accepting.verify::<Claims>(&token, &validation).is_ok());
+
+        let other = service(Arc::new(MemorySigningKeyRepo::new()), Some("other")).await;
+        assert!(other.verify::<Claims>(&token, &validation).is_err());
+
+        // without it HS256 is refused whatever the validation allows
+        let refusing = service(Arc::new(MemorySigningKeyRepo::new()), None).await;
+        let validation = Validation::new(Algorithm::HS256);
+        assert!(refusing.verify::<Claims>(&token, &validation).is_err());
+    }
+
+    #[tokio::test]
+    async fn retention_must_outlast_access_tokens() {
+        let repo = Arc::new(MemorySigningKeyRepo::new());
+        assert!(
+            SigningKeyService::new(repo, config(None), RETENTION + 1)
+                .await
+                .is_err()
+        );
+    }
+}
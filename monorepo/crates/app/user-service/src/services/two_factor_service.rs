@@ -0,0 +1,406 @@
+use std::sync::Arc;
+
+use shared::{
+    config::config::TwoFactorConfig,
+    constants::constants,
+    crypto::crypto::{random_code, sha256_hex},
+    error::error::AppError,
+    extract::client_device::ClientDevice,
+};
+use time::OffsetDateTime;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::models::two_factor::{
+    ConfirmTwoFactorRequest, DisableTwoFactorRequest, RecoveryCodesReply, TwoFactorSetupReply,
+    UserTotp,
+};
+use crate::repositories::{two_factor_repo::TwoFactorRepo, user_repo::UserRepo};
+use crate::services::login_guard::LoginGuard;
+use idgenerator::*;
+
+use bcrypt::verify;
+
+// RFC 6238 defaults, what every authenticator app expects
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+
+// no 0/o/1/l, recovery codes are read off paper
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789abcdefghjkmnpqrstuvwxyz";
+const RECOVERY_CODE_LEN: usize = 10;
+
+pub struct TwoFactorService {
+    repo: Arc<dyn TwoFactorRepo>,
+    user_repo: Arc<dyn UserRepo>,
+    guard: Arc<LoginGuard>,
+    config: TwoFactorConfig,
+}
+
+impl TwoFactorService {
+    pub fn new(
+        repo: Arc<dyn TwoFactorRepo>,
+        user_repo: Arc<dyn UserRepo>,
+        guard: Arc<LoginGuard>,
+        config: TwoFactorConfig,
+    ) -> Self {
+        TwoFactorService {
+            repo,
+            user_repo,
+            guard,
+            config,
+        }
+    }
+
+    // starts (or restarts) enrollment, 2fa stays off until `confirm`
+    pub async fn setup(&self, user_id: i64) -> Result<TwoFactorSetupReply, AppError> {
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))?;
+
+        let secret = Secret::generate_secret().to_encoded().to_string();
+        let saved = self
+            .repo
+            .save_pending(user.id, &secret)
+            .await
+            .map_err(|e| {
+                tracing::error!("database save totp error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        if !saved {
+            return Err(AppError::conflict(
+                constants::CODE_TWO_FACTOR_ALREADY_ENABLED,
+            ));
+        }
+
+        let totp = self.totp(&secret, Some(user.email))?;
+        Ok(TwoFactorSetupReply {
+            secret,
+            otpauth_uri: totp.get_url(),
+        })
+    }
+
+    pub async fn confirm(
+        &self,
+        user_id: i64,
+        req: ConfirmTwoFactorRequest,
+    ) -> Result<RecoveryCodesReply, AppError> {
+        let pending = self
+            .find_totp(user_id)
+            .await?
+            .filter(|totp| totp.enabled_at.is_none())
+            .ok_or_else(|| AppError::bad_request(constants::CODE_TWO_FACTOR_NOT_ENABLED))?;
+
+        if !self.verify_totp(&pending, &req.code).await? {
+            return Err(AppError::bad_request(
+                constants::CODE_TWO_FACTOR_CODE_INVALID,
+            ));
+        }
+
+        let recovery_codes: Vec<String> = (0..self.config.recovery_codes)
+            .map(|_| {
+                let code = random_code(RECOVERY_CODE_LEN, RECOVERY_CODE_ALPHABET);
+                format!("{}-{}", &code[..5], &code[5..])
+            })
+            .collect();
+        let hashed: Vec<(i64, String)> = recovery_codes
+            .iter()
+            .map(|code| (IdInstance::next_id(), hash_recovery_code(code)))
+            .collect();
+
+        let enabled = self.repo.enable(user_id, &hashed).await.map_err(|e| {
+            tracing::error!("database enable totp error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+        if !enabled {
+            return Err(AppError::conflict(
+                constants::CODE_TWO_FACTOR_ALREADY_ENABLED,
+            ));
+        }
+
+        Ok(RecoveryCodesReply { recovery_codes })
+    }
+
+    // needs both the password and a second factor, a stolen session alone cannot turn 2fa off,
+    // wrong guesses count towards the same lockout as failed logins
+    pub async fn disable(
+        &self,
+        user_id: i64,
+        req: DisableTwoFactorRequest,
+        device: ClientDevice,
+    ) -> Result<(), AppError> {
+        let user = self
+            .user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))?;
+        self.guard.check(&user.email, device.ip).await?;
+
+        if !verify(&req.password, &user.password_hash).unwrap_or(false) {
+            self.guard.record_failure(&user.email, device.ip).await?;
+            return Err(AppError::unauthorized(
+                constants::CODE_WRONG_ACCOUNT_OR_PASSWORD,
+            ));
+        }
+
+        if !self.verify(user_id, &req.code).await? {
+            self.guard.record_failure(&user.email, device.ip).await?;
+            return Err(AppError::bad_request(
+                constants::CODE_TWO_FACTOR_CODE_INVALID,
+            ));
+        }
+        self.guard.record_success(&user.email).await?;
+
+        self.repo.delete(user_id).await.map_err(|e| {
+            tracing::error!("database delete totp error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })
+    }
+
+    pub async fn is_enabled(&self, user_id: i64) -> Result<bool, AppError> {
+        Ok(self
+            .find_totp(user_id)
+            .await?
+            .is_some_and(|totp| totp.enabled_at.is_some()))
+    }
+
+    // accepts a TOTP code or a recovery code, either one only once
+    pub async fn verify(&self, user_id: i64, code: &str) -> Result<bool, AppError> {
+        let Some(totp) = self
+            .find_totp(user_id)
+            .await?
+            .filter(|totp| totp.enabled_at.is_some())
+        else {
+            return Err(AppError::bad_request(
+                constants::CODE_TWO_FACTOR_NOT_ENABLED,
+            ));
+        };
+
+        let code = code.trim();
+        if code.len() == TOTP_DIGITS && code.bytes().all(|byte| byte.is_ascii_digit()) {
+            return self.verify_totp(&totp, code).await;
+        }
+
+        self.repo
+            .use_recovery_code(user_id, &hash_recovery_code(code))
+            .await
+            .map_err(|e| {
+                tracing::error!("database use recovery code error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })
+    }
+
+    async fn find_totp(&self, user_id: i64) -> Result<Option<UserTotp>, AppError> {
+        self.repo.find_totp(user_id).await.map_err(|e| {
+            tracing::error!("database find totp error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })
+    }
+
+    // checks every step within the skew, the matched step is burned so a code works once
+    async fn verify_totp(&self, user_totp: &UserTotp, code: &str) -> Result<bool, AppError> {
+        let totp = self.totp(&user_totp.secret, None)?;
+        let current = OffsetDateTime::now_utc().unix_timestamp() / TOTP_STEP as i64;
+        let skew = self.config.skew as i64;
+
+        let matched = (current - skew..=current + skew)
+            .find(|step| *step > 0 && totp.check(code, *step as u64 * TOTP_STEP));
+        let Some(step) = matched else {
+            return Ok(false);
+        };
+
+        self.repo
+            .mark_step_used(user_totp.user_id, step)
+            .await
+            .map_err(|e| {
+                tracing::error!("database mark totp step error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })
+    }
+
+    // skew is applied by `verify_totp`, so the generator itself checks a single step
+    fn totp(&self, secret: &str, account: Option<String>) -> Result<TOTP, AppError> {
+        let bytes = Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .map_err(|e| {
+                tracing::error!("totp secret error: {:?}", e);
+                AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+            })?;
+
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            0,
+            TOTP_STEP,
+            bytes,
+            Some(self.config.issuer.clone()),
+            account.unwrap_or_default(),
+        )
+        .map_err(|e| {
+            tracing::error!("totp error: {}", e);
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        })
+    }
+}
+
+// case and dashes do not matter when typing a recovery code back
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    sha256_hex(&normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::User;
+    use crate::repositories::{
+        memory_login_attempt_repo::MemoryLoginAttemptRepo,
+        memory_two_factor_repo::MemoryTwoFactorRepo, memory_user_repo::MemoryUserRepo,
+    };
+    use shared::config::config::{LockoutConfig, LoginProtectionConfig};
+    use std::time::Duration;
+
+    const USER_ID: i64 = 7;
+
+    fn lockout() -> LockoutConfig {
+        LockoutConfig {
+            max_failures: 100,
+            lockout_secs: 60,
+            max_lockout_secs: 600,
+        }
+    }
+
+    fn service() -> TwoFactorService {
+        let users = MemoryUserRepo::new();
+        let now = OffsetDateTime::now_utc();
+        users.insert(User {
+            id: USER_ID,
+            username: "alice".to_string(),
+            email: "alice@x.com".to_string(),
+            password_hash: String::new(),
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+            role: "user".to_string(),
+            token_version: 0,
+            locale: None,
+            email_verified_at: Some(now),
+            pending_email: None,
+        });
+        let guard = LoginGuard::new(
+            Arc::new(MemoryLoginAttemptRepo::new()),
+            LoginProtectionConfig {
+                store: "memory".to_string(),
+                window_secs: 900,
+                account: lockout(),
+                ip: lockout(),
+            },
+        );
+        TwoFactorService::new(
+            Arc::new(MemoryTwoFactorRepo::new()),
+            Arc::new(users),
+            Arc::new(guard),
+            TwoFactorConfig {
+                issuer: "test".to_string(),
+                skew: 1,
+                recovery_codes: 2,
+            },
+        )
+    }
+
+    // waits out the end of a step so the service sees the same current step as the test
+    async fn current_step() -> i64 {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        if TOTP_STEP as i64 - now % TOTP_STEP as i64 <= 2 {
+            tokio::time::sleep(Duration::from_secs(3)).await;
+        }
+        OffsetDateTime::now_utc().unix_timestamp() / TOTP_STEP as i64
+    }
+
+    fn code_at(service: &TwoFactorService, secret: &str, step: i64) -> String {
+        let totp = service.totp(secret, None).unwrap();
+        totp.generate(step as u64 * TOTP_STEP)
+    }
+
+    // enables 2fa and returns the secret and the recovery codes
+    async fn enable(service: &TwoFactorService) -> (String, Vec<String>) {
+        let secret = service.setup(USER_ID).await.unwrap().secret;
+        let step = current_step().await;
+        let reply = service
+            .confirm(
+                USER_ID,
+                ConfirmTwoFactorRequest {
+                    code: code_at(service, &secret, step),
+                },
+            )
+            .await
+            .unwrap();
+        (secret, reply.recovery_codes)
+    }
+
+    #[test]
+    fn recovery_codes_ignore_case_and_dashes() {
+        let hash = hash_recovery_code("abcde-fghjk");
+        assert_eq!(hash_recovery_code("ABCDE-FGHJK"), hash);
+        assert_eq!(hash_recovery_code("abcdefghjk"), hash);
+        assert_eq!(hash_recovery_code(" abcde fghjk "), hash);
+        assert_ne!(hash_recovery_code("abcde-fghjm"), hash);
+    }
+
+    #[tokio::test]
+    async fn totp_codes_within_the_skew_work_once() {
+        let service = service();
+        let (secret, _) = enable(&service).await;
+        let step = current_step().await;
+
+        // the enrollment code burned the current step
+        let code = code_at(&service, &secret, step);
+        assert!(!service.verify(USER_ID, &code).await.unwrap());
+
+        // one step ahead is within the skew, two steps ahead is not
+        let too_far = code_at(&service, &secret, step + 2);
+        assert!(!service.verify(USER_ID, &too_far).await.unwrap());
+        let next = code_at(&service, &secret, step + 1);
+        assert!(service.verify(USER_ID, &next).await.unwrap());
+        assert!(!service.verify(USER_ID, &next).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn earlier_steps_are_burned_with_the_matched_one() {
+        let service = service();
+        let secret = service.setup(USER_ID).await.unwrap().secret;
+        let step = current_step().await;
+
+        let pending = service.find_totp(USER_ID).await.unwrap().unwrap();
+        let next = code_at(&service, &secret, step + 1);
+        assert!(service.verify_totp(&pending, &next).await.unwrap());
+
+        // a code from before the burned step is rejected even though it is within the skew
+        let previous = code_at(&service, &secret, step - 1);
+        assert!(!service.verify_totp(&pending, &previous).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn recovery_codes_work_once() {
+        let service = service();
+        let (_, codes) = enable(&service).await;
+        assert_eq!(codes.len(), 2);
+
+        let typed = codes[0].to_uppercase().replace('-', "");
+        assert!(service.verify(USER_ID, &typed).await.unwrap());
+        assert!(!service.verify(USER_ID, &codes[0]).await.unwrap());
+        assert!(service.verify(USER_ID, &codes[1]).await.unwrap());
+    }
+}
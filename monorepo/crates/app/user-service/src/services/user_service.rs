@@ -1,113 +1,983 @@
-use sqlx::PgPool;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
 
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode};
-use shared::constants::constants;
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, Validation, decode, encode, errors::ErrorKind,
+};
+use shared::{
+    constants::constants,
+    error::error::AppError,
+    extract::client_device::ClientDevice,
+    i18n::i18n,
+    pagination::{page::Page, page_request::PageRequest},
+    password::policy::PasswordPolicy,
+};
 
 use time::{Duration, OffsetDateTime};
 
 use crate::models::{
-    claims::{AccessTokenClaims, JwtSecret, RefreshTokenClaims},
-    user::{LoginUserReply, LoginUserRequest, RegisterUserRequest, RefreshTokenRequest, RefreshTokenReply},
+    claims::{
+        AccessTokenClaims, JwtSecret, MfaTokenClaims, RefreshTokenClaims, VerificationTokenClaims,
+    },
+    refresh_token::RefreshToken,
+    session::{Session, SessionReply},
+    two_factor::{LoginTwoFactorRequest, MfaChallengeReply},
+    user::{
+        ChangePasswordRequest, LoginReply, LoginUserReply, LoginUserRequest, LogoutRequest,
+        RefreshTokenReply, RefreshTokenRequest, RegisterUserRequest, ResendVerificationRequest,
+        UpdateProfileRequest, User, UserProfileReply, VerifyEmailRequest,
+    },
+};
+use crate::repositories::{
+    oauth_repo::OAuthRepo, refresh_token_repo::RefreshTokenRepo, session_repo::SessionRepo,
+    user_repo::UserRepo,
+};
+use crate::services::{
+    login_guard::LoginGuard, mail_service::MailService, signing_key_service::SigningKeyService,
+    two_factor_service::TwoFactorService,
 };
-use crate::repositories::user_repo::UserRepo;
 use idgenerator::*;
 
 extern crate bcrypt;
 use bcrypt::{DEFAULT_COST, hash, verify};
 
+// compared against when the email is unknown, so both cases take as long
+static UNKNOWN_USER_HASH: LazyLock<String> =
+    LazyLock::new(|| hash("unknown user", DEFAULT_COST).unwrap_or_default());
+
 pub struct UserService {
     repo: Arc<dyn UserRepo>,
-    pool: PgPool,
+    refresh_repo: Arc<dyn RefreshTokenRepo>,
+    session_repo: Arc<dyn SessionRepo>,
+    oauth_repo: Arc<dyn OAuthRepo>,
     jwt_secret: Arc<JwtSecret>,
+    keys: Arc<SigningKeyService>,
+    mail: Arc<MailService>,
+    policy: Arc<PasswordPolicy>,
+    guard: Arc<LoginGuard>,
+    two_factor: Arc<TwoFactorService>,
 }
 
 impl UserService {
-    pub fn new(repo: Arc<dyn UserRepo>, pool: PgPool, jwt_secret: Arc<JwtSecret>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        repo: Arc<dyn UserRepo>,
+        refresh_repo: Arc<dyn RefreshTokenRepo>,
+        session_repo: Arc<dyn SessionRepo>,
+        oauth_repo: Arc<dyn OAuthRepo>,
+        jwt_secret: Arc<JwtSecret>,
+        keys: Arc<SigningKeyService>,
+        mail: Arc<MailService>,
+        policy: Arc<PasswordPolicy>,
+        guard: Arc<LoginGuard>,
+        two_factor: Arc<TwoFactorService>,
+    ) -> Self {
         UserService {
             repo,
-            pool,
+            refresh_repo,
+            session_repo,
+            oauth_repo,
             jwt_secret,
+            keys,
+            mail,
+            policy,
+            guard,
+            two_factor,
         }
     }
 
-    pub async fn register(&self, user: RegisterUserRequest) -> Result<(), u16> {
+    pub async fn register(&self, user: RegisterUserRequest) -> Result<(), AppError> {
+        self.policy
+            .check("password", &user.password, &[&user.username, &user.email])?;
+
         // Check if the user exists
         let existing_user = self.repo.find_by_email(&user.email).await.map_err(|e| {
             tracing::error!("database find email error: {}", e);
-            constants::CODE_DATE_OPERATION_ERROR
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
         })?;
         if existing_user.is_some() {
-            return Err(constants::CODE_ACCOUNT_ALREADY_EXISTS);
+            return Err(AppError::conflict(constants::CODE_ACCOUNT_ALREADY_EXISTS));
         }
 
         // Encrypted password
         let hashed = hash(user.password, DEFAULT_COST).map_err(|e| {
             tracing::error!("hash error: {}", e);
-            constants::CODE_DATE_OPERATION_ERROR
-        });
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        })?;
 
         // Call `next_id` to generate a new unique id.
         let id = IdInstance::next_id();
 
-        // insert user
+        // insert user, login is refused until the email is verified
         self.repo
-            .create(id, user.username, user.email, hashed.unwrap())
+            .create(id, user.username.clone(), user.email.clone(), hashed)
             .await
             .map_err(|e| {
                 tracing::error!("database insert error: {}", e);
-                constants::CODE_DATE_OPERATION_ERROR
-            })
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        self.send_verification_email(id, &user.username, &user.email)
     }
 
-    pub async fn login(&self, user: LoginUserRequest) -> Result<LoginUserReply, u16> {
-        // Check if the user exists
-        let existing_user: Option<crate::models::user::User> =
-            self.repo.find_by_email(&user.email).await.map_err(|e| {
+    pub async fn verify_email(&self, req: VerifyEmailRequest) -> Result<(), AppError> {
+        let validation = Validation::new(Algorithm::HS256);
+        let token_data = decode::<VerificationTokenClaims>(
+            &req.token,
+            &DecodingKey::from_secret(self.jwt_secret.verification_secret.as_ref()),
+            &validation,
+        )
+        .map_err(|e| {
+            tracing::error!("jwt decode error: {}", e);
+            AppError::bad_request(constants::CODE_VERIFICATION_TOKEN_INVALID)
+        })?;
+
+        if token_data.claims.token_type != "verify_email" {
+            return Err(AppError::bad_request(
+                constants::CODE_VERIFICATION_TOKEN_INVALID,
+            ));
+        }
+
+        let user_id: i64 = token_data
+            .claims
+            .sub
+            .parse()
+            .map_err(|_| AppError::bad_request(constants::CODE_VERIFICATION_TOKEN_INVALID))?;
+
+        let email = &token_data.claims.email;
+        let user = self
+            .repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::bad_request(constants::CODE_VERIFICATION_TOKEN_INVALID))?;
+
+        // single use: the update only matches while the address is still unverified,
+        // or still the pending one for an email change
+        let verified = if user.pending_email.as_deref() == Some(email.as_str()) {
+            // the address may have been registered since the change was requested
+            let taken = self.repo.find_by_email(email).await.map_err(|e| {
                 tracing::error!("database find email error: {}", e);
-                constants::CODE_DATE_OPERATION_ERROR
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
             })?;
-        if !existing_user.is_some() {
-            return Err(constants::CODE_ACCOUNT_NOT_EXISTS);
+            if taken.is_some() {
+                return Err(AppError::conflict(constants::CODE_ACCOUNT_ALREADY_EXISTS));
+            }
+            self.repo.confirm_pending_email(user_id, email).await
+        } else {
+            self.repo.mark_email_verified(user_id, email).await
+        }
+        .map_err(|e| {
+            tracing::error!("database verify email error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+        if !verified {
+            return Err(AppError::bad_request(
+                constants::CODE_VERIFICATION_TOKEN_INVALID,
+            ));
+        }
+
+        Ok(())
+    }
+
+    // always succeeds so the endpoint cannot be used to probe for accounts
+    pub async fn resend_verification(
+        &self,
+        req: ResendVerificationRequest,
+    ) -> Result<(), AppError> {
+        let existing_user = self.repo.find_by_email(&req.email).await.map_err(|e| {
+            tracing::error!("database find email error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+
+        match existing_user {
+            Some(user) if user.email_verified_at.is_none() => {
+                self.send_verification_email(user.id, &user.username, &user.email)
+            }
+            _ => Ok(()),
         }
+    }
+
+    pub async fn login(
+        &self,
+        user: LoginUserRequest,
+        device: ClientDevice,
+    ) -> Result<LoginReply, AppError> {
+        let ip = device.ip;
+        self.guard.check(&user.email, ip).await?;
+
+        // Check if the user exists
+        let existing_user = self.repo.find_by_email(&user.email).await.map_err(|e| {
+            tracing::error!("database find email error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+        // an unknown email is answered like a wrong password, after the same bcrypt work
+        let password_hash = existing_user
+            .as_ref()
+            .map_or(UNKNOWN_USER_HASH.as_str(), |user| {
+                user.password_hash.as_str()
+            });
+        let verified = verify(user.password, password_hash).unwrap_or(false);
+        let Some(existing_user) = existing_user.filter(|_| verified) else {
+            self.guard.record_failure(&user.email, ip).await?;
+            return Err(AppError::unauthorized(
+                constants::CODE_WRONG_ACCOUNT_OR_PASSWORD,
+            ));
+        };
+
+        self.complete_login(existing_user, device).await
+    }
 
-        if !verify(
-            user.password,
-            &existing_user.as_ref().unwrap().password_hash,
+    // follows a successful first factor, accounts with 2fa get a challenge instead of tokens
+    pub async fn complete_login(
+        &self,
+        user: User,
+        device: ClientDevice,
+    ) -> Result<LoginReply, AppError> {
+        if user.email_verified_at.is_none() {
+            return Err(AppError::forbidden(constants::CODE_EMAIL_NOT_VERIFIED));
+        }
+        if !user.is_active {
+            return Err(AppError::forbidden(constants::CODE_ACCOUNT_DISABLED));
+        }
+
+        // the first factor alone is not enough, a second factor is exchanged at /auth/login/2fa
+        if self.two_factor.is_enabled(user.id).await? {
+            return Ok(LoginReply::MfaChallenge(self.issue_mfa_token(&user)?));
+        }
+        // only a completed login resets the lockout, the password alone does not vouch for
+        // the codes guessed after it
+        self.guard.record_success(&user.email).await?;
+
+        Ok(LoginReply::Tokens(self.start_session(user, device).await?))
+    }
+
+    pub async fn login_two_factor(
+        &self,
+        req: LoginTwoFactorRequest,
+        device: ClientDevice,
+    ) -> Result<LoginUserReply, AppError> {
+        let validation = Validation::new(Algorithm::HS256);
+        let token_data = decode::<MfaTokenClaims>(
+            &req.mfa_token,
+            &DecodingKey::from_secret(self.jwt_secret.mfa_secret.as_ref()),
+            &validation,
         )
-        .unwrap()
+        .map_err(|e| {
+            tracing::debug!("jwt decode error: {}", e);
+            AppError::unauthorized(constants::CODE_MFA_TOKEN_INVALID)
+        })?;
+        if token_data.claims.token_type != "mfa" {
+            return Err(AppError::unauthorized(constants::CODE_MFA_TOKEN_INVALID));
+        }
+        let user_id = token_data
+            .claims
+            .sub
+            .parse::<i64>()
+            .map_err(|_| AppError::unauthorized(constants::CODE_MFA_TOKEN_INVALID))?;
+
+        // a password change or logout-all since the challenge invalidates it
+        let user = self
+            .repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .filter(|user| user.token_version == token_data.claims.ver)
+            .ok_or_else(|| AppError::unauthorized(constants::CODE_MFA_TOKEN_INVALID))?;
+        if !user.is_active {
+            return Err(AppError::forbidden(constants::CODE_ACCOUNT_DISABLED));
+        }
+
+        // codes are guessed against the same lockout as passwords
+        let ip = device.ip;
+        self.guard.check(&user.email, ip).await?;
+        if !self.two_factor.verify(user.id, &req.code).await? {
+            self.guard.record_failure(&user.email, ip).await?;
+            return Err(AppError::unauthorized(
+                constants::CODE_TWO_FACTOR_CODE_INVALID,
+            ));
+        }
+        self.guard.record_success(&user.email).await?;
+
+        self.start_session(user, device).await
+    }
+
+    pub async fn refresh_token(
+        &self,
+        req: RefreshTokenRequest,
+        device: ClientDevice,
+    ) -> Result<RefreshTokenReply, AppError> {
+        let (stored, auth_time) = self.find_refresh_token(&req.refresh_token).await?;
+
+        if stored.revoked_at.is_some() {
+            return Err(AppError::unauthorized(
+                constants::CODE_REFRESH_TOKEN_REVOKED,
+            ));
+        }
+        // families started before sessions were tracked have no row yet
+        let session = self.find_session(stored.family_id).await?;
+        if session
+            .as_ref()
+            .is_some_and(|session| session.revoked_at.is_some())
         {
-            return Err(constants::CODE_WRONG_ACCOUNT_OR_PASSWORD);
+            return Err(AppError::unauthorized(
+                constants::CODE_REFRESH_TOKEN_REVOKED,
+            ));
+        }
+
+        // a token that was already rotated is being replayed, so the whole family is compromised
+        let rotated = stored.used_at.is_none()
+            && self.refresh_repo.mark_used(stored.id).await.map_err(|e| {
+                tracing::error!("database update refresh token error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        if !rotated {
+            tracing::warn!(
+                "refresh token reuse detected, revoking family {} of user {}",
+                stored.family_id,
+                stored.user_id
+            );
+            self.end_session(stored.family_id, stored.user_id).await?;
+            return Err(AppError::unauthorized(
+                constants::CODE_REFRESH_TOKEN_REVOKED,
+            ));
+        }
+
+        let existing_user = self
+            .repo
+            .find_by_id(stored.user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))?;
+
+        if !existing_user.is_active {
+            return Err(AppError::forbidden(constants::CODE_ACCOUNT_DISABLED));
+        }
+
+        match session {
+            Some(_) => {
+                self.session_repo
+                    .touch(
+                        stored.family_id,
+                        device.user_agent.as_deref(),
+                        &device.ip.to_string(),
+                    )
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("database update session error: {}", e);
+                        AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                    })?;
+            }
+            None => {
+                self.create_session(stored.family_id, stored.user_id, &device)
+                    .await?
+            }
         }
 
-        // access token
         let now = OffsetDateTime::now_utc();
+        let (access_token, access_exp) =
+            self.issue_access_token(&existing_user, stored.family_id, now, auth_time)?;
+        let (refresh_token, refresh_exp) = self
+            .issue_refresh_token(&existing_user, stored.family_id, now, auth_time)
+            .await?;
+
+        let reply = RefreshTokenReply {
+            access_token,
+            refresh_token,
+            access_expire_time: access_exp.unix_timestamp(),
+            refresh_expire_time: refresh_exp.unix_timestamp(),
+        };
+        Ok(reply)
+    }
+
+    // revokes the session the refresh token belongs to
+    pub async fn logout(&self, req: LogoutRequest) -> Result<(), AppError> {
+        let (stored, _) = self.find_refresh_token(&req.refresh_token).await?;
+
+        self.end_session(stored.family_id, stored.user_id).await
+    }
+
+    // revokes every session and access token of the user, along with what oauth clients hold
+    pub async fn logout_all(&self, user_id: i64) -> Result<(), AppError> {
+        self.repo
+            .increment_token_version(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database update token version error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        self.refresh_repo
+            .revoke_all_for_user(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database revoke refresh tokens error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        self.session_repo
+            .revoke_all_for_user(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database revoke sessions error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        self.revoke_oauth_tokens(user_id).await
+    }
+
+    // devices the user is signed in on, most recent login first
+    pub async fn list_sessions(
+        &self,
+        user_id: i64,
+        current_id: Option<i64>,
+        page: PageRequest,
+    ) -> Result<Page<SessionReply>, AppError> {
+        let sessions = self
+            .session_repo
+            .list_active(user_id, &page)
+            .await
+            .map_err(|e| {
+                tracing::error!("database list sessions error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        let total = if page.wants_total() {
+            Some(self.session_repo.count_active(user_id).await.map_err(|e| {
+                tracing::error!("database count sessions error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?)
+        } else {
+            None
+        };
+
+        Ok(Page::new(sessions, &page, |session| session.id, total)
+            .map(|session| SessionReply::new(session, current_id)))
+    }
+
+    // signs a device out, its access tokens are refused by the auth middleware from now on
+    pub async fn revoke_session(&self, user_id: i64, session_id: i64) -> Result<(), AppError> {
+        let revoked = self
+            .session_repo
+            .revoke(session_id, user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database revoke session error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        if !revoked {
+            return Err(AppError::not_found(constants::CODE_SESSION_NOT_FOUND));
+        }
+
+        self.revoke_family(session_id).await
+    }
+
+    pub async fn get_profile(&self, user_id: i64) -> Result<UserProfileReply, AppError> {
+        let existing_user = self.repo.find_by_id(user_id).await.map_err(|e| {
+            tracing::error!("database find id error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })?;
+
+        existing_user
+            .map(UserProfileReply::from)
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))
+    }
+
+    // a new email only replaces the current one once it is verified, so a typo cannot lock
+    // the user out
+    pub async fn update_profile(
+        &self,
+        user_id: i64,
+        req: UpdateProfileRequest,
+        device: ClientDevice,
+    ) -> Result<UserProfileReply, AppError> {
+        let user = self
+            .repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))?;
+
+        let username = req.username.unwrap_or_else(|| user.username.clone());
+        let new_email = req.email.unwrap_or_else(|| user.email.clone());
+
+        // only locales present in the message catalog can be preferred
+        let locale = match req.locale {
+            Some(requested) => Some(
+                i18n::catalog()
+                    .and_then(|catalog| catalog.resolve(&requested))
+                    .ok_or_else(|| {
+                        AppError::bad_request(constants::CODE_PARAMETER_ERROR)
+                            .with_detail(format!("unsupported locale `{}`", requested))
+                    })?,
+            ),
+            None => user.locale.clone(),
+        };
+
+        // changing the email requires re-verifying the current password,
+        // wrong guesses count towards the same lockout as failed logins
+        let email_changed = new_email != user.email;
+        if email_changed {
+            let password = req
+                .current_password
+                .ok_or_else(|| AppError::bad_request(constants::CODE_PARAMETER_ERROR))?;
+            self.guard.check(&user.email, device.ip).await?;
+            if !verify(password, &user.password_hash).unwrap_or(false) {
+                self.guard.record_failure(&user.email, device.ip).await?;
+                return Err(AppError::unauthorized(
+                    constants::CODE_WRONG_ACCOUNT_OR_PASSWORD,
+                ));
+            }
+            self.guard.record_success(&user.email).await?;
+
+            let taken = self.repo.find_by_email(&new_email).await.map_err(|e| {
+                tracing::error!("database find email error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+            if taken.is_some() {
+                return Err(AppError::conflict(constants::CODE_ACCOUNT_ALREADY_EXISTS));
+            }
+        }
+
+        if email_changed {
+            self.repo
+                .set_pending_email(user.id, &new_email)
+                .await
+                .map_err(|e| {
+                    tracing::error!("database update pending email error: {}", e);
+                    AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                })?;
+        }
+
+        let updated = self
+            .repo
+            .update_profile(user.id, &username, locale.as_deref())
+            .await
+            .map_err(|e| {
+                tracing::error!("database update profile error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))?;
+
+        // the link goes to the new address, proving it can receive mail
+        if email_changed {
+            self.send_verification_email(updated.id, &updated.username, &new_email)?;
+        }
+
+        Ok(UserProfileReply::from(updated))
+    }
+
+    // every other session is revoked, the caller's session continues on the returned tokens
+    pub async fn change_password(
+        &self,
+        user_id: i64,
+        session_id: Option<i64>,
+        req: ChangePasswordRequest,
+        device: ClientDevice,
+    ) -> Result<RefreshTokenReply, AppError> {
+        let user = self.find_user(user_id).await?;
+
+        // a stolen session must not become a way around the login lockout
+        self.guard.check(&user.email, device.ip).await?;
+        if !verify(&req.current_password, &user.password_hash).unwrap_or(false) {
+            self.guard.record_failure(&user.email, device.ip).await?;
+            return Err(AppError::unauthorized(
+                constants::CODE_WRONG_ACCOUNT_OR_PASSWORD,
+            ));
+        }
+        self.guard.record_success(&user.email).await?;
+        if req.new_password == req.current_password {
+            return Err(AppError::bad_request(constants::CODE_PARAMETER_ERROR)
+                .with_detail("new password must differ from the current one"));
+        }
+        self.policy.check(
+            "new_password",
+            &req.new_password,
+            &[&user.username, &user.email],
+        )?;
+
+        let hashed = hash(req.new_password, DEFAULT_COST).map_err(|e| {
+            tracing::error!("hash error: {}", e);
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        })?;
+        self.repo
+            .update_password(user.id, &hashed)
+            .await
+            .map_err(|e| {
+                tracing::error!("database update password error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        // the version bump and token revocation also catch legacy tokens without a session,
+        // the current session gets fresh tokens below
+        self.repo
+            .increment_token_version(user.id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database update token version error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        self.refresh_repo
+            .revoke_all_for_user(user.id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database revoke refresh tokens error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        self.revoke_oauth_tokens(user.id).await?;
+
+        let current = match session_id {
+            Some(session_id) => self
+                .find_session(session_id)
+                .await?
+                .filter(|session| session.user_id == user.id && session.revoked_at.is_none()),
+            None => None,
+        };
+        let family_id = match current {
+            Some(session) => {
+                self.session_repo
+                    .revoke_others(user.id, session.id)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("database revoke sessions error: {}", e);
+                        AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                    })?;
+                self.session_repo
+                    .touch(
+                        session.id,
+                        device.user_agent.as_deref(),
+                        &device.ip.to_string(),
+                    )
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("database update session error: {}", e);
+                        AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                    })?;
+                session.id
+            }
+            // a legacy token has no session to keep, so the caller starts a new one
+            None => {
+                self.session_repo
+                    .revoke_all_for_user(user.id)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!("database revoke sessions error: {}", e);
+                        AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+                    })?;
+                let family_id = IdInstance::next_id();
+                self.create_session(family_id, user.id, &device).await?;
+                family_id
+            }
+        };
+
+        // reload for the bumped token version
+        let user = self.find_user(user_id).await?;
+        // the password was just presented, which counts as signing in again
+        let now = OffsetDateTime::now_utc();
+        let (access_token, access_exp) = self.issue_access_token(&user, family_id, now, now)?;
+        let (refresh_token, refresh_exp) =
+            self.issue_refresh_token(&user, family_id, now, now).await?;
+
+        Ok(RefreshTokenReply {
+            access_token,
+            refresh_token,
+            access_expire_time: access_exp.unix_timestamp(),
+            refresh_expire_time: refresh_exp.unix_timestamp(),
+        })
+    }
+
+    // every login flow ends here, each login starts a new refresh token family
+    pub async fn start_session(
+        &self,
+        user: User,
+        device: ClientDevice,
+    ) -> Result<LoginUserReply, AppError> {
+        let now = OffsetDateTime::now_utc();
+        let family_id = IdInstance::next_id();
+        self.create_session(family_id, user.id, &device).await?;
+        let (access_token, access_exp) = self.issue_access_token(&user, family_id, now, now)?;
+
+        let (refresh_token, refresh_exp) =
+            self.issue_refresh_token(&user, family_id, now, now).await?;
+
+        Ok(LoginUserReply {
+            username: user.username,
+            email: user.email,
+            role: user.role,
+            access_token,
+            refresh_token,
+            access_expire_time: access_exp.unix_timestamp(),
+            refresh_expire_time: refresh_exp.unix_timestamp(),
+        })
+    }
+
+    fn issue_mfa_token(&self, user: &User) -> Result<MfaChallengeReply, AppError> {
+        let exp =
+            OffsetDateTime::now_utc() + Duration::seconds(self.jwt_secret.mfa_validity_period);
+        let claims = MfaTokenClaims {
+            sub: user.id.to_string(),
+            exp: exp.unix_timestamp(),
+            token_type: "mfa".to_string(),
+            ver: user.token_version,
+        };
+
+        let mfa_token = encode(
+            &Header::new(Algorithm::HS256),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.mfa_secret.as_ref()),
+        )
+        .map_err(|e| {
+            tracing::error!("jwt encode error: {}", e);
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        })?;
+
+        Ok(MfaChallengeReply {
+            mfa_required: true,
+            mfa_token,
+            mfa_expire_time: exp.unix_timestamp(),
+        })
+    }
+
+    async fn find_user(&self, user_id: i64) -> Result<User, AppError> {
+        self.repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))
+    }
+
+    async fn create_session(
+        &self,
+        family_id: i64,
+        user_id: i64,
+        device: &ClientDevice,
+    ) -> Result<(), AppError> {
+        self.session_repo
+            .create(
+                family_id,
+                user_id,
+                device.user_agent.as_deref(),
+                &device.ip.to_string(),
+            )
+            .await
+            .map_err(|e| {
+                tracing::error!("database insert session error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })
+    }
+
+    async fn find_session(&self, session_id: i64) -> Result<Option<Session>, AppError> {
+        self.session_repo.find_by_id(session_id).await.map_err(|e| {
+            tracing::error!("database find session error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })
+    }
+
+    // revokes the session row along with its refresh token family, so the auth middleware
+    // refuses its access tokens too
+    async fn end_session(&self, family_id: i64, user_id: i64) -> Result<(), AppError> {
+        self.session_repo
+            .revoke(family_id, user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database revoke session error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+        self.revoke_family(family_id).await
+    }
+
+    // tokens handed to oauth clients outlive the user's own sessions otherwise
+    async fn revoke_oauth_tokens(&self, user_id: i64) -> Result<(), AppError> {
+        self.oauth_repo
+            .revoke_all_for_user(user_id)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                tracing::error!("database revoke oauth tokens error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })
+    }
+
+    async fn revoke_family(&self, family_id: i64) -> Result<(), AppError> {
+        self.refresh_repo
+            .revoke_family(family_id)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                tracing::error!("database revoke refresh token family error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })
+    }
+
+    // `auth_time` is when the session was started, refreshing keeps it
+    fn issue_access_token(
+        &self,
+        user: &User,
+        family_id: i64,
+        now: OffsetDateTime,
+        auth_time: OffsetDateTime,
+    ) -> Result<(String, OffsetDateTime), AppError> {
         let access_exp = now + Duration::seconds(self.jwt_secret.access_validity_period);
 
         let access_claims = AccessTokenClaims {
-            sub: existing_user.as_ref().unwrap().email.clone(),
+            iss: self.jwt_secret.issuer.clone(),
+            sub: user.id.to_string(),
+            aud: self.jwt_secret.audience.clone(),
             exp: access_exp.unix_timestamp(),
+            nbf: now.unix_timestamp(),
             iat: now.unix_timestamp(),
-            role: existing_user.as_ref().unwrap().role.clone(),
+            jti: IdInstance::next_id().to_string(),
+            sid: family_id.to_string(),
+            role: user.role.clone(),
+            ver: user.token_version,
+            auth_time: auth_time.unix_timestamp(),
+        };
+
+        let access_token = self.keys.sign(&access_claims)?;
+
+        Ok((access_token, access_exp))
+    }
+
+    fn issue_verification_token(&self, user_id: i64, email: &str) -> Result<String, AppError> {
+        let exp = OffsetDateTime::now_utc()
+            + Duration::seconds(self.jwt_secret.verification_validity_period);
+
+        let claims = VerificationTokenClaims {
+            sub: user_id.to_string(),
+            email: email.to_string(),
+            exp: exp.unix_timestamp(),
+            token_type: "verify_email".to_string(),
         };
 
-        let access_token = encode(
+        encode(
             &Header::default(), // default use HS256
-            &access_claims,
-            &EncodingKey::from_secret(self.jwt_secret.access_secret.as_ref()),
+            &claims,
+            &EncodingKey::from_secret(self.jwt_secret.verification_secret.as_ref()),
         )
         .map_err(|e| {
             tracing::error!("jwt encode error: {}", e);
-            constants::CODE_INTERNAL_SERVER_ERROR
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+        })
+    }
+
+    fn send_verification_email(
+        &self,
+        user_id: i64,
+        username: &str,
+        email: &str,
+    ) -> Result<(), AppError> {
+        let token = self.issue_verification_token(user_id, email)?;
+        self.mail.send(
+            email,
+            "verify_email",
+            &[("username", username), ("token", &token)],
+        )
+    }
+
+    // decodes a refresh JWT and loads its persisted row, along with when the session started
+    async fn find_refresh_token(
+        &self,
+        token: &str,
+    ) -> Result<(RefreshToken, OffsetDateTime), AppError> {
+        // decode & check expiry, issuer and audience
+        let validation = self.jwt_secret.validation();
+        let token_data = decode::<RefreshTokenClaims>(
+            token,
+            &DecodingKey::from_secret(self.jwt_secret.refresh_secret.as_ref()),
+            &validation,
+        )
+        .map_err(|e| {
+            tracing::debug!("jwt decode error: {}", e);
+            match e.kind() {
+                ErrorKind::ExpiredSignature => {
+                    AppError::unauthorized(constants::CODE_TOKEN_EXPIRED)
+                }
+                _ => AppError::unauthorized(constants::CODE_UNAUTHORIZED),
+            }
         })?;
 
-        // refresh token
+        // check token type
+        if token_data.claims.token_type != "refresh" {
+            return Err(AppError::unauthorized(constants::CODE_UNAUTHORIZED));
+        }
+
+        let token_id: i64 = token_data
+            .claims
+            .jti
+            .parse()
+            .map_err(|_| AppError::unauthorized(constants::CODE_UNAUTHORIZED))?;
+
+        let stored = self
+            .refresh_repo
+            .find_by_id(token_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find refresh token error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::unauthorized(constants::CODE_REFRESH_TOKEN_REVOKED))?;
+
+        // the token must describe the row it points at, legacy tokens only have `jti`
+        let claims = &token_data.claims;
+        if !claims.is_legacy()
+            && (claims.sub != stored.user_id.to_string()
+                || claims.sid != stored.family_id.to_string())
+        {
+            tracing::warn!("refresh token {} does not match its claims", stored.id);
+            return Err(AppError::unauthorized(constants::CODE_UNAUTHORIZED));
+        }
+
+        // tokens issued before `auth_time` was recorded fall back to their own issue time
+        let auth_time = OffsetDateTime::from_unix_timestamp(token_data.claims.auth_time)
+            .ok()
+            .filter(|_| token_data.claims.auth_time > 0)
+            .unwrap_or(stored.created_at);
+        Ok((stored, auth_time))
+    }
+
+    // persists the token so it can be rotated once and revoked with its family
+    async fn issue_refresh_token(
+        &self,
+        user: &User,
+        family_id: i64,
+        now: OffsetDateTime,
+        auth_time: OffsetDateTime,
+    ) -> Result<(String, OffsetDateTime), AppError> {
         let refresh_exp = now + Duration::seconds(self.jwt_secret.refresh_validity_period);
+        let token_id = IdInstance::next_id();
+
+        self.refresh_repo
+            .create(token_id, user.id, family_id, refresh_exp)
+            .await
+            .map_err(|e| {
+                tracing::error!("database insert refresh token error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
 
         let refresh_claims = RefreshTokenClaims {
-            sub: existing_user.as_ref().unwrap().email.clone(),
+            iss: self.jwt_secret.issuer.clone(),
+            sub: user.id.to_string(),
+            aud: self.jwt_secret.audience.clone(),
             exp: refresh_exp.unix_timestamp(),
+            nbf: now.unix_timestamp(),
+            iat: now.unix_timestamp(),
             token_type: "refresh".to_string(),
+            jti: token_id.to_string(),
+            sid: family_id.to_string(),
+            auth_time: auth_time.unix_timestamp(),
         };
 
         let refresh_token = encode(
@@ -117,82 +987,494 @@ impl UserService {
         )
         .map_err(|e| {
             tracing::error!("jwt encode error: {}", e);
-            constants::CODE_INTERNAL_SERVER_ERROR
+            AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
         })?;
 
-        let reply = LoginUserReply {
-            username: existing_user.as_ref().unwrap().username.clone(),
-            email: existing_user.as_ref().unwrap().email.clone(),
-            role: existing_user.as_ref().unwrap().role.clone(),
-            access_token: access_token,
-            refresh_token: refresh_token,
-            access_expire_time: access_exp.unix_timestamp(),
-            refresh_expire_time: refresh_exp.unix_timestamp(),
-        };
+        Ok((refresh_token, refresh_exp))
+    }
+}
 
-        Ok(reply)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::oauth::OAuthToken;
+    use crate::repositories::{
+        memory_login_attempt_repo::MemoryLoginAttemptRepo, memory_oauth_repo::MemoryOAuthRepo,
+        memory_refresh_token_repo::MemoryRefreshTokenRepo, memory_session_repo::MemorySessionRepo,
+        memory_signing_key_repo::MemorySigningKeyRepo, memory_two_factor_repo::MemoryTwoFactorRepo,
+        memory_user_repo::MemoryUserRepo, oauth_repo::OAuthRepo, two_factor_repo::TwoFactorRepo,
+    };
+    use async_trait::async_trait;
+    use shared::{
+        config::config::{
+            JwtSigningConfig, LockoutConfig, LoginProtectionConfig, MailQueueConfig,
+            PasswordPolicyConfig, TwoFactorConfig, config_dir,
+        },
+        mail::{
+            mail_queue::MailQueue,
+            mailer::{Mail, MailError, Mailer},
+            template::MailTemplates,
+        },
+    };
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const PASSWORD: &str = "secret123";
+    const MAX_FAILURES: i32 = 3;
+
+    struct NullMailer;
+
+    #[async_trait]
+    impl Mailer for NullMailer {
+        async fn send(&self, _mail: &Mail) -> Result<(), MailError> {
+            Ok(())
+        }
     }
 
-    pub async fn refresh_token(&self, req: RefreshTokenRequest) -> Result<RefreshTokenReply, u16> {
-        // validate refresh token
-        let mut validation = Validation::new(Algorithm::HS256);
-        // validate token_type
-        validation.set_required_spec_claims(&["exp", "sub", "token_type"]);
+    struct Harness {
+        service: UserService,
+        users: Arc<MemoryUserRepo>,
+        totps: Arc<MemoryTwoFactorRepo>,
+        oauth: Arc<MemoryOAuthRepo>,
+    }
 
-        // decode & check token expiry
-        let token_data = decode::<RefreshTokenClaims>(
-            &req.refresh_token,
-            &DecodingKey::from_secret(self.jwt_secret.refresh_secret.as_ref()),
-            &validation,
+    async fn harness() -> Harness {
+        let users = Arc::new(MemoryUserRepo::new());
+        let totps = Arc::new(MemoryTwoFactorRepo::new());
+        let oauth = Arc::new(MemoryOAuthRepo::new());
+        let guard = Arc::new(LoginGuard::new(
+            Arc::new(MemoryLoginAttemptRepo::new()),
+            LoginProtectionConfig {
+                store: "memory".to_string(),
+                window_secs: 900,
+                account: LockoutConfig {
+                    max_failures: MAX_FAILURES,
+                    lockout_secs: 60,
+                    max_lockout_secs: 600,
+                },
+                ip: LockoutConfig {
+                    max_failures: 100,
+                    lockout_secs: 60,
+                    max_lockout_secs: 600,
+                },
+            },
+        ));
+        let keys = SigningKeyService::new(
+            Arc::new(MemorySigningKeyRepo::new()),
+            JwtSigningConfig {
+                algorithm: "EdDSA".to_string(),
+                encryption_key: "d2Db7M7TqkW5b4J23voREksskX28Ppq6XSEG7tsuAr8=".to_string(),
+                legacy_secret: None,
+                rotation_period: 86400,
+                prepublish_period: 3600,
+                retention_period: 7200,
+                refresh_interval_secs: 60,
+            },
+            600,
         )
-        .map_err(|e| {
-            tracing::error!("jwt decode error: {}", e);
-            constants::CODE_PARAMETER_ERROR
-        })?;
+        .await
+        .unwrap();
+        let mail = MailService::new(
+            MailQueue::start(
+                Arc::new(NullMailer),
+                &MailQueueConfig {
+                    capacity: 10,
+                    max_retries: 0,
+                    retry_backoff_ms: 0,
+                },
+            ),
+            MailTemplates::load(&config_dir().join("mail_templates")).unwrap(),
+            "http://localhost:3000".to_string(),
+        );
+        let policy = PasswordPolicy::load(
+            PasswordPolicyConfig {
+                min_length: 8,
+                max_length: 64,
+                require_lowercase: false,
+                require_uppercase: false,
+                require_digit: false,
+                require_symbol: false,
+                disallow_user_info: false,
+                min_strength_score: 0,
+                breached_list: String::new(),
+            },
+            &config_dir(),
+        )
+        .unwrap();
+        let two_factor = TwoFactorService::new(
+            totps.clone(),
+            users.clone(),
+            guard.clone(),
+            TwoFactorConfig {
+                issuer: "test".to_string(),
+                skew: 1,
+                recovery_codes: 2,
+            },
+        );
 
-        // check token type
-        if token_data.claims.token_type != "refresh" {
-            return Err(constants::CODE_PARAMETER_ERROR);
+        let service = UserService::new(
+            users.clone(),
+            Arc::new(MemoryRefreshTokenRepo::new()),
+            Arc::new(MemorySessionRepo::new()),
+            oauth.clone(),
+            Arc::new(JwtSecret {
+                issuer: "http://localhost:8080".to_string(),
+                audience: "web-service".to_string(),
+                accept_legacy_tokens: false,
+                access_validity_period: 600,
+                refresh_secret: "refresh-secret".to_string(),
+                refresh_validity_period: 3600,
+                verification_secret: "verification-secret".to_string(),
+                verification_validity_period: 3600,
+                mfa_secret: "mfa-secret".to_string(),
+                mfa_validity_period: 300,
+            }),
+            Arc::new(keys),
+            Arc::new(mail),
+            Arc::new(policy),
+            guard,
+            Arc::new(two_factor),
+        );
+        Harness {
+            service,
+            users,
+            totps,
+            oauth,
         }
+    }
 
-        let existing_user: Option<crate::models::user::User> = self
-            .repo
-            .find_by_email(&token_data.claims.sub)
+    // a verified user, hashed at the lowest cost to keep the tests fast
+    fn add_user(harness: &Harness, email: &str) -> i64 {
+        let id = IdInstance::next_id();
+        let now = OffsetDateTime::now_utc();
+        harness.users.insert(User {
+            id,
+            username: email.to_string(),
+            email: email.to_string(),
+            password_hash: hash(PASSWORD, 4).unwrap(),
+            created_at: now,
+            updated_at: now,
+            is_active: true,
+            role: "user".to_string(),
+            token_version: 0,
+            locale: None,
+            email_verified_at: Some(now),
+            pending_email: None,
+        });
+        id
+    }
+
+    fn device() -> ClientDevice {
+        ClientDevice {
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            user_agent: Some("test".to_string()),
+        }
+    }
+
+    async fn login(harness: &Harness, email: &str) -> Result<LoginReply, AppError> {
+        let req = LoginUserRequest {
+            email: email.to_string(),
+            password: PASSWORD.to_string(),
+        };
+        harness.service.login(req, device()).await
+    }
+
+    async fn mfa_token(harness: &Harness, email: &str) -> String {
+        match login(harness, email).await.unwrap() {
+            LoginReply::MfaChallenge(challenge) => challenge.mfa_token,
+            LoginReply::Tokens(_) => panic!("expected a 2fa challenge"),
+        }
+    }
+
+    async fn wrong_code(harness: &Harness, mfa_token: &str) -> AppError {
+        let req = LoginTwoFactorRequest {
+            mfa_token: mfa_token.to_string(),
+            code: "wrong-code".to_string(),
+        };
+        harness
+            .service
+            .login_two_factor(req, device())
             .await
-            .map_err(|e| {
-                tracing::error!("database find email error: {}", e);
-                constants::CODE_DATE_OPERATION_ERROR
-            })?;
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn password_does_not_reset_failed_second_factors() {
+        let harness = harness().await;
+        let id = add_user(&harness, "a@x.com");
+        harness
+            .totps
+            .save_pending(id, "JBSWY3DPEHPK3PXP")
+            .await
+            .unwrap();
+        harness.totps.enable(id, &[]).await.unwrap();
+
+        // the password is entered again between the guesses, they must still add up
+        for _ in 0..MAX_FAILURES - 1 {
+            let mfa_token = mfa_token(&harness, "a@x.com").await;
+            assert!(matches!(
+                wrong_code(&harness, &mfa_token).await,
+                AppError::Unauthorized { .. }
+            ));
+        }
+        let mfa_token = mfa_token(&harness, "a@x.com").await;
+        wrong_code(&harness, &mfa_token).await;
+
+        assert!(matches!(
+            login(&harness, "a@x.com").await,
+            Err(AppError::TooManyRequests { code, .. }) if code == constants::CODE_ACCOUNT_LOCKED
+        ));
+    }
+
+    #[tokio::test]
+    async fn change_password_counts_wrong_current_passwords() {
+        let harness = harness().await;
+        let id = add_user(&harness, "a@x.com");
+        let change = |current: &str| ChangePasswordRequest {
+            current_password: current.to_string(),
+            new_password: "another-secret".to_string(),
+        };
+
+        for _ in 0..MAX_FAILURES {
+            let result = harness
+                .service
+                .change_password(id, None, change("wrong-password"), device())
+                .await;
+            assert!(matches!(result, Err(AppError::Unauthorized { .. })));
+        }
+        // locked, even with the right password
+        let result = harness
+            .service
+            .change_password(id, None, change(PASSWORD), device())
+            .await;
+        assert!(matches!(
+            result,
+            Err(AppError::TooManyRequests { code, .. }) if code == constants::CODE_ACCOUNT_LOCKED
+        ));
+        assert!(login(&harness, "a@x.com").await.is_err());
+    }
+
+    fn change_email(email: &str, current_password: &str) -> UpdateProfileRequest {
+        UpdateProfileRequest {
+            username: None,
+            email: Some(email.to_string()),
+            current_password: Some(current_password.to_string()),
+            locale: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn email_change_waits_for_verification() {
+        let harness = harness().await;
+        let id = add_user(&harness, "a@x.com");
+
+        let reply = harness
+            .service
+            .update_profile(id, change_email("typo@x.con", PASSWORD), device())
+            .await
+            .unwrap();
+        assert_eq!(reply.email, "a@x.com");
+        assert!(reply.email_verified);
+        assert_eq!(reply.pending_email.as_deref(), Some("typo@x.con"));
+        // the unreachable address does not lock the user out
+        assert!(login(&harness, "a@x.com").await.is_ok());
+
+        harness
+            .service
+            .update_profile(id, change_email("b@x.com", PASSWORD), device())
+            .await
+            .unwrap();
+        let stale = harness
+            .service
+            .issue_verification_token(id, "typo@x.con")
+            .unwrap();
+        assert!(
+            harness
+                .service
+                .verify_email(VerifyEmailRequest { token: stale })
+                .await
+                .is_err()
+        );
 
-        if !existing_user.is_some() {
-            return Err(constants::CODE_ACCOUNT_NOT_EXISTS);
+        let token = harness
+            .service
+            .issue_verification_token(id, "b@x.com")
+            .unwrap();
+        harness
+            .service
+            .verify_email(VerifyEmailRequest {
+                token: token.clone(),
+            })
+            .await
+            .unwrap();
+        let profile = harness.service.get_profile(id).await.unwrap();
+        assert_eq!(profile.email, "b@x.com");
+        assert!(profile.email_verified);
+        assert_eq!(profile.pending_email, None);
+        assert!(login(&harness, "b@x.com").await.is_ok());
+        // single use
+        assert!(
+            harness
+                .service
+                .verify_email(VerifyEmailRequest { token })
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn email_change_counts_wrong_current_passwords() {
+        let harness = harness().await;
+        let id = add_user(&harness, "a@x.com");
+
+        for _ in 0..MAX_FAILURES {
+            let result = harness
+                .service
+                .update_profile(id, change_email("b@x.com", "wrong-password"), device())
+                .await;
+            assert!(matches!(result, Err(AppError::Unauthorized { .. })));
         }
+        let result = harness
+            .service
+            .update_profile(id, change_email("b@x.com", PASSWORD), device())
+            .await;
+        assert!(matches!(
+            result,
+            Err(AppError::TooManyRequests { code, .. }) if code == constants::CODE_ACCOUNT_LOCKED
+        ));
+        let profile = harness.service.get_profile(id).await.unwrap();
+        assert_eq!(profile.pending_email, None);
+    }
 
-        // access token
+    async fn add_oauth_token(harness: &Harness, user_id: i64) -> String {
         let now = OffsetDateTime::now_utc();
-        let access_exp = now + Duration::seconds(self.jwt_secret.access_validity_period);
+        let token_hash = IdInstance::next_id().to_string();
+        harness
+            .oauth
+            .create_token(&OAuthToken {
+                id: IdInstance::next_id(),
+                token_hash: token_hash.clone(),
+                token_type: "refresh".to_string(),
+                grant_id: IdInstance::next_id(),
+                client_id: 1,
+                user_id: Some(user_id),
+                scope: "openid".to_string(),
+                expires_at: now + Duration::hours(1),
+                used_at: None,
+                revoked_at: None,
+                created_at: now,
+            })
+            .await
+            .unwrap();
+        token_hash
+    }
 
-        let access_claims = AccessTokenClaims {
-            sub: existing_user.as_ref().unwrap().email.clone(),
-            exp: access_exp.unix_timestamp(),
-            iat: now.unix_timestamp(),
-            role: existing_user.as_ref().unwrap().role.clone(),
+    async fn oauth_revoked(harness: &Harness, token_hash: &str) -> bool {
+        let token = harness.oauth.find_token(token_hash).await.unwrap().unwrap();
+        token.revoked_at.is_some()
+    }
+
+    #[tokio::test]
+    async fn signing_out_everywhere_revokes_oauth_tokens() {
+        let harness = harness().await;
+        let id = add_user(&harness, "a@x.com");
+        let other = add_user(&harness, "b@x.com");
+        let token = add_oauth_token(&harness, id).await;
+        let others = add_oauth_token(&harness, other).await;
+
+        harness.service.logout_all(id).await.unwrap();
+        assert!(oauth_revoked(&harness, &token).await);
+        assert!(!oauth_revoked(&harness, &others).await);
+
+        let token = add_oauth_token(&harness, id).await;
+        let req = ChangePasswordRequest {
+            current_password: PASSWORD.to_string(),
+            new_password: "another-secret".to_string(),
         };
+        harness
+            .service
+            .change_password(id, None, req, device())
+            .await
+            .unwrap();
+        assert!(oauth_revoked(&harness, &token).await);
+    }
 
-        let access_token = encode(
-            &Header::default(), // default use HS256
-            &access_claims,
-            &EncodingKey::from_secret(self.jwt_secret.access_secret.as_ref()),
-        )
-        .map_err(|e| {
-            tracing::error!("jwt encode error: {}", e);
-            constants::CODE_INTERNAL_SERVER_ERROR
-        })?;
+    async fn tokens(harness: &Harness, email: &str) -> LoginUserReply {
+        match login(harness, email).await.unwrap() {
+            LoginReply::Tokens(tokens) => tokens,
+            LoginReply::MfaChallenge(_) => panic!("expected tokens"),
+        }
+    }
 
-        let reply = RefreshTokenReply {
-            access_token: access_token,
-            access_expire_time: access_exp.unix_timestamp(),
+    async fn refresh(
+        harness: &Harness,
+        refresh_token: &str,
+    ) -> Result<RefreshTokenReply, AppError> {
+        let req = RefreshTokenRequest {
+            refresh_token: refresh_token.to_string(),
         };
-        Ok(reply)
+        harness.service.refresh_token(req, device()).await
+    }
+
+    fn revoked_code(result: Result<RefreshTokenReply, AppError>) -> Option<u16> {
+        match result {
+            Err(AppError::Unauthorized { code, .. }) => Some(code),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_rotates_the_token() {
+        let harness = harness().await;
+        add_user(&harness, "a@x.com");
+        let login = tokens(&harness, "a@x.com").await;
+
+        let first = refresh(&harness, &login.refresh_token).await.unwrap();
+        assert_ne!(first.refresh_token, login.refresh_token);
+        let second = refresh(&harness, &first.refresh_token).await.unwrap();
+        assert_ne!(second.refresh_token, first.refresh_token);
+        assert!(refresh(&harness, &second.refresh_token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn refresh_token_reuse_revokes_the_family() {
+        let harness = harness().await;
+        add_user(&harness, "a@x.com");
+        let login = tokens(&harness, "a@x.com").await;
+        let other = tokens(&harness, "a@x.com").await;
+
+        let rotated = refresh(&harness, &login.refresh_token).await.unwrap();
+        // the rotated-out token comes back, so it leaked
+        assert_eq!(
+            revoked_code(refresh(&harness, &login.refresh_token).await),
+            Some(constants::CODE_REFRESH_TOKEN_REVOKED)
+        );
+        assert_eq!(
+            revoked_code(refresh(&harness, &rotated.refresh_token).await),
+            Some(constants::CODE_REFRESH_TOKEN_REVOKED)
+        );
+        // other logins of the user are a different family
+        assert!(refresh(&harness, &other.refresh_token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn login_without_2fa_resets_failures() {
+        let harness = harness().await;
+        add_user(&harness, "a@x.com");
+        let wrong = || LoginUserRequest {
+            email: "a@x.com".to_string(),
+            password: "wrong-password".to_string(),
+        };
+
+        for _ in 0..MAX_FAILURES - 1 {
+            harness.service.login(wrong(), device()).await.unwrap_err();
+        }
+        assert!(matches!(
+            login(&harness, "a@x.com").await,
+            Ok(LoginReply::Tokens(_))
+        ));
+        for _ in 0..MAX_FAILURES - 1 {
+            harness.service.login(wrong(), device()).await.unwrap_err();
+        }
+        assert!(login(&harness, "a@x.com").await.is_ok());
     }
 }
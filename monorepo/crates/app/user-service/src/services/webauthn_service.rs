@@ -0,0 +1,295 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use shared::{
+    config::config::WebauthnConfig, constants::constants, crypto::crypto::generate_token,
+    error::error::AppError, extract::client_device::ClientDevice,
+};
+use webauthn_rs::{
+    Webauthn, WebauthnBuilder,
+    prelude::{PasskeyAuthentication, PasskeyRegistration, Url, Uuid},
+};
+
+use crate::models::passkey::{
+    PasskeyCredential, PasskeyLoginOptionsReply, PasskeyLoginOptionsRequest,
+    PasskeyLoginVerifyRequest, PasskeyRegisterOptionsReply, PasskeyRegisterVerifyRequest,
+    PasskeyReply,
+};
+use crate::models::user::{LoginUserReply, User};
+use crate::repositories::{passkey_repo::PasskeyRepo, user_repo::UserRepo};
+use crate::services::{login_guard::LoginGuard, user_service::UserService};
+use idgenerator::*;
+
+const CEREMONY_ID_BYTES: usize = 32;
+const DEFAULT_PASSKEY_NAME: &str = "Passkey";
+
+// pending ceremonies of this process, keyed by the id handed to the client
+struct CeremonyStore<T> {
+    ttl: Duration,
+    pending: Mutex<HashMap<String, (Instant, i64, T)>>,
+}
+
+impl<T> CeremonyStore<T> {
+    fn new(ttl: Duration) -> Self {
+        CeremonyStore {
+            ttl,
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn insert(&self, user_id: i64, state: T) -> String {
+        let id = generate_token(CEREMONY_ID_BYTES);
+        let mut pending = self.pending.lock().unwrap();
+        let ttl = self.ttl;
+        pending.retain(|_, (started, _, _)| started.elapsed() < ttl);
+        pending.insert(id.clone(), (Instant::now(), user_id, state));
+        id
+    }
+
+    // a ceremony can be finished once, successful or not
+    fn take(&self, id: &str) -> Option<(i64, T)> {
+        let (started, user_id, state) = self.pending.lock().unwrap().remove(id)?;
+        (started.elapsed() < self.ttl).then_some((user_id, state))
+    }
+}
+
+// passkey registration and passwordless login
+pub struct WebauthnService {
+    repo: Arc<dyn PasskeyRepo>,
+    user_repo: Arc<dyn UserRepo>,
+    users: Arc<UserService>,
+    guard: Arc<LoginGuard>,
+    webauthn: Webauthn,
+    registrations: CeremonyStore<PasskeyRegistration>,
+    authentications: CeremonyStore<PasskeyAuthentication>,
+}
+
+impl WebauthnService {
+    pub fn new(
+        repo: Arc<dyn PasskeyRepo>,
+        user_repo: Arc<dyn UserRepo>,
+        users: Arc<UserService>,
+        guard: Arc<LoginGuard>,
+        config: &WebauthnConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        let origin = Url::parse(&config.rp_origin)?;
+        let timeout = Duration::from_secs(config.ceremony_timeout_secs);
+        let webauthn = WebauthnBuilder::new(&config.rp_id, &origin)?
+            .rp_name(&config.rp_name)
+            .timeout(timeout)
+            .build()?;
+
+        Ok(WebauthnService {
+            repo,
+            user_repo,
+            users,
+            guard,
+            webauthn,
+            registrations: CeremonyStore::new(timeout),
+            authentications: CeremonyStore::new(timeout),
+        })
+    }
+
+    pub async fn register_options(
+        &self,
+        user_id: i64,
+    ) -> Result<PasskeyRegisterOptionsReply, AppError> {
+        let user = self.find_user(user_id).await?;
+
+        // authenticators that already hold a passkey for this account refuse a second one
+        let existing = self.list_passkeys(user.id).await?;
+        let exclude = existing
+            .iter()
+            .map(|credential| credential.passkey.cred_id().clone())
+            .collect();
+
+        let (options, state) = self
+            .webauthn
+            .start_passkey_registration(
+                user_handle(&user),
+                &user.email,
+                &user.username,
+                Some(exclude),
+            )
+            .map_err(|e| {
+                tracing::error!("webauthn start registration error: {}", e);
+                AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+            })?;
+
+        Ok(PasskeyRegisterOptionsReply {
+            ceremony_id: self.registrations.insert(user.id, state),
+            options,
+        })
+    }
+
+    pub async fn register_verify(
+        &self,
+        user_id: i64,
+        req: PasskeyRegisterVerifyRequest,
+    ) -> Result<PasskeyReply, AppError> {
+        let state = self
+            .registrations
+            .take(&req.ceremony_id)
+            .filter(|(owner, _)| *owner == user_id)
+            .map(|(_, state)| state)
+            .ok_or_else(|| AppError::bad_request(constants::CODE_PASSKEY_CEREMONY_INVALID))?;
+
+        let passkey = self
+            .webauthn
+            .finish_passkey_registration(&req.credential, &state)
+            .map_err(|e| {
+                tracing::debug!("webauthn finish registration error: {}", e);
+                AppError::bad_request(constants::CODE_PASSKEY_VERIFICATION_FAILED)
+            })?;
+
+        let name = req.name.as_deref().unwrap_or(DEFAULT_PASSKEY_NAME);
+        let created = self
+            .repo
+            .create(IdInstance::next_id(), user_id, name, &passkey)
+            .await
+            .map_err(|e| {
+                tracing::error!("database insert passkey error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::conflict(constants::CODE_PASSKEY_ALREADY_REGISTERED))?;
+
+        Ok(PasskeyReply::from(created))
+    }
+
+    pub async fn login_options(
+        &self,
+        req: PasskeyLoginOptionsRequest,
+        device: ClientDevice,
+    ) -> Result<PasskeyLoginOptionsReply, AppError> {
+        self.guard.check(&req.email, device.ip).await?;
+
+        let user = self
+            .user_repo
+            .find_by_email(&req.email)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find email error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        // an unknown email gets the same answer as an account without passkeys
+        let passkeys: Vec<_> = match &user {
+            Some(user) => self
+                .list_passkeys(user.id)
+                .await?
+                .into_iter()
+                .map(|credential| credential.passkey.0)
+                .collect(),
+            None => Vec::new(),
+        };
+        let Some(user) = user.filter(|_| !passkeys.is_empty()) else {
+            return Err(AppError::bad_request(
+                constants::CODE_PASSKEY_NOT_REGISTERED,
+            ));
+        };
+
+        let (options, state) = self
+            .webauthn
+            .start_passkey_authentication(&passkeys)
+            .map_err(|e| {
+                tracing::error!("webauthn start authentication error: {}", e);
+                AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+            })?;
+
+        Ok(PasskeyLoginOptionsReply {
+            ceremony_id: self.authentications.insert(user.id, state),
+            options,
+        })
+    }
+
+    // a passkey is possession plus user verification, so no TOTP challenge follows
+    pub async fn login_verify(
+        &self,
+        req: PasskeyLoginVerifyRequest,
+        device: ClientDevice,
+    ) -> Result<LoginUserReply, AppError> {
+        let (user_id, state) = self
+            .authentications
+            .take(&req.ceremony_id)
+            .ok_or_else(|| AppError::bad_request(constants::CODE_PASSKEY_CEREMONY_INVALID))?;
+        let user = self.find_user(user_id).await?;
+        self.guard.check(&user.email, device.ip).await?;
+
+        // also rejects a sign counter that went backwards, the mark of a cloned authenticator
+        let result = match self
+            .webauthn
+            .finish_passkey_authentication(&req.credential, &state)
+        {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!("webauthn authentication of user {} failed: {}", user_id, e);
+                self.guard.record_failure(&user.email, device.ip).await?;
+                return Err(AppError::unauthorized(
+                    constants::CODE_PASSKEY_VERIFICATION_FAILED,
+                ));
+            }
+        };
+
+        let credential = self
+            .repo
+            .find_by_credential_id(result.cred_id().as_ref())
+            .await
+            .map_err(|e| {
+                tracing::error!("database find passkey error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .filter(|credential| credential.user_id == user_id);
+        let Some(mut credential) = credential else {
+            self.guard.record_failure(&user.email, device.ip).await?;
+            return Err(AppError::unauthorized(
+                constants::CODE_PASSKEY_VERIFICATION_FAILED,
+            ));
+        };
+        self.guard.record_success(&user.email).await?;
+
+        credential.passkey.update_credential(&result);
+        self.repo
+            .update_usage(credential.id, &credential.passkey, result.counter() as i64)
+            .await
+            .map_err(|e| {
+                tracing::error!("database update passkey error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?;
+
+        if user.email_verified_at.is_none() {
+            return Err(AppError::forbidden(constants::CODE_EMAIL_NOT_VERIFIED));
+        }
+        if !user.is_active {
+            return Err(AppError::forbidden(constants::CODE_ACCOUNT_DISABLED));
+        }
+
+        self.users.start_session(user, device).await
+    }
+
+    async fn find_user(&self, user_id: i64) -> Result<User, AppError> {
+        self.user_repo
+            .find_by_id(user_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("database find id error: {}", e);
+                AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+            })?
+            .ok_or_else(|| AppError::not_found(constants::CODE_ACCOUNT_NOT_EXISTS))
+    }
+
+    async fn list_passkeys(&self, user_id: i64) -> Result<Vec<PasskeyCredential>, AppError> {
+        self.repo.list_by_user(user_id).await.map_err(|e| {
+            tracing::error!("database list passkeys error: {}", e);
+            AppError::internal(constants::CODE_DATE_OPERATION_ERROR)
+        })
+    }
+}
+
+// webauthn wants a stable opaque handle per account, derived from the snowflake id
+fn user_handle(user: &User) -> Uuid {
+    Uuid::from_u64_pair(0, user.id as u64)
+}
@@ -10,6 +10,36 @@ pub const MESSAGE_ACCOUNT_NOT_EXISTS: &str = "account not exists";
 pub const MESSAGE_WRONG_ACCOUNT_OR_PASSWORD: &str = "wrong account or password";
 pub const MESSAGE_DATE_OPERATION_ERROR: &str = "database operation error";
 pub const MESSAGE_INTERNAL_SERVER_ERROR: &str = "internal server error";
+pub const MESSAGE_UNAUTHORIZED: &str = "unauthorized";
+pub const MESSAGE_TOKEN_EXPIRED: &str = "token expired";
+pub const MESSAGE_FORBIDDEN: &str = "forbidden";
+pub const MESSAGE_REFRESH_TOKEN_REVOKED: &str = "refresh token revoked";
+pub const MESSAGE_TOKEN_REVOKED: &str = "token revoked";
+pub const MESSAGE_EMAIL_NOT_VERIFIED: &str = "email not verified";
+pub const MESSAGE_ACCOUNT_DISABLED: &str = "account disabled";
+pub const MESSAGE_VERIFICATION_TOKEN_INVALID: &str = "verification token invalid";
+pub const MESSAGE_RESET_TOKEN_INVALID: &str = "reset token invalid";
+pub const MESSAGE_WEAK_PASSWORD: &str = "password does not meet the policy";
+pub const MESSAGE_ACCOUNT_LOCKED: &str = "account temporarily locked";
+pub const MESSAGE_TOO_MANY_ATTEMPTS: &str = "too many login attempts";
+pub const MESSAGE_TOO_MANY_REQUESTS: &str = "too many requests";
+pub const MESSAGE_TWO_FACTOR_ALREADY_ENABLED: &str = "two-factor authentication already enabled";
+pub const MESSAGE_TWO_FACTOR_NOT_ENABLED: &str = "two-factor authentication not enabled";
+pub const MESSAGE_TWO_FACTOR_CODE_INVALID: &str = "invalid two-factor code";
+pub const MESSAGE_MFA_TOKEN_INVALID: &str = "two-factor challenge invalid or expired";
+pub const MESSAGE_PASSKEY_CEREMONY_INVALID: &str = "passkey ceremony invalid or expired";
+pub const MESSAGE_PASSKEY_VERIFICATION_FAILED: &str = "passkey verification failed";
+pub const MESSAGE_PASSKEY_NOT_REGISTERED: &str = "no passkey registered";
+pub const MESSAGE_PASSKEY_ALREADY_REGISTERED: &str = "passkey already registered";
+pub const MESSAGE_OIDC_PROVIDER_NOT_FOUND: &str = "unknown identity provider";
+pub const MESSAGE_OIDC_STATE_INVALID: &str = "sign-in state invalid or expired";
+pub const MESSAGE_OIDC_LOGIN_FAILED: &str = "identity provider sign-in failed";
+pub const MESSAGE_OIDC_EMAIL_UNVERIFIED: &str = "identity provider did not return a verified email";
+pub const MESSAGE_OAUTH_CLIENT_INVALID: &str = "unknown or unauthorized client";
+pub const MESSAGE_OAUTH_REDIRECT_URI_INVALID: &str = "redirect uri not registered for the client";
+pub const MESSAGE_OAUTH_REQUEST_INVALID: &str = "invalid authorization request";
+pub const MESSAGE_OAUTH_SCOPE_INVALID: &str = "requested scope not allowed";
+pub const MESSAGE_SESSION_NOT_FOUND: &str = "session not found";
 
 pub const CODE_SUCCESS: u16 = 0;
 pub const CODE_FAILURE: u16 = 9999;
@@ -19,6 +49,36 @@ pub const CODE_ACCOUNT_NOT_EXISTS: u16 = 10002;
 pub const CODE_WRONG_ACCOUNT_OR_PASSWORD: u16 = 10003;
 pub const CODE_DATE_OPERATION_ERROR: u16 = 10004;
 pub const CODE_INTERNAL_SERVER_ERROR: u16 = 10005;
+pub const CODE_UNAUTHORIZED: u16 = 10006;
+pub const CODE_TOKEN_EXPIRED: u16 = 10007;
+pub const CODE_FORBIDDEN: u16 = 10008;
+pub const CODE_REFRESH_TOKEN_REVOKED: u16 = 10009;
+pub const CODE_TOKEN_REVOKED: u16 = 10010;
+pub const CODE_EMAIL_NOT_VERIFIED: u16 = 10011;
+pub const CODE_ACCOUNT_DISABLED: u16 = 10012;
+pub const CODE_VERIFICATION_TOKEN_INVALID: u16 = 10013;
+pub const CODE_RESET_TOKEN_INVALID: u16 = 10014;
+pub const CODE_WEAK_PASSWORD: u16 = 10015;
+pub const CODE_ACCOUNT_LOCKED: u16 = 10016;
+pub const CODE_TOO_MANY_ATTEMPTS: u16 = 10017;
+pub const CODE_TOO_MANY_REQUESTS: u16 = 10018;
+pub const CODE_TWO_FACTOR_ALREADY_ENABLED: u16 = 10019;
+pub const CODE_TWO_FACTOR_NOT_ENABLED: u16 = 10020;
+pub const CODE_TWO_FACTOR_CODE_INVALID: u16 = 10021;
+pub const CODE_MFA_TOKEN_INVALID: u16 = 10022;
+pub const CODE_PASSKEY_CEREMONY_INVALID: u16 = 10023;
+pub const CODE_PASSKEY_VERIFICATION_FAILED: u16 = 10024;
+pub const CODE_PASSKEY_NOT_REGISTERED: u16 = 10025;
+pub const CODE_PASSKEY_ALREADY_REGISTERED: u16 = 10026;
+pub const CODE_OIDC_PROVIDER_NOT_FOUND: u16 = 10027;
+pub const CODE_OIDC_STATE_INVALID: u16 = 10028;
+pub const CODE_OIDC_LOGIN_FAILED: u16 = 10029;
+pub const CODE_OIDC_EMAIL_UNVERIFIED: u16 = 10030;
+pub const CODE_OAUTH_CLIENT_INVALID: u16 = 10031;
+pub const CODE_OAUTH_REDIRECT_URI_INVALID: u16 = 10032;
+pub const CODE_OAUTH_REQUEST_INVALID: u16 = 10033;
+pub const CODE_OAUTH_SCOPE_INVALID: u16 = 10034;
+pub const CODE_SESSION_NOT_FOUND: u16 = 10035;
 
 static GLOBAL_DATA_REPLY: Lazy<Mutex<HashMap<u16, &'static str>>> = Lazy::new(|| {
     let mut m: HashMap<u16, &str> = HashMap::new();
@@ -33,6 +93,60 @@ static GLOBAL_DATA_REPLY: Lazy<Mutex<HashMap<u16, &'static str>>> = Lazy::new(||
     );
     m.insert(CODE_DATE_OPERATION_ERROR, MESSAGE_DATE_OPERATION_ERROR);
     m.insert(CODE_INTERNAL_SERVER_ERROR, MESSAGE_INTERNAL_SERVER_ERROR);
+    m.insert(CODE_UNAUTHORIZED, MESSAGE_UNAUTHORIZED);
+    m.insert(CODE_TOKEN_EXPIRED, MESSAGE_TOKEN_EXPIRED);
+    m.insert(CODE_FORBIDDEN, MESSAGE_FORBIDDEN);
+    m.insert(CODE_REFRESH_TOKEN_REVOKED, MESSAGE_REFRESH_TOKEN_REVOKED);
+    m.insert(CODE_TOKEN_REVOKED, MESSAGE_TOKEN_REVOKED);
+    m.insert(CODE_EMAIL_NOT_VERIFIED, MESSAGE_EMAIL_NOT_VERIFIED);
+    m.insert(CODE_ACCOUNT_DISABLED, MESSAGE_ACCOUNT_DISABLED);
+    m.insert(
+        CODE_VERIFICATION_TOKEN_INVALID,
+        MESSAGE_VERIFICATION_TOKEN_INVALID,
+    );
+    m.insert(CODE_RESET_TOKEN_INVALID, MESSAGE_RESET_TOKEN_INVALID);
+    m.insert(CODE_WEAK_PASSWORD, MESSAGE_WEAK_PASSWORD);
+    m.insert(CODE_ACCOUNT_LOCKED, MESSAGE_ACCOUNT_LOCKED);
+    m.insert(CODE_TOO_MANY_ATTEMPTS, MESSAGE_TOO_MANY_ATTEMPTS);
+    m.insert(CODE_TOO_MANY_REQUESTS, MESSAGE_TOO_MANY_REQUESTS);
+    m.insert(
+        CODE_TWO_FACTOR_ALREADY_ENABLED,
+        MESSAGE_TWO_FACTOR_ALREADY_ENABLED,
+    );
+    m.insert(CODE_TWO_FACTOR_NOT_ENABLED, MESSAGE_TWO_FACTOR_NOT_ENABLED);
+    m.insert(
+        CODE_TWO_FACTOR_CODE_INVALID,
+        MESSAGE_TWO_FACTOR_CODE_INVALID,
+    );
+    m.insert(CODE_MFA_TOKEN_INVALID, MESSAGE_MFA_TOKEN_INVALID);
+    m.insert(
+        CODE_PASSKEY_CEREMONY_INVALID,
+        MESSAGE_PASSKEY_CEREMONY_INVALID,
+    );
+    m.insert(
+        CODE_PASSKEY_VERIFICATION_FAILED,
+        MESSAGE_PASSKEY_VERIFICATION_FAILED,
+    );
+    m.insert(CODE_PASSKEY_NOT_REGISTERED, MESSAGE_PASSKEY_NOT_REGISTERED);
+    m.insert(
+        CODE_PASSKEY_ALREADY_REGISTERED,
+        MESSAGE_PASSKEY_ALREADY_REGISTERED,
+    );
+    m.insert(
+        CODE_OIDC_PROVIDER_NOT_FOUND,
+        MESSAGE_OIDC_PROVIDER_NOT_FOUND,
+    );
+    m.insert(CODE_OIDC_STATE_INVALID, MESSAGE_OIDC_STATE_INVALID);
+    m.insert(CODE_OIDC_LOGIN_FAILED, MESSAGE_OIDC_LOGIN_FAILED);
+    m.insert(CODE_OIDC_EMAIL_UNVERIFIED, MESSAGE_OIDC_EMAIL_UNVERIFIED);
+    m.insert(CODE_OAUTH_CLIENT_INVALID, MESSAGE_OAUTH_CLIENT_INVALID);
+    m.insert(
+        CODE_OAUTH_REDIRECT_URI_INVALID,
+        MESSAGE_OAUTH_REDIRECT_URI_INVALID,
+    );
+    m.insert(CODE_OAUTH_REQUEST_INVALID, MESSAGE_OAUTH_REQUEST_INVALID);
+    m.insert(CODE_OAUTH_SCOPE_INVALID, MESSAGE_OAUTH_SCOPE_INVALID);
+    m.insert(CODE_SESSION_NOT_FOUND, MESSAGE_SESSION_NOT_FOUND);
     Mutex::new(m)
 });
 
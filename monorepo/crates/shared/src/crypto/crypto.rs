@@ -0,0 +1,103 @@
+use aes_gcm::{
+    Aes256Gcm, KeyInit, Nonce,
+    aead::{Aead, Payload},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::{Rng, RngCore};
+use sha2::{Digest, Sha256};
+
+// url-safe random token carrying `bytes` bytes of entropy
+pub fn generate_token(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    rand::rngs::OsRng.fill_bytes(&mut buf);
+    URL_SAFE_NO_PAD.encode(buf)
+}
+
+// random code drawn from `alphabet`, for codes people have to type
+pub fn random_code(len: usize, alphabet: &[u8]) -> String {
+    let mut rng = rand::rngs::OsRng;
+    (0..len)
+        .map(|_| alphabet[rng.gen_range(0..alphabet.len())] as char)
+        .collect()
+}
+
+// opaque tokens are stored as digests so a database leak does not expose them
+pub fn sha256_hex(input: &str) -> String {
+    Sha256::digest(input.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// RFC 7636 `S256` code challenge of a PKCE verifier
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+const NONCE_BYTES: usize = 12;
+
+// AES-256-GCM for secrets at rest, `base64url(nonce || ciphertext)`, `context` is authenticated
+// but not stored, so a value only opens under the context it was sealed with
+pub fn seal(key: &[u8; 32], plaintext: &[u8], context: &[u8]) -> String {
+    let mut nonce = [0u8; NONCE_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut nonce);
+    let payload = Payload {
+        msg: plaintext,
+        aad: context,
+    };
+    let ciphertext = Aes256Gcm::new(key.into())
+        .encrypt(Nonce::from_slice(&nonce), payload)
+        .expect("AES-GCM encryption of an in-memory buffer");
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend(ciphertext);
+    URL_SAFE_NO_PAD.encode(sealed)
+}
+
+// `None` for a wrong key or context and for anything tampered with
+pub fn open(key: &[u8; 32], sealed: &str, context: &[u8]) -> Option<Vec<u8>> {
+    let sealed = URL_SAFE_NO_PAD.decode(sealed).ok()?;
+    if sealed.len() < NONCE_BYTES {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_BYTES);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: context,
+    };
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), payload)
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_values_open_with_the_same_key_and_context() {
+        let key = [7u8; 32];
+        let sealed = seal(&key, b"private key", b"kid-1");
+        assert_ne!(seal(&key, b"private key", b"kid-1"), sealed);
+
+        assert_eq!(
+            open(&key, &sealed, b"kid-1").as_deref(),
+            Some(&b"private key"[..])
+        );
+        assert_eq!(open(&[8u8; 32], &sealed, b"kid-1"), None);
+        assert_eq!(open(&key, &sealed, b"kid-2"), None);
+    }
+
+    #[test]
+    fn tampered_values_do_not_open() {
+        let key = [7u8; 32];
+        let mut sealed = URL_SAFE_NO_PAD
+            .decode(seal(&key, b"private key", b""))
+            .unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+
+        assert_eq!(open(&key, &URL_SAFE_NO_PAD.encode(&sealed), b""), None);
+        assert_eq!(open(&key, "AAAA", b""), None);
+        assert_eq!(open(&key, "not base64!", b""), None);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::constants::constants;
+use crate::reply::reply::{FieldErrors, Reply};
+use axum::{
+    Json,
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use std::fmt;
+
+// business error, `code` is one of `constants::CODE_*`
+#[derive(Debug)]
+pub enum AppError {
+    BadRequest {
+        code: u16,
+        detail: Option<String>,
+    },
+    Unauthorized {
+        code: u16,
+        detail: Option<String>,
+    },
+    Forbidden {
+        code: u16,
+        detail: Option<String>,
+    },
+    NotFound {
+        code: u16,
+        detail: Option<String>,
+    },
+    Conflict {
+        code: u16,
+        detail: Option<String>,
+    },
+    Internal {
+        code: u16,
+        detail: Option<String>,
+    },
+    // throttled, `retry_after` seconds are also sent in the `Retry-After` header
+    TooManyRequests {
+        code: u16,
+        detail: Option<String>,
+        retry_after: u64,
+    },
+    // request body failed to parse or validate, answered with 400
+    Validation {
+        code: u16,
+        detail: Option<String>,
+        errors: FieldErrors,
+    },
+}
+
+impl AppError {
+    pub fn bad_request(code: u16) -> Self {
+        AppError::BadRequest { code, detail: None }
+    }
+
+    pub fn unauthorized(code: u16) -> Self {
+        AppError::Unauthorized { code, detail: None }
+    }
+
+    pub fn forbidden(code: u16) -> Self {
+        AppError::Forbidden { code, detail: None }
+    }
+
+    pub fn not_found(code: u16) -> Self {
+        AppError::NotFound { code, detail: None }
+    }
+
+    pub fn conflict(code: u16) -> Self {
+        AppError::Conflict { code, detail: None }
+    }
+
+    pub fn internal(code: u16) -> Self {
+        AppError::Internal { code, detail: None }
+    }
+
+    pub fn too_many_requests(code: u16, retry_after: u64) -> Self {
+        AppError::TooManyRequests {
+            code,
+            detail: None,
+            retry_after,
+        }
+    }
+
+    pub fn validation(errors: FieldErrors) -> Self {
+        AppError::Validation {
+            code: constants::CODE_PARAMETER_ERROR,
+            detail: None,
+            errors,
+        }
+    }
+
+    pub fn with_detail(mut self, message: impl Into<String>) -> Self {
+        match &mut self {
+            AppError::BadRequest { detail, .. }
+            | AppError::Unauthorized { detail, .. }
+            | AppError::Forbidden { detail, .. }
+            | AppError::NotFound { detail, .. }
+            | AppError::Conflict { detail, .. }
+            | AppError::Internal { detail, .. }
+            | AppError::TooManyRequests { detail, .. }
+            | AppError::Validation { detail, .. } => *detail = Some(message.into()),
+        }
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest { .. } | AppError::Validation { .. } => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden { .. } => StatusCode::FORBIDDEN,
+            AppError::NotFound { .. } => StatusCode::NOT_FOUND,
+            AppError::Conflict { .. } => StatusCode::CONFLICT,
+            AppError::Internal { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        match self {
+            AppError::BadRequest { code, .. }
+            | AppError::Unauthorized { code, .. }
+            | AppError::Forbidden { code, .. }
+            | AppError::NotFound { code, .. }
+            | AppError::Conflict { code, .. }
+            | AppError::Internal { code, .. }
+            | AppError::TooManyRequests { code, .. }
+            | AppError::Validation { code, .. } => *code,
+        }
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            AppError::BadRequest { detail, .. }
+            | AppError::Unauthorized { detail, .. }
+            | AppError::Forbidden { detail, .. }
+            | AppError::NotFound { detail, .. }
+            | AppError::Conflict { detail, .. }
+            | AppError::Internal { detail, .. }
+            | AppError::TooManyRequests { detail, .. }
+            | AppError::Validation { detail, .. } => detail.as_deref(),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.detail() {
+            Some(detail) => write!(f, "{} ({}): {}", self.code(), self.status(), detail),
+            None => write!(f, "{} ({})", self.code(), self.status()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let mut reply = Reply::<()>::error(self.code());
+        reply.detail = self.detail().map(str::to_string);
+        let retry_after = match self {
+            AppError::Validation { errors, .. } => {
+                reply.errors = Some(errors);
+                None
+            }
+            AppError::TooManyRequests { retry_after, .. } => Some(retry_after),
+            _ => None,
+        };
+
+        let mut response = (status, Json(reply)).into_response();
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
+    }
+}
@@ -0,0 +1,35 @@
+use crate::error::error::AppError;
+use crate::extract::client_ip::ClientIp;
+use axum::{
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use std::net::IpAddr;
+
+const MAX_USER_AGENT_CHARS: usize = 512;
+
+// where a request comes from, recorded on the sessions it starts
+#[derive(Debug, Clone)]
+pub struct ClientDevice {
+    pub ip: IpAddr,
+    pub user_agent: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for ClientDevice
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ClientIp(ip) = ClientIp::from_request_parts(parts, state).await?;
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_CHARS).collect::<String>())
+            .filter(|value| !value.is_empty());
+
+        Ok(ClientDevice { ip, user_agent })
+    }
+}
@@ -0,0 +1,139 @@
+use crate::constants::constants;
+use crate::error::error::AppError;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{HeaderMap, HeaderName, request::Parts},
+};
+use ipnet::IpNet;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+// address of the client, requires serving with `into_make_service_with_connect_info`,
+// behind a proxy it is taken from `X-Forwarded-For` once `TrustedProxies` is an extension
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+// `service.trusted_proxies`, usage: `router.layer(Extension(trusted_proxies))`
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies(Arc<Vec<IpNet>>);
+
+impl TrustedProxies {
+    pub fn new(proxies: Vec<IpNet>) -> Self {
+        TrustedProxies(Arc::new(proxies))
+    }
+
+    fn trusts(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|proxy| proxy.contains(&ip))
+    }
+
+    // walks `X-Forwarded-For` from the peer backwards while the hop is a trusted proxy,
+    // entries left of the first untrusted hop may be forged by the client
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut forwarded: Vec<&str> = headers
+            .get_all(X_FORWARDED_FOR)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+
+        let mut client = peer.to_canonical();
+        while self.trusts(client) {
+            match forwarded.pop().and_then(|hop| hop.parse::<IpAddr>().ok()) {
+                Some(hop) => client = hop.to_canonical(),
+                None => break,
+            }
+        }
+        client
+    }
+}
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip())
+            .ok_or_else(|| {
+                tracing::error!("connect info missing, client ip unavailable");
+                AppError::internal(constants::CODE_INTERNAL_SERVER_ERROR)
+            })?;
+
+        let ip = match parts.extensions.get::<TrustedProxies>() {
+            Some(proxies) => proxies.client_ip(peer, &parts.headers),
+            None => peer,
+        };
+        Ok(ClientIp(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn proxies(ranges: &[&str]) -> TrustedProxies {
+        TrustedProxies::new(ranges.iter().map(|range| range.parse().unwrap()).collect())
+    }
+
+    fn forwarded_for(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(X_FORWARDED_FOR, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn ignores_the_header_from_untrusted_peers() {
+        let proxies = proxies(&["10.0.0.0/8"]);
+        let headers = forwarded_for(&["1.1.1.1"]);
+        assert_eq!(proxies.client_ip(ip("2.2.2.2"), &headers), ip("2.2.2.2"));
+        assert_eq!(
+            TrustedProxies::default().client_ip(ip("10.0.0.1"), &headers),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn stops_at_the_first_untrusted_hop() {
+        let proxies = proxies(&["10.0.0.0/8", "127.0.0.1/32"]);
+        // the client prepended a forged address
+        let headers = forwarded_for(&["6.6.6.6, 1.1.1.1", "10.0.0.2"]);
+        assert_eq!(proxies.client_ip(ip("127.0.0.1"), &headers), ip("1.1.1.1"));
+    }
+
+    #[test]
+    fn falls_back_to_the_last_trusted_hop() {
+        let proxies = proxies(&["10.0.0.0/8"]);
+        assert_eq!(
+            proxies.client_ip(ip("10.0.0.1"), &HeaderMap::new()),
+            ip("10.0.0.1")
+        );
+        let headers = forwarded_for(&["unknown, 10.0.0.2"]);
+        assert_eq!(proxies.client_ip(ip("10.0.0.1"), &headers), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn matches_ipv4_mapped_peers() {
+        let proxies = proxies(&["127.0.0.1/32"]);
+        let headers = forwarded_for(&["1.1.1.1"]);
+        assert_eq!(
+            proxies.client_ip(ip("::ffff:127.0.0.1"), &headers),
+            ip("1.1.1.1")
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use crate::error::error::AppError;
+use crate::reply::reply::{FieldError, FieldErrors};
+use axum::{
+    Json,
+    extract::{FromRequest, Request, rejection::JsonRejection},
+};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+// json body that is deserialized and validated, failures become `AppError::Validation`
+pub struct ValidatedJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await.map_err(|e| {
+            tracing::error!("json error: {}", e);
+            AppError::validation(json_field_errors(&e))
+        })?;
+
+        value.validate().map_err(|e| {
+            tracing::error!("validate error: {}", e);
+            AppError::validation(validation_field_errors(&e))
+        })?;
+
+        Ok(ValidatedJson(value))
+    }
+}
+
+// the body could not be parsed at all, so the error is reported against `body`
+fn json_field_errors(rejection: &JsonRejection) -> FieldErrors {
+    let code = match rejection {
+        JsonRejection::JsonDataError(_) => "invalid_data",
+        JsonRejection::JsonSyntaxError(_) => "invalid_syntax",
+        JsonRejection::MissingJsonContentType(_) => "missing_content_type",
+        _ => "invalid_body",
+    };
+
+    let mut errors = FieldErrors::new();
+    errors.insert(
+        "body".to_string(),
+        vec![FieldError {
+            code: code.to_string(),
+            message: Some(rejection.body_text()),
+            params: BTreeMap::new(),
+        }],
+    );
+    errors
+}
+
+pub fn validation_field_errors(errors: &ValidationErrors) -> FieldErrors {
+    let mut fields = FieldErrors::new();
+    collect_field_errors(errors, "", &mut fields);
+    fields
+}
+
+// nested structs and lists are flattened into `parent.child` / `list[0].child` paths
+fn collect_field_errors(errors: &ValidationErrors, prefix: &str, fields: &mut FieldErrors) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                let entries = fields.entry(path).or_default();
+                for error in errors {
+                    entries.push(FieldError {
+                        code: error.code.to_string(),
+                        message: error.message.as_ref().map(|m| m.to_string()),
+                        // never echo the rejected value back, it may be a password
+                        params: error
+                            .params
+                            .iter()
+                            .filter(|(name, _)| *name != "value")
+                            .map(|(name, value)| (name.to_string(), value.clone()))
+                            .collect(),
+                    });
+                }
+            }
+            ValidationErrorsKind::Struct(errors) => {
+                collect_field_errors(errors, &path, fields);
+            }
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect_field_errors(errors, &format!("{}[{}]", path, index), fields);
+                }
+            }
+        }
+    }
+}
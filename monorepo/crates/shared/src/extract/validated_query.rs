@@ -0,0 +1,47 @@
+use crate::error::error::AppError;
+use crate::extract::validated_json::validation_field_errors;
+use crate::reply::reply::{FieldError, FieldErrors};
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::de::DeserializeOwned;
+use std::collections::BTreeMap;
+use validator::Validate;
+
+// query string that is deserialized and validated, failures become `AppError::Validation`
+pub struct ValidatedQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ValidatedQuery<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::<T>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| {
+                tracing::error!("query error: {}", e);
+                // the query could not be parsed at all, so the error is reported against `query`
+                let mut errors = FieldErrors::new();
+                errors.insert(
+                    "query".to_string(),
+                    vec![FieldError {
+                        code: "invalid_query".to_string(),
+                        message: Some(e.body_text()),
+                        params: BTreeMap::new(),
+                    }],
+                );
+                AppError::validation(errors)
+            })?;
+
+        value.validate().map_err(|e| {
+            tracing::error!("validate error: {}", e);
+            AppError::validation(validation_field_errors(&e))
+        })?;
+
+        Ok(ValidatedQuery(value))
+    }
+}
@@ -0,0 +1,226 @@
+use crate::constants::constants;
+use axum::{extract::Request, http::header, middleware::Next, response::Response};
+use once_cell::sync::OnceCell;
+use std::{collections::HashMap, error::Error, fs, future::Future, path::Path};
+
+tokio::task_local! {
+    // locale of the request being handled, set by `negotiate_locale`
+    static LOCALE: String;
+}
+
+static CATALOG: OnceCell<Catalog> = OnceCell::new();
+
+// reply messages keyed by locale tag and business code
+#[derive(Debug)]
+pub struct Catalog {
+    default_locale: String,
+    messages: HashMap<String, HashMap<u16, String>>,
+}
+
+impl Catalog {
+    // reads every `<locale>.yaml` in `dir`, e.g. `zh-CN.yaml` with `10001: "账号已存在"`
+    pub fn load(dir: &Path, default_locale: &str) -> Result<Self, Box<dyn Error>> {
+        let mut messages = HashMap::new();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yaml") {
+                continue;
+            }
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let content = fs::read_to_string(&path)?;
+            let catalog: HashMap<u16, String> = serde_yml::from_str(&content).map_err(|e| {
+                tracing::error!("Failed to parse locale file {}: {}", path.display(), e);
+                Box::new(e) as Box<dyn Error>
+            })?;
+            messages.insert(normalize(locale), catalog);
+        }
+
+        tracing::info!("Loaded locales: {:?}", messages.keys().collect::<Vec<_>>());
+
+        Ok(Catalog {
+            default_locale: normalize(default_locale),
+            messages,
+        })
+    }
+
+    pub fn default_locale(&self) -> &str {
+        &self.default_locale
+    }
+
+    // exact tag first, then the primary language, e.g. `zh-TW` -> `zh` -> `zh-cn`
+    pub fn resolve(&self, requested: &str) -> Option<String> {
+        let requested = normalize(requested);
+        if self.messages.contains_key(&requested) {
+            return Some(requested);
+        }
+
+        let primary = requested.split('-').next().unwrap_or_default();
+        if self.messages.contains_key(primary) {
+            return Some(primary.to_string());
+        }
+
+        let mut candidates: Vec<&String> = self
+            .messages
+            .keys()
+            .filter(|locale| locale.split('-').next() == Some(primary))
+            .collect();
+        candidates.sort();
+        candidates.first().map(|locale| locale.to_string())
+    }
+
+    // picks the best supported locale of an `Accept-Language` header value
+    pub fn negotiate(&self, accept_language: &str) -> Option<String> {
+        let mut ranges: Vec<(&str, f32)> = accept_language
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                (!tag.is_empty() && quality > 0.0).then_some((tag, quality))
+            })
+            .collect();
+        ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        ranges.into_iter().find_map(|(tag, _)| match tag {
+            "*" => Some(self.default_locale.clone()),
+            _ => self.resolve(tag),
+        })
+    }
+
+    pub fn message(&self, locale: &str, code: u16) -> Option<&str> {
+        self.messages
+            .get(locale)
+            .and_then(|catalog| catalog.get(&code))
+            .or_else(|| {
+                self.messages
+                    .get(&self.default_locale)
+                    .and_then(|catalog| catalog.get(&code))
+            })
+            .map(String::as_str)
+    }
+}
+
+pub fn normalize(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_lowercase()
+}
+
+pub fn init(catalog: Catalog) {
+    if CATALOG.set(catalog).is_err() {
+        tracing::warn!("i18n catalog is already initialized");
+    }
+}
+
+pub fn catalog() -> Option<&'static Catalog> {
+    CATALOG.get()
+}
+
+pub fn current_locale() -> Option<String> {
+    LOCALE.try_with(|locale| locale.clone()).ok()
+}
+
+// runs `f` with `locale` as the current locale
+pub async fn scope<F: Future>(locale: String, f: F) -> F::Output {
+    LOCALE.scope(locale, f).await
+}
+
+// localized message for `code`, falls back to the default locale and then the built-in English
+pub fn message(code: u16) -> String {
+    let localized = CATALOG.get().and_then(|catalog| {
+        let locale = current_locale().unwrap_or_else(|| catalog.default_locale.clone());
+        catalog.message(&locale, code)
+    });
+
+    localized
+        .unwrap_or_else(|| constants::get_string_value(code))
+        .to_string()
+}
+
+// usage: `app.layer(middleware::from_fn(negotiate_locale))`
+pub async fn negotiate_locale(req: Request, next: Next) -> Response {
+    let Some(catalog) = CATALOG.get() else {
+        return next.run(req).await;
+    };
+
+    let locale = req
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| catalog.negotiate(value))
+        .unwrap_or_else(|| catalog.default_locale.clone());
+
+    scope(locale, next.run(req)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog(locales: &[&str], default_locale: &str) -> Catalog {
+        Catalog {
+            default_locale: default_locale.to_string(),
+            messages: locales
+                .iter()
+                .map(|locale| (locale.to_string(), HashMap::from([(1, locale.to_string())])))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn negotiate_orders_by_quality() {
+        let catalog = catalog(&["en", "zh-cn", "fr"], "en");
+
+        assert_eq!(
+            catalog
+                .negotiate("fr;q=0.5, zh-CN;q=0.9, en;q=0.1")
+                .as_deref(),
+            Some("zh-cn")
+        );
+        // ranges without `q` weigh 1.0
+        assert_eq!(catalog.negotiate("en;q=0.8, fr").as_deref(), Some("fr"));
+        assert_eq!(catalog.negotiate("zh_CN").as_deref(), Some("zh-cn"));
+    }
+
+    #[test]
+    fn negotiate_skips_unsupported_and_refused_ranges() {
+        let catalog = catalog(&["en", "zh-cn", "fr"], "en");
+
+        assert_eq!(
+            catalog.negotiate("de, ja;q=0.9, fr;q=0.2").as_deref(),
+            Some("fr")
+        );
+        assert_eq!(catalog.negotiate("fr;q=0, en;q=0.3").as_deref(), Some("en"));
+        assert_eq!(catalog.negotiate("fr;q=abc").as_deref(), Some("fr"));
+        assert_eq!(catalog.negotiate("de, ja"), None);
+        assert_eq!(catalog.negotiate(""), None);
+    }
+
+    #[test]
+    fn negotiate_falls_back_to_primary_language_and_wildcard() {
+        let catalog = catalog(&["en", "zh-cn"], "en");
+
+        assert_eq!(catalog.negotiate("zh-TW").as_deref(), Some("zh-cn"));
+        assert_eq!(catalog.negotiate("en-GB;q=0.9").as_deref(), Some("en"));
+        assert_eq!(catalog.negotiate("de, *;q=0.1").as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn message_falls_back_to_default_locale() {
+        let mut catalog = catalog(&["en", "zh-cn"], "en");
+        catalog
+            .messages
+            .get_mut("en")
+            .unwrap()
+            .insert(2, "only english".to_string());
+
+        assert_eq!(catalog.message("zh-cn", 1), Some("zh-cn"));
+        assert_eq!(catalog.message("zh-cn", 2), Some("only english"));
+        assert_eq!(catalog.message("zh-cn", 3), None);
+    }
+}
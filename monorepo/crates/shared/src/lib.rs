@@ -1,3 +1,5 @@
+#![allow(clippy::module_inception)]
+
 pub mod config {
     pub mod config;
 }
@@ -6,10 +8,51 @@ pub mod constants {
     pub mod constants;
 }
 
+pub mod crypto {
+    pub mod crypto;
+}
+
+pub mod error {
+    pub mod error;
+}
+
+pub mod extract {
+    pub mod client_device;
+    pub mod client_ip;
+    pub mod validated_json;
+    pub mod validated_query;
+}
+
+pub mod i18n {
+    pub mod i18n;
+}
+
 pub mod logger {
     pub mod logger;
 }
 
+pub mod mail {
+    pub mod file_mailer;
+    pub mod mail_queue;
+    pub mod mailer;
+    pub mod smtp_mailer;
+    pub mod template;
+}
+
+pub mod pagination {
+    pub mod keyset;
+    pub mod page;
+    pub mod page_request;
+}
+
+pub mod password {
+    pub mod policy;
+}
+
+pub mod rate_limit {
+    pub mod rate_limit;
+}
+
 pub mod reply {
     pub mod reply;
 }
@@ -0,0 +1,30 @@
+use crate::mail::mailer::{Mail, MailError, Mailer, build_message};
+use async_trait::async_trait;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor, message::Mailbox};
+use std::fs;
+
+// writes every mail as `<uuid>.eml` into a directory, for dev and tests
+pub struct FileMailer {
+    transport: AsyncFileTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl FileMailer {
+    pub fn new(dir: &str, from: Mailbox) -> Result<Self, MailError> {
+        fs::create_dir_all(dir)?;
+        Ok(FileMailer {
+            transport: AsyncFileTransport::new(dir),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: &Mail) -> Result<(), MailError> {
+        let message = build_message(&self.from, mail)?;
+        let id = self.transport.send(message).await?;
+        tracing::debug!("mail to {} written as {}.eml", mail.to, id);
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use crate::config::config::MailQueueConfig;
+use crate::mail::mailer::{Mail, MailError, Mailer};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+
+// hands mails to a background worker so requests never wait on the mail server
+#[derive(Clone)]
+pub struct MailQueue {
+    sender: mpsc::Sender<Mail>,
+}
+
+impl MailQueue {
+    // must be called inside the tokio runtime
+    pub fn start(mailer: Arc<dyn Mailer>, config: &MailQueueConfig) -> Self {
+        let (sender, receiver) = mpsc::channel(config.capacity.max(1));
+        tokio::spawn(run(
+            mailer,
+            receiver,
+            config.max_retries,
+            Duration::from_millis(config.retry_backoff_ms),
+        ));
+        MailQueue { sender }
+    }
+
+    pub fn enqueue(&self, mail: Mail) -> Result<(), MailError> {
+        self.sender.try_send(mail).map_err(|e| {
+            tracing::error!("mail queue error: {}", e);
+            Box::new(e) as MailError
+        })
+    }
+}
+
+async fn run(
+    mailer: Arc<dyn Mailer>,
+    mut receiver: mpsc::Receiver<Mail>,
+    max_retries: u32,
+    backoff: Duration,
+) {
+    while let Some(mail) = receiver.recv().await {
+        // retries of one mail must not hold up the rest of the queue
+        tokio::spawn(deliver(mailer.clone(), mail, max_retries, backoff));
+    }
+}
+
+// exponential backoff: backoff, 2 * backoff, 4 * backoff ...
+async fn deliver(mailer: Arc<dyn Mailer>, mail: Mail, max_retries: u32, backoff: Duration) {
+    let mut attempt = 0;
+    loop {
+        match mailer.send(&mail).await {
+            Ok(()) => {
+                tracing::info!("mail \"{}\" sent to {}", mail.subject, mail.to);
+                return;
+            }
+            Err(e) if attempt < max_retries => {
+                let delay = backoff * 2u32.saturating_pow(attempt);
+                tracing::warn!(
+                    "mail to {} failed (attempt {}): {}, retrying in {:?}",
+                    mail.to,
+                    attempt + 1,
+                    e,
+                    delay
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                tracing::error!(
+                    "mail to {} dropped after {} attempts: {}",
+                    mail.to,
+                    attempt + 1,
+                    e
+                );
+                return;
+            }
+        }
+    }
+}
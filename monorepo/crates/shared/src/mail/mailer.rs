@@ -0,0 +1,53 @@
+use crate::config::config::MailConfig;
+use crate::mail::{file_mailer::FileMailer, smtp_mailer::SmtpMailer};
+use async_trait::async_trait;
+use lettre::{
+    Message,
+    message::{Mailbox, MultiPart, header::ContentType},
+};
+use std::sync::Arc;
+
+pub type MailError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: Option<String>,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: &Mail) -> Result<(), MailError>;
+}
+
+// picks the backend configured in `mail.backend`
+pub fn create_mailer(config: &MailConfig) -> Result<Arc<dyn Mailer>, MailError> {
+    let from: Mailbox = config.from.parse()?;
+
+    match config.backend.as_str() {
+        "smtp" => Ok(Arc::new(SmtpMailer::new(&config.smtp, from)?)),
+        "file" => Ok(Arc::new(FileMailer::new(&config.file.dir, from)?)),
+        backend => Err(format!("unknown mail backend `{}`", backend).into()),
+    }
+}
+
+pub fn build_message(from: &Mailbox, mail: &Mail) -> Result<Message, MailError> {
+    let builder = Message::builder()
+        .from(from.clone())
+        .to(mail.to.parse()?)
+        .subject(&mail.subject);
+
+    let message = match &mail.html {
+        Some(html) => builder.multipart(MultiPart::alternative_plain_html(
+            mail.text.clone(),
+            html.clone(),
+        ))?,
+        None => builder
+            .header(ContentType::TEXT_PLAIN)
+            .body(mail.text.clone())?,
+    };
+
+    Ok(message)
+}
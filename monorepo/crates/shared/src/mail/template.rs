@@ -0,0 +1,158 @@
+use crate::i18n::i18n;
+use crate::mail::mailer::Mail;
+use serde::Deserialize;
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+#[derive(Debug, Deserialize)]
+struct MailTemplate {
+    subject: String,
+    text: String,
+    html: Option<String>,
+}
+
+// `{{name}}` placeholders are replaced by the render variables
+#[derive(Debug)]
+pub struct MailTemplates {
+    templates: HashMap<String, MailTemplate>,
+}
+
+impl MailTemplates {
+    // `verify_email.yaml` is the fallback, `verify_email.zh-CN.yaml` the zh-CN variant
+    pub fn load(dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut templates = HashMap::new();
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yaml") {
+                continue;
+            }
+            let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let key = match stem.split_once('.') {
+                Some((name, locale)) => format!("{}.{}", name, i18n::normalize(locale)),
+                None => stem.to_string(),
+            };
+
+            let content = fs::read_to_string(&path)?;
+            let template: MailTemplate = serde_yml::from_str(&content).map_err(|e| {
+                tracing::error!("Failed to parse mail template {}: {}", path.display(), e);
+                Box::new(e) as Box<dyn Error>
+            })?;
+            templates.insert(key, template);
+        }
+
+        Ok(MailTemplates { templates })
+    }
+
+    pub fn render(
+        &self,
+        name: &str,
+        locale: Option<&str>,
+        to: &str,
+        vars: &[(&str, &str)],
+    ) -> Option<Mail> {
+        let template = self.lookup(name, locale)?;
+
+        Some(Mail {
+            to: to.to_string(),
+            subject: substitute(&template.subject, vars, false),
+            text: substitute(&template.text, vars, false),
+            html: template
+                .html
+                .as_ref()
+                .map(|html| substitute(html, vars, true)),
+        })
+    }
+
+    // exact locale, then the primary language, then the locale-less template
+    fn lookup(&self, name: &str, locale: Option<&str>) -> Option<&MailTemplate> {
+        if let Some(locale) = locale.map(i18n::normalize) {
+            let primary = locale.split('-').next().unwrap_or_default();
+            let localized = self
+                .templates
+                .get(&format!("{}.{}", name, locale))
+                .or_else(|| self.templates.get(&format!("{}.{}", name, primary)));
+            if localized.is_some() {
+                return localized;
+            }
+        }
+        self.templates.get(name)
+    }
+}
+
+// one scan of the template, so a value containing `{{name}}` is never expanded itself,
+// unknown placeholders are left as they are
+fn substitute(template: &str, vars: &[(&str, &str)], html: bool) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + 2 + len + 2];
+        let name = &placeholder[2..placeholder.len() - 2];
+
+        rendered.push_str(&rest[..start]);
+        match vars.iter().find(|(var, _)| *var == name) {
+            Some((_, value)) if html => rendered.push_str(&escape_html(value)),
+            Some((_, value)) => rendered.push_str(value),
+            None => rendered.push_str(placeholder),
+        }
+        rest = &rest[start + placeholder.len()..];
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_every_occurrence() {
+        let rendered = substitute(
+            "Hi {{username}}, open {{link}} ({{link}})",
+            &[("username", "bob"), ("link", "https://x/t")],
+            false,
+        );
+        assert_eq!(rendered, "Hi bob, open https://x/t (https://x/t)");
+    }
+
+    #[test]
+    fn values_are_not_expanded_again() {
+        let rendered = substitute(
+            "Hi {{username}}, your link is {{link}}",
+            &[("username", "{{link}}"), ("link", "https://x/secret")],
+            false,
+        );
+        assert_eq!(rendered, "Hi {{link}}, your link is https://x/secret");
+    }
+
+    #[test]
+    fn keeps_unknown_and_unclosed_placeholders() {
+        let rendered = substitute("{{unknown}} {{name}} {{name", &[("name", "bob")], false);
+        assert_eq!(rendered, "{{unknown}} bob {{name");
+    }
+
+    #[test]
+    fn escapes_values_in_html() {
+        let rendered = substitute("<p>{{name}}</p>", &[("name", "<b>\"a\" & 'b'</b>")], true);
+        assert_eq!(
+            rendered,
+            "<p>&lt;b&gt;&quot;a&quot; &amp; &#39;b&#39;&lt;/b&gt;</p>"
+        );
+    }
+}
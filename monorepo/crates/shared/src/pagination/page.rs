@@ -0,0 +1,78 @@
+use crate::pagination::{keyset::encode_cursor, page_request::PageRequest};
+use serde::Serialize;
+
+// payload of a list endpoint, carried as `Reply::data`
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    // pass back as `cursor` for the next page, absent on the last one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    // rows across every page, only counted for offset pages
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<i64>,
+}
+
+impl<T> Page<T> {
+    // `rows` were fetched in keyset order with `PageRequest::fetch_limit`,
+    // `id` reads the snowflake id the rows are paged on
+    pub fn new(
+        mut rows: Vec<T>,
+        page: &PageRequest,
+        id: impl Fn(&T) -> i64,
+        total: Option<i64>,
+    ) -> Self {
+        let has_more = rows.len() as i64 > page.limit();
+        rows.truncate(page.limit() as usize);
+        let next_cursor = rows
+            .last()
+            .filter(|_| has_more)
+            .map(|last| encode_cursor(id(last)));
+
+        Page {
+            items: rows,
+            next_cursor,
+            total,
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            total: self.total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pagination::keyset::decode_cursor;
+
+    #[test]
+    fn extra_row_yields_a_cursor_to_the_last_item() {
+        let page = PageRequest::Offset {
+            limit: 2,
+            offset: 0,
+        };
+        let page = Page::new(vec![30, 20, 10], &page, |id| *id, Some(3));
+        assert_eq!(page.items, vec![30, 20]);
+        assert_eq!(
+            page.next_cursor.as_deref().and_then(decode_cursor),
+            Some(20)
+        );
+        assert_eq!(page.total, Some(3));
+    }
+
+    #[test]
+    fn last_page_has_no_cursor() {
+        let page = PageRequest::Cursor {
+            limit: 2,
+            after: 30,
+        };
+        let page = Page::new(vec![20, 10], &page, |id| *id, None).map(|id| id * 2);
+        assert_eq!(page.items, vec![40, 20]);
+        assert_eq!(page.next_cursor, None);
+    }
+}
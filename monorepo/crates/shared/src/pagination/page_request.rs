@@ -0,0 +1,240 @@
+use crate::error::error::AppError;
+use crate::pagination::keyset::decode_cursor;
+use crate::reply::reply::{FieldError, FieldErrors};
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 20;
+pub const MAX_PAGE_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+struct PageQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    cursor: Option<String>,
+}
+
+// `?limit=&offset=` or `?limit=&cursor=` where the cursor is a previous page's `next_cursor`,
+// without either the first page is served by offset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageRequest {
+    Offset { limit: i64, offset: i64 },
+    Cursor { limit: i64, after: i64 },
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        PageRequest::Offset {
+            limit: DEFAULT_PAGE_LIMIT,
+            offset: 0,
+        }
+    }
+}
+
+impl PageRequest {
+    pub fn limit(&self) -> i64 {
+        match self {
+            PageRequest::Offset { limit, .. } | PageRequest::Cursor { limit, .. } => *limit,
+        }
+    }
+
+    // one row more than the page holds, so `Page::new` can tell whether another page follows
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit() + 1
+    }
+
+    pub fn offset(&self) -> i64 {
+        match self {
+            PageRequest::Offset { offset, .. } => *offset,
+            PageRequest::Cursor { .. } => 0,
+        }
+    }
+
+    // id of the last row of the previous page
+    pub fn after(&self) -> Option<i64> {
+        match self {
+            PageRequest::Offset { .. } => None,
+            PageRequest::Cursor { after, .. } => Some(*after),
+        }
+    }
+
+    // a limit above the maximum is lowered to it, anything else invalid is rejected
+    fn from_query(query: PageQuery) -> Result<Self, AppError> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .min(MAX_PAGE_LIMIT);
+        if limit < 1 {
+            let params = BTreeMap::from([
+                ("min".to_string(), Value::from(1)),
+                ("max".to_string(), Value::from(MAX_PAGE_LIMIT)),
+            ]);
+            return Err(invalid("limit", "range", None, params));
+        }
+
+        match (query.offset, query.cursor) {
+            (Some(_), Some(_)) => Err(invalid(
+                "cursor",
+                "conflict",
+                Some("use either offset or cursor".to_string()),
+                BTreeMap::new(),
+            )),
+            (_, Some(cursor)) => decode_cursor(&cursor)
+                .map(|after| PageRequest::Cursor { limit, after })
+                .ok_or_else(|| invalid("cursor", "invalid_cursor", None, BTreeMap::new())),
+            (Some(offset), None) if offset < 0 => {
+                let params = BTreeMap::from([("min".to_string(), Value::from(0))]);
+                Err(invalid("offset", "range", None, params))
+            }
+            (offset, None) => Ok(PageRequest::Offset {
+                limit,
+                offset: offset.unwrap_or(0),
+            }),
+        }
+    }
+
+    // counting every row defeats the point of a keyset page, so only offset pages report `total`
+    pub fn wants_total(&self) -> bool {
+        matches!(self, PageRequest::Offset { .. })
+    }
+}
+
+impl<S> FromRequestParts<S> for PageRequest
+where
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query) = Query::<PageQuery>::from_request_parts(parts, state)
+            .await
+            .map_err(|e| {
+                tracing::error!("query error: {}", e);
+                invalid(
+                    "query",
+                    "invalid_query",
+                    Some(e.body_text()),
+                    BTreeMap::new(),
+                )
+            })?;
+
+        PageRequest::from_query(query)
+    }
+}
+
+fn invalid(
+    field: &str,
+    code: &str,
+    message: Option<String>,
+    params: BTreeMap<String, Value>,
+) -> AppError {
+    let mut errors = FieldErrors::new();
+    errors.insert(
+        field.to_string(),
+        vec![FieldError {
+            code: code.to_string(),
+            message,
+            params,
+        }],
+    );
+    AppError::validation(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pagination::keyset::encode_cursor;
+
+    fn query(limit: Option<i64>, offset: Option<i64>, cursor: Option<&str>) -> PageQuery {
+        PageQuery {
+            limit,
+            offset,
+            cursor: cursor.map(str::to_string),
+        }
+    }
+
+    fn rejected_field(result: Result<PageRequest, AppError>) -> (String, String) {
+        match result {
+            Err(AppError::Validation { errors, .. }) => {
+                let (field, errors) = errors.into_iter().next().unwrap();
+                (field, errors[0].code.clone())
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn defaults_to_the_first_offset_page() {
+        assert_eq!(
+            PageRequest::from_query(query(None, None, None)).unwrap(),
+            PageRequest::default()
+        );
+    }
+
+    #[test]
+    fn limit_is_clamped_to_the_maximum() {
+        let page = PageRequest::from_query(query(Some(MAX_PAGE_LIMIT + 1), None, None)).unwrap();
+        assert_eq!(page.limit(), MAX_PAGE_LIMIT);
+        assert_eq!(page.fetch_limit(), MAX_PAGE_LIMIT + 1);
+
+        let page = PageRequest::from_query(query(Some(5), Some(10), None)).unwrap();
+        assert_eq!(
+            page,
+            PageRequest::Offset {
+                limit: 5,
+                offset: 10
+            }
+        );
+    }
+
+    #[test]
+    fn non_positive_limit_is_rejected() {
+        for limit in [0, -1] {
+            let result = PageRequest::from_query(query(Some(limit), None, None));
+            assert_eq!(rejected_field(result), ("limit".into(), "range".into()));
+        }
+    }
+
+    #[test]
+    fn negative_offset_is_rejected() {
+        let result = PageRequest::from_query(query(None, Some(-1), None));
+        assert_eq!(rejected_field(result), ("offset".into(), "range".into()));
+    }
+
+    #[test]
+    fn offset_and_cursor_together_are_rejected() {
+        let cursor = encode_cursor(42);
+        let result = PageRequest::from_query(query(None, Some(0), Some(&cursor)));
+        assert_eq!(rejected_field(result), ("cursor".into(), "conflict".into()));
+    }
+
+    #[test]
+    fn cursor_page_starts_after_the_cursor() {
+        let cursor = encode_cursor(42);
+        let page = PageRequest::from_query(query(Some(10), None, Some(&cursor))).unwrap();
+        assert_eq!(
+            page,
+            PageRequest::Cursor {
+                limit: 10,
+                after: 42
+            }
+        );
+        assert_eq!(page.after(), Some(42));
+        assert_eq!(page.offset(), 0);
+        assert!(!page.wants_total());
+    }
+
+    #[test]
+    fn invalid_cursor_is_rejected() {
+        let result = PageRequest::from_query(query(None, None, Some("bogus")));
+        assert_eq!(
+            rejected_field(result),
+            ("cursor".into(), "invalid_cursor".into())
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use crate::config::config::PasswordPolicyConfig;
+use crate::constants::constants;
+use crate::error::error::AppError;
+use crate::reply::reply::{FieldError, FieldErrors};
+use serde_json::Value;
+use std::{collections::BTreeMap, collections::HashSet, error::Error, fs, path::Path};
+
+// bcrypt only reads this many bytes, the rest of a longer password would not count
+const BCRYPT_MAX_BYTES: usize = 72;
+
+// shared by register, reset and change-password so every path enforces the same rules
+#[derive(Debug)]
+pub struct PasswordPolicy {
+    config: PasswordPolicyConfig,
+    // lowercased, compared case-insensitively
+    breached: HashSet<String>,
+}
+
+impl PasswordPolicy {
+    // `breached_list` is resolved against `dir`, blank lines and `#` comments are skipped
+    pub fn load(config: PasswordPolicyConfig, dir: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut breached = HashSet::new();
+
+        if !config.breached_list.is_empty() {
+            let path = dir.join(&config.breached_list);
+            let content = fs::read_to_string(&path).map_err(|e| {
+                tracing::error!(
+                    "Failed to read breached passwords {}: {}",
+                    path.display(),
+                    e
+                );
+                Box::new(e) as Box<dyn Error>
+            })?;
+            breached.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_lowercase),
+            );
+        }
+
+        Ok(PasswordPolicy { config, breached })
+    }
+
+    // `user_inputs` are the username and email, violations are reported against `field`
+    pub fn check(&self, field: &str, password: &str, user_inputs: &[&str]) -> Result<(), AppError> {
+        let violations = self.violations(password, user_inputs);
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        let mut errors = FieldErrors::new();
+        errors.insert(field.to_string(), violations);
+        Err(AppError::Validation {
+            code: constants::CODE_WEAK_PASSWORD,
+            detail: None,
+            errors,
+        })
+    }
+
+    fn violations(&self, password: &str, user_inputs: &[&str]) -> Vec<FieldError> {
+        let config = &self.config;
+        let mut violations = Vec::new();
+
+        let length = password.chars().count();
+        if length < config.min_length {
+            violations.push(violation("too_short", &[("min", config.min_length.into())]));
+        }
+        let too_long = length > config.max_length || password.len() > BCRYPT_MAX_BYTES;
+        if length > config.max_length {
+            violations.push(violation("too_long", &[("max", config.max_length.into())]));
+        } else if password.len() > BCRYPT_MAX_BYTES {
+            // multi-byte characters reach the bcrypt limit before `max_length`
+            violations.push(violation(
+                "too_long",
+                &[("max_bytes", BCRYPT_MAX_BYTES.into())],
+            ));
+        }
+
+        let has = |matches: fn(char) -> bool| password.chars().any(matches);
+        let classes = [
+            (
+                config.require_lowercase,
+                "missing_lowercase",
+                has(char::is_lowercase),
+            ),
+            (
+                config.require_uppercase,
+                "missing_uppercase",
+                has(char::is_uppercase),
+            ),
+            (
+                config.require_digit,
+                "missing_digit",
+                has(|c| c.is_ascii_digit()),
+            ),
+            (
+                config.require_symbol,
+                "missing_symbol",
+                has(|c| !c.is_alphanumeric()),
+            ),
+        ];
+        for (required, code, present) in classes {
+            if required && !present {
+                violations.push(violation(code, &[]));
+            }
+        }
+
+        let lowered = password.to_lowercase();
+        if config.disallow_user_info && contains_user_info(&lowered, user_inputs) {
+            violations.push(violation("contains_user_info", &[]));
+        }
+
+        if self.breached.contains(&lowered) {
+            violations.push(violation("breached", &[]));
+        }
+
+        // too long passwords are already rejected and are expensive to score
+        if config.min_strength_score > 0 && !too_long {
+            let score = u8::from(zxcvbn::zxcvbn(password, user_inputs).score());
+            if score < config.min_strength_score {
+                violations.push(violation(
+                    "too_weak",
+                    &[
+                        ("score", score.into()),
+                        ("min_score", config.min_strength_score.into()),
+                    ],
+                ));
+            }
+        }
+
+        violations
+    }
+}
+
+// the email counts both whole and by its local part, fragments under 3 chars are ignored
+fn contains_user_info(lowered: &str, user_inputs: &[&str]) -> bool {
+    user_inputs
+        .iter()
+        .flat_map(|input| {
+            let input = input.to_lowercase();
+            let local = input.split_once('@').map(|(local, _)| local.to_string());
+            std::iter::once(input).chain(local)
+        })
+        .filter(|fragment| fragment.chars().count() >= 3)
+        .any(|fragment| lowered.contains(&fragment))
+}
+
+fn violation(code: &str, params: &[(&str, Value)]) -> FieldError {
+    FieldError {
+        code: code.to_string(),
+        message: None,
+        params: params
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect::<BTreeMap<_, _>>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every rule off, tests enable the one they cover
+    fn config() -> PasswordPolicyConfig {
+        PasswordPolicyConfig {
+            min_length: 1,
+            max_length: 64,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            disallow_user_info: false,
+            min_strength_score: 0,
+            breached_list: String::new(),
+        }
+    }
+
+    fn policy(config: PasswordPolicyConfig) -> PasswordPolicy {
+        PasswordPolicy {
+            config,
+            breached: HashSet::new(),
+        }
+    }
+
+    fn codes(policy: &PasswordPolicy, password: &str, user_inputs: &[&str]) -> Vec<String> {
+        policy
+            .violations(password, user_inputs)
+            .into_iter()
+            .map(|violation| violation.code)
+            .collect()
+    }
+
+    #[test]
+    fn length_is_counted_in_chars() {
+        let policy = policy(PasswordPolicyConfig {
+            min_length: 8,
+            max_length: 10,
+            ..config()
+        });
+        assert_eq!(codes(&policy, "short", &[]), ["too_short"]);
+        assert_eq!(codes(&policy, "ääääääää", &[]), Vec::<String>::new());
+        assert_eq!(codes(&policy, "much too long", &[]), ["too_long"]);
+    }
+
+    #[test]
+    fn bcrypt_byte_limit_applies_within_max_length() {
+        let policy = policy(config());
+        // 30 chars but 90 bytes
+        let password = "密".repeat(30);
+        let violations = policy.violations(&password, &[]);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].code, "too_long");
+        assert_eq!(violations[0].params["max_bytes"], BCRYPT_MAX_BYTES);
+        assert!(codes(&policy, &"a".repeat(BCRYPT_MAX_BYTES - 8), &[]).is_empty());
+    }
+
+    #[test]
+    fn character_classes() {
+        let policy = policy(PasswordPolicyConfig {
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..config()
+        });
+        assert_eq!(
+            codes(&policy, "abc", &[]),
+            ["missing_uppercase", "missing_digit", "missing_symbol"]
+        );
+        assert_eq!(codes(&policy, "ABC1!", &[]), ["missing_lowercase"]);
+        assert!(codes(&policy, "aB3$", &[]).is_empty());
+    }
+
+    #[test]
+    fn user_info_matches_username_and_email_local_part() {
+        let policy = policy(PasswordPolicyConfig {
+            disallow_user_info: true,
+            ..config()
+        });
+        let inputs = ["Alice", "bob.smith@example.com"];
+        assert_eq!(codes(&policy, "xxALICExx", &inputs), ["contains_user_info"]);
+        assert_eq!(
+            codes(&policy, "bob.smith99", &inputs),
+            ["contains_user_info"]
+        );
+        assert!(codes(&policy, "unrelated", &inputs).is_empty());
+        // fragments under 3 chars would reject far too much
+        assert!(codes(&policy, "joe-ab", &["ab", "ab@x.com"]).is_empty());
+    }
+
+    #[test]
+    fn breached_list_is_case_insensitive() {
+        let mut policy = policy(config());
+        policy.breached.insert("password1".to_string());
+        assert_eq!(codes(&policy, "PassWord1", &[]), ["breached"]);
+        assert!(codes(&policy, "password2", &[]).is_empty());
+    }
+
+    #[test]
+    fn strength_score_threshold() {
+        let policy = policy(PasswordPolicyConfig {
+            min_strength_score: 3,
+            ..config()
+        });
+        assert_eq!(codes(&policy, "abc123", &[]), ["too_weak"]);
+        assert!(codes(&policy, "correct horse battery staple", &[]).is_empty());
+    }
+
+    #[test]
+    fn check_reports_violations_against_the_field() {
+        let policy = policy(PasswordPolicyConfig {
+            min_length: 8,
+            ..config()
+        });
+        assert!(policy.check("password", "long enough", &[]).is_ok());
+        match policy.check("new_password", "short", &[]) {
+            Err(AppError::Validation { code, errors, .. }) => {
+                assert_eq!(code, constants::CODE_WEAK_PASSWORD);
+                assert_eq!(errors["new_password"][0].code, "too_short");
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+}
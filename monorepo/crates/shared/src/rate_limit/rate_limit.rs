@@ -0,0 +1,367 @@
+use crate::config::config::{RateLimitConfig, RateLimitRule};
+use crate::constants::constants;
+use crate::crypto::crypto::sha256_hex;
+use crate::error::error::AppError;
+use crate::extract::client_ip::TrustedProxies;
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+// buckets beyond this trigger a sweep of the idle ones
+const SWEEP_THRESHOLD: usize = 10_000;
+
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+// maps request headers to a user id, `None` for anonymous requests
+pub type UserIdentifier = Arc<dyn Fn(&HeaderMap) -> Option<String> + Send + Sync>;
+
+// GCRA over in-process buckets, each bucket only stores its theoretical arrival time
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    identify_user: Option<UserIdentifier>,
+    trusted_proxies: TrustedProxies,
+    epoch: Instant,
+    // "<rule>:<key>" -> theoretical arrival time, in nanoseconds since `epoch`
+    buckets: Mutex<HashMap<String, u64>>,
+}
+
+// outcome of the most constraining rule, rendered as `RateLimit-*` headers
+struct Decision {
+    // index into the checked buckets of the rule this decision comes from
+    bucket: usize,
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    reset: u64,
+    retry_after: u64,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            config,
+            identify_user: None,
+            trusted_proxies: TrustedProxies::default(),
+            epoch: Instant::now(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // needed by rules keyed by `user`, without it they fall back to the client ip
+    pub fn with_user_identifier(mut self, identify_user: UserIdentifier) -> Self {
+        self.identify_user = Some(identify_user);
+        self
+    }
+
+    // without it `ip` keys are the peer address, which is the proxy when behind one
+    pub fn with_trusted_proxies(mut self, trusted_proxies: TrustedProxies) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+
+    // the global rule and the matching route rules, each with a stable bucket prefix
+    fn rules<'a>(
+        &'a self,
+        method: &'a str,
+        path: &'a str,
+    ) -> impl Iterator<Item = (String, &'a RateLimitRule)> {
+        let routes = self
+            .config
+            .routes
+            .iter()
+            .enumerate()
+            .filter(move |(_, rule)| {
+                rule.path.as_deref() == Some(path)
+                    && rule
+                        .method
+                        .as_deref()
+                        .is_none_or(|expected| expected.eq_ignore_ascii_case(method))
+            })
+            .map(|(index, rule)| (format!("route{}", index), rule));
+        std::iter::once(("global".to_string(), &self.config.global)).chain(routes)
+    }
+
+    fn key(&self, rule: &RateLimitRule, headers: &HeaderMap, addr: Option<SocketAddr>) -> String {
+        let identity = match rule.key.as_str() {
+            "user" => self
+                .identify_user
+                .as_ref()
+                .and_then(|identify| identify(headers))
+                .map(|user| format!("user:{}", user)),
+            // only a digest is kept in memory
+            "api_key" => headers
+                .get(self.config.api_key_header.as_str())
+                .and_then(|value| value.to_str().ok())
+                .map(|api_key| format!("api_key:{}", sha256_hex(api_key))),
+            _ => None,
+        };
+
+        identity.unwrap_or_else(|| match addr {
+            Some(addr) => format!("ip:{}", self.trusted_proxies.client_ip(addr.ip(), headers)),
+            None => "ip:unknown".to_string(),
+        })
+    }
+
+    // every rule must admit the request, buckets are only charged when all of them do
+    fn check(&self, buckets: &[(&RateLimitRule, String)]) -> Option<Decision> {
+        self.check_at(buckets, self.epoch.elapsed().as_nanos() as u64)
+    }
+
+    // `now` in nanoseconds since `epoch`
+    fn check_at(&self, buckets: &[(&RateLimitRule, String)], now: u64) -> Option<Decision> {
+        let mut state = self.buckets.lock().unwrap();
+
+        if state.len() >= SWEEP_THRESHOLD {
+            state.retain(|_, tat| *tat > now);
+        }
+
+        let mut updates = Vec::with_capacity(buckets.len());
+        let mut decisions = Vec::with_capacity(buckets.len());
+        for (bucket, (rule, key)) in buckets.iter().enumerate() {
+            let requests = rule.requests.max(1);
+            let period = Duration::from_secs(rule.period_secs).as_nanos() as u64;
+            let interval = period / requests as u64;
+
+            let tat = state.get(key).copied().unwrap_or(now).max(now);
+            let new_tat = tat + interval;
+            if new_tat > now + period {
+                decisions.push(Decision {
+                    bucket,
+                    allowed: false,
+                    limit: requests,
+                    remaining: 0,
+                    reset: ceil_secs(tat - now),
+                    retry_after: ceil_secs(new_tat - (now + period)),
+                });
+            } else {
+                updates.push((key, new_tat));
+                decisions.push(Decision {
+                    bucket,
+                    allowed: true,
+                    limit: requests,
+                    remaining: ((now + period - new_tat) / interval.max(1)) as u32,
+                    reset: ceil_secs(new_tat - now),
+                    retry_after: 0,
+                });
+            }
+        }
+
+        if decisions.iter().all(|decision| decision.allowed) {
+            for (key, tat) in updates {
+                state.insert(key.clone(), tat);
+            }
+            decisions
+                .into_iter()
+                .min_by_key(|decision| decision.remaining)
+        } else {
+            decisions
+                .into_iter()
+                .filter(|decision| !decision.allowed)
+                .max_by_key(|decision| decision.retry_after)
+        }
+    }
+}
+
+fn ceil_secs(nanos: u64) -> u64 {
+    nanos.div_ceil(1_000_000_000)
+}
+
+// usage: `router.layer(middleware::from_fn_with_state(limiter, rate_limit))`
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !limiter.config.enabled {
+        return next.run(req).await;
+    }
+
+    // route patterns rather than raw paths, so `/users/{id}` shares one quota
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|matched| matched.as_str().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let addr = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+
+    let buckets: Vec<_> = limiter
+        .rules(req.method().as_str(), &path)
+        .map(|(prefix, rule)| {
+            let key = format!("{}:{}", prefix, limiter.key(rule, req.headers(), addr));
+            (rule, key)
+        })
+        .collect();
+    let Some(decision) = limiter.check(&buckets) else {
+        return next.run(req).await;
+    };
+
+    let mut response = if decision.allowed {
+        next.run(req).await
+    } else {
+        let (rule, key) = &buckets[decision.bucket];
+        tracing::warn!(
+            "rate limited {} for {}, {} requests per {}s",
+            path,
+            key,
+            rule.requests,
+            rule.period_secs
+        );
+        AppError::too_many_requests(constants::CODE_TOO_MANY_REQUESTS, decision.retry_after)
+            .into_response()
+    };
+
+    let headers = response.headers_mut();
+    headers.insert(RATELIMIT_LIMIT, HeaderValue::from(decision.limit));
+    headers.insert(RATELIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(RATELIMIT_RESET, HeaderValue::from(decision.reset));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn rule(path: Option<&str>, requests: u32, period_secs: u64) -> RateLimitRule {
+        RateLimitRule {
+            path: path.map(str::to_string),
+            method: None,
+            key: "ip".to_string(),
+            requests,
+            period_secs,
+        }
+    }
+
+    fn limiter(global: RateLimitRule, routes: Vec<RateLimitRule>) -> RateLimiter {
+        RateLimiter::new(RateLimitConfig {
+            enabled: true,
+            api_key_header: "x-api-key".to_string(),
+            global,
+            routes,
+        })
+    }
+
+    #[test]
+    fn burst_then_retry_after() {
+        let limiter = limiter(rule(None, 5, 10), vec![]);
+        let global = rule(None, 5, 10);
+        let buckets = [(&global, "global:ip:1.2.3.4".to_string())];
+
+        for expected in (0..5).rev() {
+            let decision = limiter.check_at(&buckets, 0).unwrap();
+            assert!(decision.allowed);
+            assert_eq!(decision.limit, 5);
+            assert_eq!(decision.remaining, expected);
+        }
+
+        // one request is earned back every 2s
+        let decision = limiter.check_at(&buckets, 0).unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.retry_after, 2);
+        assert_eq!(decision.reset, 10);
+
+        let decision = limiter.check_at(&buckets, SECOND / 2).unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, 2);
+    }
+
+    #[test]
+    fn refills_one_request_per_interval() {
+        let limiter = limiter(rule(None, 5, 10), vec![]);
+        let global = rule(None, 5, 10);
+        let buckets = [(&global, "global:ip:1.2.3.4".to_string())];
+        for _ in 0..5 {
+            assert!(limiter.check_at(&buckets, 0).unwrap().allowed);
+        }
+
+        let decision = limiter.check_at(&buckets, 2 * SECOND).unwrap();
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert!(!limiter.check_at(&buckets, 2 * SECOND).unwrap().allowed);
+
+        // a full period restores the whole burst
+        let decision = limiter.check_at(&buckets, 12 * SECOND).unwrap();
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 4);
+    }
+
+    #[test]
+    fn keys_are_independent() {
+        let limiter = limiter(rule(None, 1, 60), vec![]);
+        let global = rule(None, 1, 60);
+        let first = [(&global, "global:ip:1.1.1.1".to_string())];
+        let second = [(&global, "global:ip:2.2.2.2".to_string())];
+
+        assert!(limiter.check_at(&first, 0).unwrap().allowed);
+        assert!(!limiter.check_at(&first, 0).unwrap().allowed);
+        assert!(limiter.check_at(&second, 0).unwrap().allowed);
+    }
+
+    #[test]
+    fn reports_the_rejecting_bucket_without_charging_the_others() {
+        let global = rule(None, 100, 60);
+        let login = rule(Some("/login"), 2, 60);
+        let limiter = limiter(global.clone(), vec![login.clone()]);
+        let buckets = [
+            (&global, "global:ip:1.2.3.4".to_string()),
+            (&login, "route0:ip:1.2.3.4".to_string()),
+        ];
+
+        for _ in 0..2 {
+            let decision = limiter.check_at(&buckets, 0).unwrap();
+            assert!(decision.allowed);
+            assert_eq!(decision.bucket, 1);
+        }
+        let decision = limiter.check_at(&buckets, 0).unwrap();
+        assert!(!decision.allowed);
+        assert_eq!(decision.bucket, 1);
+        assert_eq!(decision.retry_after, 30);
+
+        // the rejected request did not consume the global quota
+        let decision = limiter.check_at(&buckets[..1], 0).unwrap();
+        assert_eq!(decision.remaining, 97);
+    }
+
+    #[test]
+    fn ip_keys_follow_trusted_proxies() {
+        let global = rule(None, 100, 60);
+        let peer = Some(SocketAddr::from(([10, 0, 0, 1], 443)));
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", HeaderValue::from_static("1.1.1.1"));
+
+        let direct = limiter(global.clone(), vec![]);
+        assert_eq!(direct.key(&global, &headers, peer), "ip:10.0.0.1");
+
+        let proxied = limiter(global.clone(), vec![])
+            .with_trusted_proxies(TrustedProxies::new(vec!["10.0.0.0/8".parse().unwrap()]));
+        assert_eq!(proxied.key(&global, &headers, peer), "ip:1.1.1.1");
+    }
+
+    #[test]
+    fn matches_route_rules_by_path_and_method() {
+        let mut post_login = rule(Some("/login"), 2, 60);
+        post_login.method = Some("POST".to_string());
+        let limiter = limiter(rule(None, 100, 60), vec![post_login]);
+
+        let prefixes: Vec<_> = limiter.rules("post", "/login").map(|(p, _)| p).collect();
+        assert_eq!(prefixes, ["global", "route0"]);
+        let prefixes: Vec<_> = limiter.rules("GET", "/login").map(|(p, _)| p).collect();
+        assert_eq!(prefixes, ["global"]);
+    }
+}
@@ -1,19 +1,40 @@
 use crate::constants::constants;
+use crate::i18n::i18n;
 use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+// field name -> every rule the field failed
+pub type FieldErrors = BTreeMap<String, Vec<FieldError>>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, Value>,
+}
 
 #[derive(Serialize)]
 pub struct Reply<T> {
     pub code: u16,
     pub msg: String,
     pub data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<FieldErrors>,
 }
 
 impl<T> Reply<T> {
     pub fn success(data: T) -> Self {
         Self {
             code: constants::CODE_SUCCESS,
-            msg: constants::get_string_value(constants::CODE_SUCCESS).to_string(),
+            msg: i18n::message(constants::CODE_SUCCESS),
             data: Some(data),
+            detail: None,
+            errors: None,
         }
     }
 
@@ -30,8 +51,10 @@ impl<T> Reply<T> {
     pub fn error(code: u16) -> Self {
         Self {
             code,
-            msg: constants::get_string_value(code).to_string(),
+            msg: i18n::message(code),
             data: None,
+            detail: None,
+            errors: None,
         }
     }
 }
@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE refresh_tokens (
+    id BIGINT PRIMARY KEY,                    -- jti，雪花算法生成
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    family_id BIGINT NOT NULL,                -- 令牌家族，同一次登录轮换出的令牌共享
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL, -- 过期时间
+    used_at TIMESTAMP WITH TIME ZONE,         -- 已轮换时间，再次使用视为重放
+    revoked_at TIMESTAMP WITH TIME ZONE,      -- 吊销时间
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP -- 签发时间
+);
+
+CREATE INDEX idx_refresh_tokens_family_id ON refresh_tokens (family_id);
+CREATE INDEX idx_refresh_tokens_user_id ON refresh_tokens (user_id);
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE users
+    ADD COLUMN token_version INTEGER NOT NULL DEFAULT 0; -- 令牌版本，递增后之前签发的 access token 全部失效
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE users
+    ADD COLUMN locale VARCHAR(16);            -- 用户偏好语言，为空时使用 Accept-Language
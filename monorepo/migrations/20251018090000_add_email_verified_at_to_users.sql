@@ -0,0 +1,6 @@
+-- Add migration script here
+ALTER TABLE users
+    ADD COLUMN email_verified_at TIMESTAMP WITH TIME ZONE; -- 邮箱验证时间，为空表示未验证
+
+-- 已有账号视为已验证
+UPDATE users SET email_verified_at = created_at;
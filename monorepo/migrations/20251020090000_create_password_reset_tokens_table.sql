@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE password_reset_tokens (
+    id BIGINT PRIMARY KEY,                    -- 雪花算法生成
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) NOT NULL UNIQUE,   -- 令牌的 SHA-256，明文只出现在邮件中
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL, -- 过期时间
+    used_at TIMESTAMP WITH TIME ZONE,         -- 使用时间，令牌只能使用一次
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP -- 签发时间
+);
+
+CREATE INDEX idx_password_reset_tokens_user_id ON password_reset_tokens (user_id);
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE login_attempts (
+    key VARCHAR(320) PRIMARY KEY,             -- account:<email> 或 ip:<地址>
+    failures INTEGER NOT NULL DEFAULT 0,      -- 连续失败次数
+    last_failure_at TIMESTAMP WITH TIME ZONE NOT NULL, -- 最近一次失败时间
+    locked_until TIMESTAMP WITH TIME ZONE     -- 锁定截止时间
+);
@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE user_totp (
+    user_id BIGINT PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    secret VARCHAR(64) NOT NULL,              -- base32 编码的 TOTP 密钥
+    enabled_at TIMESTAMP WITH TIME ZONE,      -- 确认时间，NULL 表示尚未确认
+    last_used_step BIGINT NOT NULL DEFAULT 0, -- 最近一次使用的时间步，防止验证码重放
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP -- 创建时间
+);
+
+CREATE TABLE recovery_codes (
+    id BIGINT PRIMARY KEY,                    -- 雪花算法生成
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,           -- 恢复码的 SHA-256
+    used_at TIMESTAMP WITH TIME ZONE,         -- 使用时间，恢复码只能使用一次
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP, -- 创建时间
+    UNIQUE (user_id, code_hash)
+);
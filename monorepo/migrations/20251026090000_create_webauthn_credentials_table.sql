@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE webauthn_credentials (
+    id BIGINT PRIMARY KEY,                    -- 雪花算法生成
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    credential_id BYTEA NOT NULL UNIQUE,      -- 认证器生成的凭证 ID
+    name VARCHAR(64) NOT NULL,                -- 用户为通行密钥起的名称
+    passkey JSONB NOT NULL,                   -- 序列化的凭证，包含公钥
+    sign_count BIGINT NOT NULL DEFAULT 0,     -- 签名计数器，用于发现被克隆的认证器
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP, -- 注册时间
+    last_used_at TIMESTAMP WITH TIME ZONE     -- 最近一次登录时间
+);
+
+CREATE INDEX idx_webauthn_credentials_user_id ON webauthn_credentials (user_id);
@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE user_identities (
+    id BIGINT PRIMARY KEY,                    -- 雪花算法生成
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    provider VARCHAR(64) NOT NULL,            -- 配置中的身份提供方名称
+    subject VARCHAR(255) NOT NULL,            -- 身份提供方的用户标识 (sub)
+    email VARCHAR(255),                       -- 最近一次登录时提供方返回的邮箱
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP, -- 关联时间
+    last_login_at TIMESTAMP WITH TIME ZONE,   -- 最近一次登录时间
+    UNIQUE (provider, subject)
+);
+
+CREATE INDEX idx_user_identities_user_id ON user_identities (user_id);
@@ -0,0 +1,49 @@
+-- Add migration script here
+CREATE TABLE oauth_clients (
+    id BIGINT PRIMARY KEY,                    -- 雪花算法生成
+    client_id VARCHAR(64) NOT NULL UNIQUE,    -- 对外公开的客户端标识
+    client_secret_hash VARCHAR(64),           -- 客户端密钥的 SHA-256，NULL 表示公开客户端
+    name VARCHAR(128) NOT NULL,               -- 授权页展示的应用名称
+    redirect_uris TEXT[] NOT NULL DEFAULT '{}', -- 允许的回调地址，精确匹配
+    grant_types TEXT[] NOT NULL,              -- 允许的授权类型
+    scopes TEXT[] NOT NULL,                   -- 允许申请的权限范围
+    created_by BIGINT REFERENCES users (id) ON DELETE SET NULL, -- 注册该客户端的管理员
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP -- 注册时间
+);
+
+CREATE TABLE oauth_authorization_codes (
+    code_hash VARCHAR(64) PRIMARY KEY,        -- 授权码的 SHA-256
+    grant_id BIGINT NOT NULL,                 -- 授权 ID，由同一授权换取的令牌共享
+    client_id BIGINT NOT NULL REFERENCES oauth_clients (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    redirect_uri TEXT NOT NULL,               -- 换取令牌时必须一致
+    scope TEXT NOT NULL,                      -- 用户同意的权限范围，空格分隔
+    code_challenge VARCHAR(128),              -- PKCE S256 challenge
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL, -- 过期时间
+    used_at TIMESTAMP WITH TIME ZONE,         -- 使用时间，再次使用视为重放
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP -- 签发时间
+);
+
+CREATE TABLE oauth_consents (
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    client_id BIGINT NOT NULL REFERENCES oauth_clients (id) ON DELETE CASCADE,
+    scope TEXT NOT NULL,                      -- 已同意的权限范围，空格分隔
+    granted_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP, -- 最近一次同意时间
+    PRIMARY KEY (user_id, client_id)
+);
+
+CREATE TABLE oauth_tokens (
+    id BIGINT PRIMARY KEY,                    -- 雪花算法生成
+    token_hash VARCHAR(64) NOT NULL UNIQUE,   -- 不透明令牌的 SHA-256
+    token_type VARCHAR(16) NOT NULL,          -- access 或 refresh
+    grant_id BIGINT NOT NULL,                 -- 授权 ID，吊销刷新令牌时一并吊销
+    client_id BIGINT NOT NULL REFERENCES oauth_clients (id) ON DELETE CASCADE,
+    user_id BIGINT REFERENCES users (id) ON DELETE CASCADE, -- client_credentials 授权为 NULL
+    scope TEXT NOT NULL,                      -- 权限范围，空格分隔
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL, -- 过期时间
+    used_at TIMESTAMP WITH TIME ZONE,         -- 刷新令牌已轮换时间，再次使用视为重放
+    revoked_at TIMESTAMP WITH TIME ZONE,      -- 吊销时间
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP -- 签发时间
+);
+
+CREATE INDEX idx_oauth_tokens_grant_id ON oauth_tokens (grant_id);
@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE signing_keys (
+    kid VARCHAR(64) PRIMARY KEY,              -- JWT 头部的 kid
+    algorithm VARCHAR(16) NOT NULL,           -- EdDSA / RS256
+    private_key TEXT NOT NULL,                -- PKCS#8 PEM 私钥
+    public_jwk JSONB NOT NULL,                -- 公布在 JWKS 中的公钥
+    activates_at TIMESTAMP WITH TIME ZONE NOT NULL, -- 开始用于签名的时间, 之前只公布不签名
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP -- 生成时间
+);
+
+CREATE INDEX idx_signing_keys_activates_at ON signing_keys (activates_at);
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE oauth_authorization_codes
+    ADD COLUMN nonce VARCHAR(255),            -- 客户端传入的 nonce，原样写入 ID Token
+    ADD COLUMN auth_time TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP; -- 用户登录时间
@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE sessions (
+    id BIGINT PRIMARY KEY,                    -- 即 refresh_tokens.family_id，一次登录一个会话
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    user_agent VARCHAR(512),                  -- 客户端 User-Agent
+    ip VARCHAR(45),                           -- 最近一次使用的客户端 IP
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP, -- 登录时间
+    last_seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP, -- 最近一次刷新令牌时间
+    revoked_at TIMESTAMP WITH TIME ZONE       -- 注销时间
+);
+
+CREATE INDEX idx_sessions_user_id ON sessions (user_id);
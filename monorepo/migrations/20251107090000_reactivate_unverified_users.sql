@@ -0,0 +1,3 @@
+-- Add migration script here
+-- 未验证邮箱改由 email_verified_at 单独表示，is_active 只表示是否被管理员停用
+UPDATE users SET is_active = TRUE WHERE email_verified_at IS NULL AND is_active = FALSE;
@@ -0,0 +1,3 @@
+-- Add migration script here
+ALTER TABLE users
+    ADD COLUMN pending_email VARCHAR(255);    -- 待验证的新邮箱，验证通过后才替换 email